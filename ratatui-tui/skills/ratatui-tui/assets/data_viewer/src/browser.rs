@@ -0,0 +1,504 @@
+//! Directory listing for the file panel
+//!
+//! Reads a directory into entries with their size, modification time and
+//! (for data files small enough to scan quickly) row count, then filters and
+//! sorts them according to the panel's display options. SQLite databases
+//! can be expanded to list their tables and views.
+
+use crate::{columnar, compression, jsonpath, jsonstream, spreadsheet, sqlite};
+use anyhow::{Context, Result};
+use serde::de::IgnoredAny;
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    fs,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Data files larger than this on disk are not scanned for a row count
+const ROW_COUNT_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// What an entry in the listing refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// The `..` entry leading to the parent directory
+    Parent,
+    Dir,
+    File,
+    /// A SQLite database, expandable into its tables and views
+    Database,
+    /// A table or view listed under its database
+    Table,
+    View,
+}
+
+/// One entry of a directory listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    /// Name shown in the list (`..` for the parent entry)
+    pub name: String,
+    pub kind: EntryKind,
+    /// Size on disk, for files
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    /// Number of data rows, when the file is a small enough JSON or CSV file
+    /// or a table of a small enough database
+    pub rows: Option<usize>,
+}
+
+impl FileEntry {
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+}
+
+/// Order of entries within the directories and files groups
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Size,
+            SortKey::Size => SortKey::Modified,
+            SortKey::Modified => SortKey::Name,
+        }
+    }
+}
+
+/// Display options of the file panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListOptions {
+    pub sort: SortKey,
+    /// Reverse the sort order (largest or newest first)
+    pub descending: bool,
+    /// Show entries whose name starts with a dot
+    pub show_hidden: bool,
+    /// Show every file rather than only data files
+    pub show_all: bool,
+    /// Only show files whose name matches this glob (directories are kept)
+    pub glob: Option<String>,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        ListOptions {
+            sort: SortKey::Name,
+            descending: false,
+            show_hidden: false,
+            show_all: false,
+            glob: None,
+        }
+    }
+}
+
+impl ListOptions {
+    /// Short summary shown under the file panel, e.g. "name ↑, hidden, *.csv"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!(
+            "{} {}",
+            self.sort.label(),
+            if self.descending { "↓" } else { "↑" }
+        )];
+        if self.show_hidden {
+            parts.push("hidden".to_string());
+        }
+        if self.show_all {
+            parts.push("all files".to_string());
+        }
+        if let Some(glob) = &self.glob {
+            parts.push(glob.clone());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Whether a file can be opened by the viewer
+pub fn is_supported(path: &Path) -> bool {
+    let (ext, _) = compression::split_extension(path);
+    matches!(ext.as_str(), "json" | "ndjson" | "jsonl" | "csv")
+        || spreadsheet::is_spreadsheet(path)
+        || columnar::is_columnar(path)
+}
+
+/// List a directory: `..` first, then directories, then files
+pub fn list_dir(dir: &Path, options: &ListOptions) -> Result<Vec<FileEntry>> {
+    let mut entries = Vec::new();
+
+    if let Some(parent) = dir.parent() {
+        entries.push(FileEntry {
+            path: parent.to_path_buf(),
+            name: "..".to_string(),
+            kind: EntryKind::Parent,
+            size: None,
+            modified: None,
+            rows: None,
+        });
+    }
+
+    let read = fs::read_dir(dir).context("Failed to read directory")?;
+
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in read.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') && !options.show_hidden {
+            continue;
+        }
+        let metadata = fs::metadata(&path).ok();
+        let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+
+        if path.is_dir() {
+            dirs.push(FileEntry {
+                path,
+                name,
+                kind: EntryKind::Dir,
+                size: None,
+                modified,
+                rows: None,
+            });
+            continue;
+        }
+
+        let database = sqlite::is_database(&path);
+        let supported = is_supported(&path);
+        if !supported && !database && !options.show_all {
+            continue;
+        }
+        if let Some(glob) = &options.glob {
+            if !glob_match(glob, &name) {
+                continue;
+            }
+        }
+        let size = metadata.as_ref().map(|m| m.len());
+        let rows = match size {
+            Some(size) if supported && size <= ROW_COUNT_LIMIT => count_rows(&path),
+            _ => None,
+        };
+        files.push(FileEntry {
+            path,
+            name,
+            kind: if database {
+                EntryKind::Database
+            } else {
+                EntryKind::File
+            },
+            size,
+            modified,
+            rows,
+        });
+    }
+
+    sort_entries(&mut dirs, options);
+    sort_entries(&mut files, options);
+
+    entries.extend(dirs);
+    entries.extend(files);
+    Ok(entries)
+}
+
+/// List the tables and views of a database, as children of its entry
+///
+/// Tables of a database small enough to scan quickly get a row count.
+pub fn list_database(db: &Path) -> Result<Vec<FileEntry>> {
+    let small = fs::metadata(db).is_ok_and(|m| m.len() <= ROW_COUNT_LIMIT);
+    let objects = sqlite::objects(db)?;
+    Ok(objects
+        .into_iter()
+        .map(|object| {
+            let (kind, rows) = match object.kind {
+                sqlite::ObjectKind::Table => (
+                    EntryKind::Table,
+                    small
+                        .then(|| sqlite::count(db, &object.name).ok())
+                        .flatten(),
+                ),
+                sqlite::ObjectKind::View => (EntryKind::View, None),
+            };
+            FileEntry {
+                path: sqlite::object_path(db, &object.name),
+                name: object.name,
+                kind,
+                size: None,
+                modified: None,
+                rows,
+            }
+        })
+        .collect())
+}
+
+/// Sort entries by the chosen key, falling back to the name for ties
+fn sort_entries(entries: &mut [FileEntry], options: &ListOptions) {
+    entries.sort_by(|a, b| {
+        let by_key = match options.sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        };
+        let order = by_key.then_with(|| a.name.cmp(&b.name));
+        if options.descending {
+            order.reverse()
+        } else {
+            order
+        }
+    });
+}
+
+/// Count the data rows of a JSON or CSV file
+///
+/// CSV files count records after the header; JSON files count the elements
+/// of a top-level array, or of the array a JSON path would suggest; NDJSON
+/// files count their non-blank lines.
+fn count_rows(path: &Path) -> Option<usize> {
+    let (ext, _) = compression::split_extension(path);
+    let reader = compression::open(path).ok()?;
+    match ext.as_str() {
+        "csv" => {
+            let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+            let mut record = csv::ByteRecord::new();
+            let mut count = 0;
+            while reader.read_byte_record(&mut record).ok()? {
+                count += 1;
+            }
+            Some(count)
+        }
+        "json" => {
+            let mut count = 0;
+            let streamed =
+                jsonstream::for_each_element(BufReader::new(reader), |_: IgnoredAny| count += 1);
+            match streamed.ok()? {
+                Some(_) => Some(count),
+                None => {
+                    let value: Value =
+                        serde_json::from_reader(compression::open(path).ok()?).ok()?;
+                    jsonpath::suggest(&value).map(|(_, len)| len)
+                }
+            }
+        }
+        "ndjson" | "jsonl" => {
+            let mut count = 0;
+            for line in BufReader::new(reader).lines() {
+                if !line.ok()?.trim().is_empty() {
+                    count += 1;
+                }
+            }
+            Some(count)
+        }
+        _ => None,
+    }
+}
+
+/// Match a file name against a shell-style glob
+///
+/// Supports `*`, `?` and `[abc]`/`[a-z]`/`[!abc]` classes; matching ignores
+/// case so `*.CSV` finds `data.csv`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // Backtracking over the most recent `*` is enough for shell globs
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            Some('[') => {
+                if let Some((matched, next)) = match_class(&pattern, p, name[n]) {
+                    if matched {
+                        p = next;
+                        n += 1;
+                        continue;
+                    }
+                } else if name[n] == '[' {
+                    p += 1;
+                    n += 1;
+                    continue;
+                }
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match star {
+            Some((star_p, star_n)) => {
+                p = star_p + 1;
+                n = star_n + 1;
+                star = Some((star_p, star_n + 1));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match `c` against the `[...]` class starting at `start`, returning whether
+/// it matched and the index after the class; `None` if the class is unclosed
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<(bool, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match pattern.get(i) {
+            None => return None,
+            Some(']') if !first => break,
+            Some(&lo) => {
+                if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|c| *c != ']')
+                {
+                    let hi = pattern[i + 2];
+                    matched |= lo <= c && c <= hi;
+                    i += 3;
+                } else {
+                    matched |= lo == c;
+                    i += 1;
+                }
+            }
+        }
+        first = false;
+    }
+    Some((matched != negated, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("data_viewer_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.csv", "events.csv"));
+        assert!(glob_match("*.CSV", "events.csv"));
+        assert!(!glob_match("*.csv", "events.csv.gz"));
+        assert!(glob_match("*.csv*", "events.csv.gz"));
+        assert!(glob_match("ev?nts*", "events.json"));
+        assert!(glob_match("log-[0-9]*", "log-2024.csv"));
+        assert!(!glob_match("log-[!0-9]*", "log-2024.csv"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("[x", "[x"));
+    }
+
+    #[test]
+    fn test_list_dir_options() {
+        let dir = temp_dir("browser_list");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("b.csv"), "id,name\n1,a\n2,b\n3,c\n").unwrap();
+        fs::write(dir.join("a.json"), r#"{"data": [{"id": 1}, {"id": 2}]}"#).unwrap();
+        fs::write(dir.join("notes.txt"), "hello").unwrap();
+        fs::write(dir.join(".hidden.csv"), "x\n1\n").unwrap();
+        fs::write(dir.join("c.ndjson"), "{\"id\": 1}\n\n{\"id\": 2}\n").unwrap();
+
+        let names = |options: &ListOptions| {
+            list_dir(&dir, options)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.name)
+                .collect::<Vec<_>>()
+        };
+
+        let mut options = ListOptions::default();
+        assert_eq!(
+            names(&options),
+            ["..", "sub", "a.json", "b.csv", "c.ndjson"]
+        );
+
+        options.show_hidden = true;
+        options.show_all = true;
+        assert_eq!(
+            names(&options),
+            [
+                "..",
+                "sub",
+                ".hidden.csv",
+                "a.json",
+                "b.csv",
+                "c.ndjson",
+                "notes.txt"
+            ]
+        );
+
+        options.sort = SortKey::Size;
+        options.descending = true;
+        options.glob = Some("*.csv".to_string());
+        assert_eq!(names(&options), ["..", "sub", "b.csv", ".hidden.csv"]);
+
+        let entries = list_dir(&dir, &ListOptions::default()).unwrap();
+        assert_eq!(entries[0].kind, EntryKind::Parent);
+        assert_eq!(entries[0].path, dir.parent().unwrap());
+        let rows: Vec<_> = entries.iter().map(|e| e.rows).collect();
+        assert_eq!(rows, [None, None, Some(2), Some(3), Some(2)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_database() {
+        let dir = temp_dir("browser_database");
+        let db = dir.join("app.sqlite");
+        rusqlite::Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE users (id INTEGER); INSERT INTO users VALUES (1), (2);
+                 CREATE VIEW ids AS SELECT id FROM users;",
+            )
+            .unwrap();
+
+        let entries = list_dir(&dir, &ListOptions::default()).unwrap();
+        assert_eq!(entries[1].name, "app.sqlite");
+        assert_eq!(entries[1].kind, EntryKind::Database);
+
+        let children = list_database(&db).unwrap();
+        let listed: Vec<_> = children
+            .iter()
+            .map(|e| (e.name.as_str(), e.kind, e.rows))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("ids", EntryKind::View, None),
+                ("users", EntryKind::Table, Some(2))
+            ]
+        );
+        assert_eq!(children[1].path, db.join("users"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,295 @@
+//! Data extraction for the chart tab
+//!
+//! Turns table columns into plottable points (numbers or timestamps against
+//! an x column or the row index), value counts for bar charts, and the axis
+//! bounds and labels derived from them.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+
+/// How a chart is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Line,
+    Scatter,
+    /// Value counts of the first series column
+    Bar,
+}
+
+impl ChartKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChartKind::Line => "line",
+            ChartKind::Scatter => "scatter",
+            ChartKind::Bar => "bar",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ChartKind::Line => ChartKind::Scatter,
+            ChartKind::Scatter => ChartKind::Bar,
+            ChartKind::Bar => ChartKind::Line,
+        }
+    }
+}
+
+/// What the values on the x axis represent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XScale {
+    /// Position of the row in the table
+    RowIndex,
+    /// Plain numbers from the x column
+    Number,
+    /// Timestamps from the x column, as seconds since the Unix epoch
+    Time,
+}
+
+/// Chart configuration chosen in the chart tab
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartSettings {
+    pub kind: ChartKind,
+    /// Column plotted on the x axis; `None` plots against the row index
+    pub x: Option<usize>,
+    /// Columns plotted as series on the y axis
+    pub series: Vec<usize>,
+    /// Visible x range when zoomed or panned; `None` shows everything
+    pub window: Option<(f64, f64)>,
+}
+
+impl Default for ChartSettings {
+    fn default() -> Self {
+        ChartSettings {
+            kind: ChartKind::Line,
+            x: None,
+            series: Vec::new(),
+            window: None,
+        }
+    }
+}
+
+impl ChartSettings {
+    /// Narrow (factor < 1) or widen (factor > 1) the window around its center
+    pub fn zoom(&mut self, full: (f64, f64), factor: f64) {
+        let (lo, hi) = self.window.unwrap_or(full);
+        let center = (lo + hi) / 2.0;
+        let half = ((hi - lo) / 2.0 * factor).max(f64::EPSILON);
+        self.window = clamp_window((center - half, center + half), full);
+    }
+
+    /// Shift the window by a fraction of its width
+    pub fn pan(&mut self, full: (f64, f64), fraction: f64) {
+        let (lo, hi) = self.window.unwrap_or(full);
+        let shift = (hi - lo) * fraction;
+        self.window = clamp_window((lo + shift, hi + shift), full);
+    }
+}
+
+/// Keep a window inside the full range; a window covering everything is `None`
+fn clamp_window(window: (f64, f64), full: (f64, f64)) -> Option<(f64, f64)> {
+    let width = (window.1 - window.0).min(full.1 - full.0);
+    if width >= full.1 - full.0 {
+        return None;
+    }
+    let lo = window.0.clamp(full.0, full.1 - width);
+    Some((lo, lo + width))
+}
+
+/// Parse a cell as a number
+pub fn parse_number(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Parse a cell as a timestamp, returning seconds since the Unix epoch
+pub fn parse_timestamp(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.timestamp_millis() as f64 / 1000.0);
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y/%m/%d %H:%M:%S",
+    ] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt.and_utc().timestamp_millis() as f64 / 1000.0);
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp() as f64)
+}
+
+/// Decide how to read the x column: numbers if every value parses as one,
+/// otherwise timestamps if they do
+pub fn x_scale(rows: &[&Vec<String>], x: Option<usize>) -> XScale {
+    let Some(col) = x else {
+        return XScale::RowIndex;
+    };
+    let values: Vec<&str> = rows
+        .iter()
+        .filter_map(|r| r.get(col).map(String::as_str))
+        .filter(|v| !v.trim().is_empty())
+        .collect();
+    if !values.is_empty() && values.iter().all(|v| parse_number(v).is_some()) {
+        XScale::Number
+    } else if !values.is_empty() && values.iter().all(|v| parse_timestamp(v).is_some()) {
+        XScale::Time
+    } else {
+        XScale::RowIndex
+    }
+}
+
+/// Points of one series, skipping rows where either coordinate is not numeric
+pub fn series_points(
+    rows: &[&Vec<String>],
+    x: Option<usize>,
+    scale: XScale,
+    y: usize,
+) -> Vec<(f64, f64)> {
+    rows.iter()
+        .enumerate()
+        .filter_map(|(i, row)| {
+            let x_value = match (scale, x) {
+                (XScale::Number, Some(col)) => parse_number(row.get(col)?)?,
+                (XScale::Time, Some(col)) => parse_timestamp(row.get(col)?)?,
+                _ => i as f64,
+            };
+            Some((x_value, parse_number(row.get(y)?)?))
+        })
+        .collect()
+}
+
+/// Minimum and maximum of some values, widened when they are all equal
+pub fn bounds(values: impl Iterator<Item = f64>) -> Option<(f64, f64)> {
+    let (lo, hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+        (lo.min(v), hi.max(v))
+    });
+    if lo > hi {
+        None
+    } else if lo == hi {
+        Some((lo - 1.0, hi + 1.0))
+    } else {
+        Some((lo, hi))
+    }
+}
+
+/// Labels for the low, middle and high ends of an axis
+pub fn axis_labels((lo, hi): (f64, f64), scale: XScale) -> Vec<String> {
+    [lo, (lo + hi) / 2.0, hi]
+        .iter()
+        .map(|&v| format_axis_value(v, scale, hi - lo))
+        .collect()
+}
+
+/// Format an axis value; timestamps show the date, and the time of day when
+/// the visible span is short
+fn format_axis_value(value: f64, scale: XScale, span: f64) -> String {
+    match scale {
+        XScale::Time => match DateTime::from_timestamp(value as i64, 0) {
+            Some(dt) if span < 3.0 * 86_400.0 => dt.format("%m-%d %H:%M").to_string(),
+            Some(dt) => dt.format("%Y-%m-%d").to_string(),
+            None => format!("{:.0}", value),
+        },
+        _ if value.abs() >= 1e6 || (value != 0.0 && value.abs() < 1e-2) => {
+            format!("{:.2e}", value)
+        }
+        _ if value.fract() == 0.0 => format!("{:.0}", value),
+        _ => format!("{:.2}", value),
+    }
+}
+
+/// Count occurrences of each value in a column, most frequent first
+pub fn value_counts(rows: &[&Vec<String>], col: usize, limit: usize) -> Vec<(String, u64)> {
+    let mut counts: HashMap<&str, u64> = HashMap::new();
+    for row in rows {
+        if let Some(value) = row.get(col) {
+            *counts.entry(value.as_str()).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(String, u64)> = counts
+        .into_iter()
+        .map(|(value, count)| (value.to_string(), count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(limit);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(data: &[&[&str]]) -> Vec<Vec<String>> {
+        data.iter()
+            .map(|r| r.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_series_against_row_index_and_number() {
+        let data = rows(&[&["1", "10"], &["2", "x"], &["4", "30"]]);
+        let refs: Vec<&Vec<String>> = data.iter().collect();
+
+        assert_eq!(x_scale(&refs, None), XScale::RowIndex);
+        assert_eq!(
+            series_points(&refs, None, XScale::RowIndex, 1),
+            vec![(0.0, 10.0), (2.0, 30.0)]
+        );
+
+        assert_eq!(x_scale(&refs, Some(0)), XScale::Number);
+        assert_eq!(
+            series_points(&refs, Some(0), XScale::Number, 1),
+            vec![(1.0, 10.0), (4.0, 30.0)]
+        );
+    }
+
+    #[test]
+    fn test_timestamp_x_axis() {
+        let data = rows(&[&["2024-01-01T00:00:00Z", "1"], &["2024-01-02", "2"]]);
+        let refs: Vec<&Vec<String>> = data.iter().collect();
+
+        assert_eq!(x_scale(&refs, Some(0)), XScale::Time);
+        let points = series_points(&refs, Some(0), XScale::Time, 1);
+        assert_eq!(points[1].0 - points[0].0, 86_400.0);
+        assert_eq!(
+            axis_labels((points[0].0, points[1].0), XScale::Time),
+            vec!["01-01 00:00", "01-01 12:00", "01-02 00:00"]
+        );
+    }
+
+    #[test]
+    fn test_bounds_and_labels() {
+        assert_eq!(bounds([3.0, 1.0, 2.0].into_iter()), Some((1.0, 3.0)));
+        assert_eq!(bounds([5.0].into_iter()), Some((4.0, 6.0)));
+        assert_eq!(bounds(std::iter::empty()), None);
+        assert_eq!(
+            axis_labels((0.0, 5.0), XScale::Number),
+            vec!["0", "2.50", "5"]
+        );
+    }
+
+    #[test]
+    fn test_zoom_and_pan_stay_in_range() {
+        let mut settings = ChartSettings::default();
+        settings.zoom((0.0, 100.0), 0.5);
+        assert_eq!(settings.window, Some((25.0, 75.0)));
+
+        settings.pan((0.0, 100.0), 1.0);
+        assert_eq!(settings.window, Some((50.0, 100.0)));
+
+        settings.zoom((0.0, 100.0), 4.0);
+        assert_eq!(settings.window, None);
+    }
+
+    #[test]
+    fn test_value_counts() {
+        let data = rows(&[&["a"], &["b"], &["a"], &["c"], &["a"], &["b"]]);
+        let refs: Vec<&Vec<String>> = data.iter().collect();
+        assert_eq!(
+            value_counts(&refs, 0, 2),
+            vec![("a".to_string(), 3), ("b".to_string(), 2)]
+        );
+    }
+}
@@ -0,0 +1,429 @@
+//! Reading Parquet and Arrow IPC (Feather) files
+//!
+//! A file is opened from its metadata alone: the schema and how many rows
+//! each row group (or record batch) holds. Groups are then read one at a
+//! time as they are needed. Cells go through Arrow's JSON writer, so nested
+//! struct, list and map columns come out as JSON objects and arrays and are
+//! shown the way nested JSON is.
+
+use anyhow::{Context, Result};
+use arrow_array::{RecordBatch, RecordBatchReader};
+use arrow_ipc::reader::{FileReader, StreamReader};
+use arrow_json::{writer::JsonArray, WriterBuilder};
+use arrow_schema::SchemaRef;
+use parquet::{
+    arrow::arrow_reader::ParquetRecordBatchReaderBuilder,
+    file::reader::{FileReader as _, SerializedFileReader},
+    schema::printer::print_schema,
+};
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+/// Columnar file format, told apart by extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Parquet,
+    /// Arrow IPC, as a file (Feather v2) or a stream
+    Arrow,
+}
+
+impl Format {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Format::Parquet => "Parquet",
+            Format::Arrow => "Arrow",
+        }
+    }
+
+    /// What the format calls the chunks of rows it is read in
+    pub fn group_label(&self) -> &'static str {
+        match self {
+            Format::Parquet => "row groups",
+            Format::Arrow => "record batches",
+        }
+    }
+}
+
+/// Format of a file named like a Parquet or Arrow file
+pub fn format(path: &Path) -> Option<Format> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "parquet" | "pq" => Some(Format::Parquet),
+        "arrow" | "arrows" | "feather" | "ipc" => Some(Format::Arrow),
+        _ => None,
+    }
+}
+
+/// Whether a file is named like a Parquet or Arrow file
+pub fn is_columnar(path: &Path) -> bool {
+    format(path).is_some()
+}
+
+/// The row groups of a file and how many of them are loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowGroups {
+    pub path: PathBuf,
+    pub format: Format,
+    /// Number of rows in each group
+    pub sizes: Vec<usize>,
+    /// Groups loaded so far, from the first
+    pub loaded: usize,
+}
+
+impl RowGroups {
+    /// Number of rows in the file
+    pub fn total(&self) -> usize {
+        self.sizes.iter().sum()
+    }
+
+    pub fn remaining(&self) -> bool {
+        self.loaded < self.sizes.len()
+    }
+
+    /// Rows of the next group as text, counting it as loaded
+    pub fn read_next(&mut self) -> Result<Vec<Vec<String>>> {
+        let batches = read_batches(&self.path, self.format, Some(self.loaded))
+            .with_context(|| format!("Failed to read {} {}", self.format.label(), self.loaded))?;
+        self.loaded += 1;
+        let headers = column_names(&batches.schema);
+        Ok(objects(&batches.batches)?
+            .iter()
+            .map(|object| headers.iter().map(|h| cell_text(object.get(h))).collect())
+            .collect())
+    }
+}
+
+/// What opening a file reads: its columns, its schema as text and its row
+/// groups, none of them loaded yet
+#[derive(Debug, Clone)]
+pub struct Opened {
+    pub headers: Vec<String>,
+    pub schema: String,
+    pub groups: RowGroups,
+}
+
+/// Read the metadata of a Parquet or Arrow file
+pub fn open(path: &Path) -> Result<Opened> {
+    let format = format(path).context("Not a Parquet or Arrow file")?;
+    let (headers, schema, sizes) = match format {
+        Format::Parquet => open_parquet(path),
+        Format::Arrow => open_arrow(path),
+    }
+    .with_context(|| format!("Failed to open {} as {}", path.display(), format.label()))?;
+    Ok(Opened {
+        headers,
+        schema,
+        groups: RowGroups {
+            path: path.to_path_buf(),
+            format,
+            sizes,
+            loaded: 0,
+        },
+    })
+}
+
+/// Every row of a file as a JSON array of objects, for JSON paths to pick from
+pub fn document(path: &Path) -> Result<Value> {
+    let format = format(path).context("Not a Parquet or Arrow file")?;
+    let batches = read_batches(path, format, None)?;
+    Ok(Value::Array(
+        objects(&batches.batches)?
+            .into_iter()
+            .map(Value::Object)
+            .collect(),
+    ))
+}
+
+fn open_parquet(path: &Path) -> Result<(Vec<String>, String, Vec<usize>)> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let metadata = reader.metadata();
+    let file = metadata.file_metadata();
+    let root = file.schema_descr().root_schema();
+    let headers = root
+        .get_fields()
+        .iter()
+        .map(|field| field.name().to_string())
+        .collect();
+    let sizes: Vec<usize> = metadata
+        .row_groups()
+        .iter()
+        .map(|group| group.num_rows() as usize)
+        .collect();
+
+    let mut text = summary(Format::Parquet, &sizes);
+    if let Some(created_by) = file.created_by() {
+        text.push_str(&format!(", written by {}", created_by));
+    }
+    text.push_str("\n\n");
+    let mut schema = Vec::new();
+    print_schema(&mut schema, root);
+    text.push_str(String::from_utf8_lossy(&schema).trim_end());
+    Ok((headers, text, sizes))
+}
+
+fn open_arrow(path: &Path) -> Result<(Vec<String>, String, Vec<usize>)> {
+    let batches = read_batches(path, Format::Arrow, None)?;
+    let sizes: Vec<usize> = batches.batches.iter().map(|b| b.num_rows()).collect();
+
+    let mut lines = vec![summary(Format::Arrow, &sizes), String::new()];
+    lines.push("Columns".to_string());
+    let fields = batches.schema.fields();
+    let name_width = fields.iter().map(|f| f.name().len()).max().unwrap_or(0);
+    let type_width = fields
+        .iter()
+        .map(|f| f.data_type().to_string().len())
+        .max()
+        .unwrap_or(0);
+    for field in fields {
+        let nullable = if field.is_nullable() {
+            "nullable"
+        } else {
+            "not null"
+        };
+        lines.push(format!(
+            "  {:name_width$}  {:type_width$}  {}",
+            field.name(),
+            field.data_type().to_string(),
+            nullable
+        ));
+    }
+    Ok((column_names(&batches.schema), lines.join("\n"), sizes))
+}
+
+/// "N rows in M row groups"
+fn summary(format: Format, sizes: &[usize]) -> String {
+    format!(
+        "{} rows in {} {}",
+        sizes.iter().sum::<usize>(),
+        sizes.len(),
+        format.group_label()
+    )
+}
+
+fn column_names(schema: &SchemaRef) -> Vec<String> {
+    schema.fields().iter().map(|f| f.name().clone()).collect()
+}
+
+/// Record batches read from a file, with the schema they share
+struct Batches {
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+}
+
+/// Read one row group (or record batch) of a file, or all of them
+fn read_batches(path: &Path, format: Format, group: Option<usize>) -> Result<Batches> {
+    match format {
+        Format::Parquet => {
+            let mut builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+            if let Some(group) = group {
+                builder = builder.with_row_groups(vec![group]);
+            }
+            let reader = builder.build()?;
+            let schema = reader.schema();
+            let batches = reader.collect::<Result<_, _>>()?;
+            Ok(Batches { schema, batches })
+        }
+        // Feather files can be read from any batch on; streams only in order
+        Format::Arrow => match FileReader::try_new(File::open(path)?, None) {
+            Ok(mut reader) => {
+                let schema = reader.schema();
+                let batches = match group {
+                    Some(group) => {
+                        reader.set_index(group)?;
+                        reader.next().transpose()?.into_iter().collect()
+                    }
+                    None => reader.collect::<Result<_, _>>()?,
+                };
+                Ok(Batches { schema, batches })
+            }
+            Err(_) => {
+                let reader = StreamReader::try_new(BufReader::new(File::open(path)?), None)
+                    .context("Neither an Arrow IPC file nor stream")?;
+                let schema = reader.schema();
+                let batches = match group {
+                    Some(group) => reader.skip(group).take(1).collect::<Result<_, _>>()?,
+                    None => reader.collect::<Result<_, _>>()?,
+                };
+                Ok(Batches { schema, batches })
+            }
+        },
+    }
+}
+
+/// Rows of record batches as JSON objects, with nulls kept
+fn objects(batches: &[RecordBatch]) -> Result<Vec<serde_json::Map<String, Value>>> {
+    let mut writer = WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, JsonArray>(Vec::new());
+    writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+    writer.finish()?;
+    let json = writer.into_inner();
+    if json.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// A cell as the table shows it, with nested values summarized like JSON
+fn cell_text(value: Option<&Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(Value::Null) => "null".to_string(),
+        Some(Value::Bool(b)) => b.to_string(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(arr)) => format!("[{} items]", arr.len()),
+        Some(Value::Object(obj)) => format!("{{{} keys}}", obj.len()),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use arrow_array::{
+        builder::{ListBuilder, StringBuilder},
+        ArrayRef, Int64Array, StringArray, StructArray,
+    };
+    use arrow_schema::{DataType, Field};
+    use parquet::{arrow::ArrowWriter, file::properties::WriterProperties};
+    use std::{fs, sync::Arc};
+
+    /// Five orders with a nested customer struct and a list of tags
+    fn batch() -> RecordBatch {
+        let ids = Int64Array::from(vec![1, 2, 3, 4, 5]);
+        let names = StringArray::from(vec![Some("ann"), Some("bob"), None, Some("cy"), Some("di")]);
+        let customer = StructArray::from(vec![
+            (
+                Arc::new(Field::new("name", DataType::Utf8, true)),
+                Arc::new(names) as ArrayRef,
+            ),
+            (
+                Arc::new(Field::new("vip", DataType::Int64, false)),
+                Arc::new(Int64Array::from(vec![0, 1, 0, 0, 1])) as ArrayRef,
+            ),
+        ]);
+        let mut tags = ListBuilder::new(StringBuilder::new());
+        for row in [&["a", "b"][..], &[], &["c"], &["d", "e", "f"], &[]] {
+            for tag in row {
+                tags.values().append_value(tag);
+            }
+            tags.append(true);
+        }
+        RecordBatch::try_from_iter(vec![
+            ("id", Arc::new(ids) as ArrayRef),
+            ("customer", Arc::new(customer) as ArrayRef),
+            ("tags", Arc::new(tags.finish()) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    /// Write the orders as Parquet in row groups of two rows
+    pub(crate) fn write_parquet(path: &Path) {
+        let batch = batch();
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .build();
+        let mut writer = ArrowWriter::try_new(
+            File::create(path).unwrap(),
+            batch.schema(),
+            Some(properties),
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_read_parquet() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_parquet_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("orders.parquet");
+        write_parquet(&path);
+
+        let mut opened = open(&path).unwrap();
+        assert_eq!(opened.headers, ["id", "customer", "tags"]);
+        assert_eq!(opened.groups.sizes, [2, 2, 1]);
+        assert_eq!(opened.groups.total(), 5);
+        assert!(opened
+            .schema
+            .starts_with("5 rows in 3 row groups, written by parquet-rs"));
+        assert!(opened.schema.contains("REQUIRED INT64 id;"));
+        assert!(opened.schema.contains("OPTIONAL BYTE_ARRAY name (STRING);"));
+        assert!(opened.schema.contains("LIST"));
+
+        let rows = opened.groups.read_next().unwrap();
+        assert_eq!(
+            rows,
+            [
+                ["1", "{2 keys}", "[2 items]"],
+                ["2", "{2 keys}", "[0 items]"]
+            ]
+        );
+        assert_eq!(opened.groups.read_next().unwrap()[0][0], "3");
+        assert_eq!(
+            opened.groups.read_next().unwrap(),
+            [["5", "{2 keys}", "[0 items]"]]
+        );
+        assert!(!opened.groups.remaining());
+
+        let document = document(&path).unwrap();
+        assert_eq!(
+            document[2]["customer"],
+            serde_json::json!({"name": null, "vip": 0})
+        );
+        assert_eq!(document[3]["tags"], serde_json::json!(["d", "e", "f"]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_arrow() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_arrow_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let batch = batch();
+        let file = dir.join("orders.feather");
+        let mut writer =
+            arrow_ipc::writer::FileWriter::try_new(File::create(&file).unwrap(), &batch.schema())
+                .unwrap();
+        writer.write(&batch.slice(0, 3)).unwrap();
+        writer.write(&batch.slice(3, 2)).unwrap();
+        writer.finish().unwrap();
+        let stream = dir.join("orders.arrows");
+        let mut writer = arrow_ipc::writer::StreamWriter::try_new(
+            File::create(&stream).unwrap(),
+            &batch.schema(),
+        )
+        .unwrap();
+        writer.write(&batch.slice(0, 3)).unwrap();
+        writer.write(&batch.slice(3, 2)).unwrap();
+        writer.finish().unwrap();
+
+        for path in [file, stream] {
+            let mut opened = open(&path).unwrap();
+            assert_eq!(opened.groups.sizes, [3, 2]);
+            assert!(opened
+                .schema
+                .starts_with("5 rows in 2 record batches\n\nColumns\n  id "));
+            assert!(opened.schema.contains("  id        Int64"));
+            opened.groups.loaded = 1;
+            let rows = opened.groups.read_next().unwrap();
+            assert_eq!(
+                rows,
+                [
+                    ["4", "{2 keys}", "[3 items]"],
+                    ["5", "{2 keys}", "[0 items]"]
+                ]
+            );
+        }
+        assert!(open(&dir.join("missing.arrow")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
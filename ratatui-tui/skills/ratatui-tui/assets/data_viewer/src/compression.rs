@@ -0,0 +1,229 @@
+//! Transparent decompression of compressed data files
+//!
+//! Compression is recognised from the outer extension (`events.csv.gz`), and
+//! the file is decoded as a stream straight into the CSV parser and the JSON
+//! array reader. Only files that need transcoding, and JSON documents other
+//! than a top-level array, are decompressed into memory whole.
+
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+/// Compression wrapper around a data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    /// Map a file extension to a compression format
+    fn from_extension(ext: &str) -> Self {
+        match ext {
+            "gz" | "gzip" => Compression::Gzip,
+            "zst" | "zstd" => Compression::Zstd,
+            "bz2" => Compression::Bzip2,
+            "xz" => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    /// Short name shown in titles
+    pub fn label(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+        }
+    }
+}
+
+/// Split a path into its data format extension and compression,
+/// e.g. `events.csv.gz` becomes `("csv", Gzip)`
+pub fn split_extension(path: &Path) -> (String, Compression) {
+    let ext = |p: &Path| {
+        p.extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    };
+
+    let outer = ext(path);
+    match Compression::from_extension(&outer) {
+        Compression::None => (outer, Compression::None),
+        compression => (ext(&path.with_extension("")), compression),
+    }
+}
+
+/// Open a file, wrapping it in a streaming decoder when compressed
+pub fn open(path: &Path) -> Result<Box<dyn Read>> {
+    let file = BufReader::new(File::open(path).context("Failed to open file")?);
+    let (_, compression) = split_extension(path);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        Compression::Zstd => {
+            Box::new(zstd::Decoder::with_buffer(file).context("Failed to start zstd decoder")?)
+        }
+        Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(file)),
+    })
+}
+
+/// Reader that counts the bytes passing through it, to learn the
+/// decompressed size of a file while it is parsed
+pub struct Counted<R> {
+    pub inner: R,
+    pub bytes: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+/// Read a whole file, decompressing it on the way in
+pub fn read(path: &Path) -> Result<Vec<u8>> {
+    let (_, compression) = split_extension(path);
+    let mut bytes = Vec::new();
+    open(path)?
+        .read_to_end(&mut bytes)
+        .with_context(|| match compression {
+            Compression::None => "Failed to read file".to_string(),
+            _ => format!("Failed to decompress {} data", compression.label()),
+        })?;
+    Ok(bytes)
+}
+
+/// On-disk and (when known exactly) decompressed size of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeInfo {
+    pub compressed: u64,
+    pub uncompressed: Option<u64>,
+}
+
+/// Look up a file's sizes without decompressing it
+///
+/// Compressed files report `None` until they are loaded. Neither format
+/// records the total reliably: gzip's trailer only holds the size of its last
+/// member modulo 4 GiB, and a zstd frame header only covers that frame.
+pub fn size_info(path: &Path) -> Option<SizeInfo> {
+    let compressed = fs::metadata(path).ok()?.len();
+    let (_, compression) = split_extension(path);
+    let uncompressed = (compression == Compression::None).then_some(compressed);
+    Some(SizeInfo {
+        compressed,
+        uncompressed,
+    })
+}
+
+/// Format a byte count for display, e.g. `1.5K`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, UNITS[0])
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("data_viewer_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_split_extension() {
+        assert_eq!(
+            split_extension(Path::new("events.csv.gz")),
+            ("csv".to_string(), Compression::Gzip)
+        );
+        assert_eq!(
+            split_extension(Path::new("dump.JSON.zst")),
+            ("json".to_string(), Compression::Zstd)
+        );
+        assert_eq!(
+            split_extension(Path::new("data.csv")),
+            ("csv".to_string(), Compression::None)
+        );
+        assert_eq!(
+            split_extension(Path::new("archive.gz")),
+            (String::new(), Compression::Gzip)
+        );
+    }
+
+    #[test]
+    fn test_read_gzip_and_size_info() {
+        let path = temp_path("events.csv.gz");
+        let content = b"a,b\n1,2\n3,4\n";
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&path).unwrap(), Default::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+
+        assert_eq!(read(&path).unwrap(), content);
+        let sizes = size_info(&path).unwrap();
+        assert_eq!(sizes.compressed, fs::metadata(&path).unwrap().len());
+        assert_eq!(sizes.uncompressed, None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_zstd_bzip2_xz() {
+        let content = b"[{\"a\": 1}]";
+
+        // Concatenated frames decode as one stream; the first frame's
+        // header would only give the size of the first half
+        let zst = temp_path("data.json.zst");
+        let (first, second) = content.split_at(4);
+        let mut frames = zstd::bulk::compress(first, 3).unwrap();
+        frames.extend(zstd::bulk::compress(second, 3).unwrap());
+        fs::write(&zst, frames).unwrap();
+        assert_eq!(read(&zst).unwrap(), content);
+        assert_eq!(size_info(&zst).unwrap().uncompressed, None);
+
+        let bz2 = temp_path("data.json.bz2");
+        let mut encoder =
+            bzip2::write::BzEncoder::new(File::create(&bz2).unwrap(), Default::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(read(&bz2).unwrap(), content);
+
+        let xz = temp_path("data.json.xz");
+        let mut encoder = xz2::write::XzEncoder::new(File::create(&xz).unwrap(), 6);
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(read(&xz).unwrap(), content);
+
+        for path in [zst, bz2, xz] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5K");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0M");
+    }
+}
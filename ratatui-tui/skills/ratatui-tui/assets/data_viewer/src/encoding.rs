@@ -0,0 +1,255 @@
+//! Character encoding detection and transcoding
+//!
+//! Files are read as raw bytes, sniffed for a byte order mark, then either
+//! validated as UTF-8 or transcoded from the detected (or user chosen)
+//! encoding so the parsers always see UTF-8.
+
+use chardetng::EncodingDetector;
+use encoding_rs::{
+    Encoding, GBK, ISO_8859_15, ISO_8859_2, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252,
+};
+use std::borrow::Cow;
+
+/// Encodings the user can force with the override key, in cycle order
+pub const OVERRIDES: &[&Encoding] = &[
+    UTF_8,
+    UTF_16LE,
+    UTF_16BE,
+    WINDOWS_1252,
+    ISO_8859_15,
+    ISO_8859_2,
+    SHIFT_JIS,
+    GBK,
+];
+
+/// How the bytes of a file were interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingInfo {
+    /// Encoding used to decode the file
+    pub encoding: &'static Encoding,
+    /// Whether a byte order mark was found and stripped
+    pub bom: bool,
+    /// Whether the encoding was forced by the user rather than detected
+    pub forced: bool,
+    /// Whether malformed sequences were replaced with U+FFFD
+    pub lossy: bool,
+}
+
+impl EncodingInfo {
+    /// Text that was handed over already decoded, as by SQLite and
+    /// spreadsheet readers
+    pub fn utf8() -> Self {
+        EncodingInfo {
+            encoding: UTF_8,
+            bom: false,
+            forced: false,
+            lossy: false,
+        }
+    }
+
+    /// Short description for titles, e.g. "UTF-16LE, BOM"
+    pub fn label(&self) -> String {
+        let mut label = self.encoding.name().to_string();
+        if self.bom {
+            label.push_str(", BOM");
+        }
+        if self.forced {
+            label.push_str(", forced");
+        }
+        if self.lossy {
+            label.push_str(", lossy");
+        }
+        label
+    }
+}
+
+/// Pick the next override in the cycle; `None` means automatic detection
+pub fn next_override(current: Option<&'static Encoding>) -> Option<&'static Encoding> {
+    match current {
+        None => Some(OVERRIDES[0]),
+        Some(enc) => match OVERRIDES.iter().position(|e| *e == enc) {
+            Some(i) if i + 1 < OVERRIDES.len() => Some(OVERRIDES[i + 1]),
+            _ => None,
+        },
+    }
+}
+
+/// Guess the encoding of `bytes`, returning it with the length of any BOM
+pub fn detect(bytes: &[u8]) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return (encoding, bom_len);
+    }
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return (encoding, 0);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0);
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    (detector.guess(None, true), 0)
+}
+
+/// Detect UTF-16 without a BOM from the position of NUL bytes in ASCII-heavy text
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.len() < 4 {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let even_nuls = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_nuls = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count();
+
+    if odd_nuls * 2 > pairs && even_nuls * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_nuls * 2 > pairs && odd_nuls * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Convert file bytes to UTF-8, detecting the encoding unless one is forced
+///
+/// UTF-8 input is passed through untouched (minus any BOM) so that parsers can
+/// still report invalid sequences themselves; anything else is transcoded.
+pub fn to_utf8<'a>(
+    bytes: &'a [u8],
+    forced: Option<&'static Encoding>,
+) -> (Cow<'a, [u8]>, EncodingInfo) {
+    let (encoding, bom_len) = match forced {
+        Some(encoding) => {
+            let bom_len = match Encoding::for_bom(bytes) {
+                Some((bom_encoding, len)) if bom_encoding == encoding => len,
+                _ => 0,
+            };
+            (encoding, bom_len)
+        }
+        None => detect(bytes),
+    };
+
+    let body = &bytes[bom_len..];
+    let mut info = EncodingInfo {
+        encoding,
+        bom: bom_len > 0,
+        forced: forced.is_some(),
+        lossy: false,
+    };
+
+    if encoding == UTF_8 {
+        info.lossy = std::str::from_utf8(body).is_err();
+        return (Cow::Borrowed(body), info);
+    }
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(body);
+    info.lossy = had_errors;
+    (Cow::Owned(text.into_owned().into_bytes()), info)
+}
+
+/// How a file starting with `sample` reads when streamed without
+/// transcoding, or `None` when it is not UTF-8 and has to be decoded whole
+///
+/// The sample may end in the middle of a character. Invalid sequences later
+/// in the file are left for the streaming parser to find, after which the
+/// caller decodes the file whole with [`to_utf8`].
+pub fn utf8_stream(sample: &[u8], forced: Option<&'static Encoding>) -> Option<EncodingInfo> {
+    match forced {
+        Some(encoding) if encoding != UTF_8 => return None,
+        Some(_) => {}
+        None => {
+            if Encoding::for_bom(sample).is_some_and(|(encoding, _)| encoding != UTF_8)
+                || sniff_utf16(sample).is_some()
+            {
+                return None;
+            }
+            if std::str::from_utf8(sample).is_err_and(|e| e.error_len().is_some()) {
+                return None;
+            }
+        }
+    }
+    Some(EncodingInfo {
+        encoding: UTF_8,
+        bom: sample.starts_with(b"\xEF\xBB\xBF"),
+        forced: forced.is_some(),
+        lossy: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_bom_is_stripped() {
+        let (bytes, info) = to_utf8(b"\xEF\xBB\xBFname,age\n", None);
+        assert_eq!(&*bytes, b"name,age\n");
+        assert_eq!(info.encoding, UTF_8);
+        assert!(info.bom);
+    }
+
+    #[test]
+    fn test_utf16le_with_and_without_bom() {
+        let text: Vec<u8> = "a,b\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+
+        let mut with_bom = vec![0xFF, 0xFE];
+        with_bom.extend_from_slice(&text);
+        let (bytes, info) = to_utf8(&with_bom, None);
+        assert_eq!(&*bytes, b"a,b\n");
+        assert_eq!(info.encoding, UTF_16LE);
+        assert!(info.bom);
+
+        let (bytes, info) = to_utf8(&text, None);
+        assert_eq!(&*bytes, b"a,b\n");
+        assert_eq!(info.encoding, UTF_16LE);
+        assert!(!info.bom);
+    }
+
+    #[test]
+    fn test_windows_1252_is_transcoded() {
+        let (bytes, info) = to_utf8(b"name\nCaf\xE9 cr\xE8me br\xFBl\xE9e\n", None);
+        assert_eq!(
+            std::str::from_utf8(&bytes).unwrap(),
+            "name\nCafé crème brûlée\n"
+        );
+        assert_ne!(info.encoding, UTF_8);
+    }
+
+    #[test]
+    fn test_forced_encoding() {
+        let (bytes, info) = to_utf8(b"\xE9", Some(WINDOWS_1252));
+        assert_eq!(std::str::from_utf8(&bytes).unwrap(), "é");
+        assert!(info.forced);
+        assert_eq!(info.label(), "windows-1252, forced");
+    }
+
+    #[test]
+    fn test_utf8_stream_sample() {
+        // A sample cut inside "é" still reads as UTF-8
+        let info = utf8_stream(b"\xEF\xBB\xBF[\"caf\xC3", None).unwrap();
+        assert!(info.bom);
+        assert_eq!(utf8_stream(b"[\"caf\xE9\"]", None), None);
+        assert_eq!(utf8_stream(b"\xFF\xFE[\x00", None), None);
+        assert_eq!(utf8_stream(b"[]", Some(WINDOWS_1252)), None);
+        assert!(utf8_stream(b"[\"\xE9\"]", Some(UTF_8)).unwrap().forced);
+    }
+
+    #[test]
+    fn test_next_override_cycles_back_to_auto() {
+        let mut current = None;
+        for _ in 0..OVERRIDES.len() {
+            current = next_override(current);
+            assert!(current.is_some());
+        }
+        assert_eq!(next_override(current), None);
+    }
+}
@@ -0,0 +1,1171 @@
+//! Expression language used by the SQL console and row filters
+//!
+//! Expressions are tokenized with their character positions so errors can
+//! point at the failing spot. Cells are strings; they compare and compute as
+//! numbers whenever both sides parse as numbers, and empty cells are NULL.
+
+use crate::{chart::parse_timestamp, pivot::Aggregate};
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike};
+use std::{cmp::Ordering, fmt};
+
+/// Error from parsing or binding an expression, with the character position
+/// where it was detected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExprError {
+    pub pos: usize,
+    pub message: String,
+}
+
+impl ExprError {
+    pub fn new(pos: usize, message: impl Into<String>) -> Self {
+        ExprError {
+            pos,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.pos + 1)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+/// A value produced while evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scalar {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+}
+
+impl Scalar {
+    /// Interpret a table cell; empty cells are NULL
+    pub fn from_cell(cell: &str) -> Self {
+        if cell.is_empty() {
+            Scalar::Null
+        } else {
+            Scalar::Str(cell.to_string())
+        }
+    }
+
+    /// Numeric value, parsing strings when they look like numbers
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Scalar::Num(n) => Some(*n),
+            Scalar::Bool(b) => Some(*b as u8 as f64),
+            Scalar::Str(s) => s.trim().parse::<f64>().ok().filter(|n| n.is_finite()),
+            Scalar::Null => None,
+        }
+    }
+
+    /// Truth value used by WHERE, AND, OR and NOT
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Scalar::Null => false,
+            Scalar::Bool(b) => *b,
+            Scalar::Num(n) => *n != 0.0,
+            Scalar::Str(s) => !s.is_empty() && s != "false" && s != "0",
+        }
+    }
+
+    /// Text shown in a table cell
+    pub fn to_cell(&self) -> String {
+        match self {
+            Scalar::Null => String::new(),
+            Scalar::Bool(b) => b.to_string(),
+            Scalar::Num(n) => format_number(*n),
+            Scalar::Str(s) => s.clone(),
+        }
+    }
+
+    /// Compare two values: NULL first, then numbers in numeric order, then
+    /// everything else as text
+    ///
+    /// This is a total order, so columns mixing numbers and text sort
+    /// consistently.
+    pub fn compare(&self, other: &Scalar) -> Ordering {
+        let rank = |value: &Scalar| match value {
+            Scalar::Null => 0,
+            _ if value.as_number().is_some() => 1,
+            _ => 2,
+        };
+        rank(self)
+            .cmp(&rank(other))
+            .then_with(|| match (self.as_number(), other.as_number()) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                _ => self.to_cell().cmp(&other.to_cell()),
+            })
+    }
+}
+
+/// Format a number for a cell: whole values without a trailing `.0`, others
+/// to at most four decimal places
+///
+/// SQL results, computed columns and pivot aggregates all print through here.
+pub fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{:.4}", n)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+/// A lexical token and the character position it starts at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Number(f64),
+    /// Single- or double-quoted string literal
+    Str(String),
+    /// Bare word: a column name, function name or keyword
+    Ident(String),
+    /// Backtick-quoted column name
+    QuotedIdent(String),
+    Symbol(&'static str),
+    End,
+}
+
+const SYMBOLS: [&str; 17] = [
+    "<>", "!=", "<=", ">=", "||", "=", "<", ">", "+", "-", "*", "/", "%", "(", ")", ",", ".",
+];
+
+/// Split input into tokens
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ExprError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let kind = if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))
+        {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let n = text
+                .parse::<f64>()
+                .map_err(|_| ExprError::new(start, format!("Invalid number '{}'", text)))?;
+            TokenKind::Number(n)
+        } else if c == '\'' || c == '"' || c == '`' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ExprError::new(start, "Unterminated quoted text")),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            if c == '`' {
+                TokenKind::QuotedIdent(text)
+            } else {
+                TokenKind::Str(text)
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else {
+            let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            match SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                Some(symbol) => {
+                    i += symbol.len();
+                    TokenKind::Symbol(symbol)
+                }
+                None => {
+                    return Err(ExprError::new(
+                        start,
+                        format!("Unexpected character '{}'", c),
+                    ))
+                }
+            }
+        };
+        tokens.push(Token { kind, pos: start });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::End,
+        pos: chars.len(),
+    });
+    Ok(tokens)
+}
+
+/// Binary operators, lowest precedence first within each group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Concat,
+}
+
+/// Parsed expression tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Scalar),
+    /// Column reference; `index` is filled in by [`Expr::bind`]
+    Column {
+        name: String,
+        index: usize,
+        pos: usize,
+    },
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    InList {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    Like {
+        expr: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    Call {
+        name: String,
+        args: Vec<Expr>,
+        pos: usize,
+    },
+    /// Aggregate over a group of rows; `arg` is `None` for `count(*)`
+    Aggregate {
+        func: Aggregate,
+        arg: Option<Box<Expr>>,
+        pos: usize,
+    },
+    /// Part of a timestamp, e.g. `parse_ts(created_at).hour`
+    Part {
+        expr: Box<Expr>,
+        part: TimePart,
+        pos: usize,
+    },
+}
+
+/// Parts of a timestamp that can follow a value with `.`, all in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimePart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    /// ISO day of the week, 1 for Monday to 7 for Sunday
+    Weekday,
+    /// Calendar date as `YYYY-MM-DD`
+    Date,
+    /// Seconds since the Unix epoch
+    Epoch,
+}
+
+impl TimePart {
+    const ALL: [(&'static str, TimePart); 9] = [
+        ("year", TimePart::Year),
+        ("month", TimePart::Month),
+        ("day", TimePart::Day),
+        ("hour", TimePart::Hour),
+        ("minute", TimePart::Minute),
+        ("second", TimePart::Second),
+        ("weekday", TimePart::Weekday),
+        ("date", TimePart::Date),
+        ("epoch", TimePart::Epoch),
+    ];
+
+    fn named(name: &str) -> Option<TimePart> {
+        Self::ALL
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, part)| part)
+    }
+
+    /// This part of a timestamp value; NULL if the value is not one
+    fn of(&self, value: &Scalar) -> Scalar {
+        let Some(time) = timestamp(value) else {
+            return Scalar::Null;
+        };
+        let number = |n: u32| Scalar::Num(n as f64);
+        match self {
+            TimePart::Year => Scalar::Num(time.year() as f64),
+            TimePart::Month => number(time.month()),
+            TimePart::Day => number(time.day()),
+            TimePart::Hour => number(time.hour()),
+            TimePart::Minute => number(time.minute()),
+            TimePart::Second => number(time.second()),
+            TimePart::Weekday => number(time.weekday().number_from_monday()),
+            TimePart::Date => Scalar::Str(time.format("%Y-%m-%d").to_string()),
+            TimePart::Epoch => Scalar::Num(time.and_utc().timestamp_millis() as f64 / 1000.0),
+        }
+    }
+}
+
+/// Read a value as a timestamp: date and time text, or seconds since the
+/// Unix epoch
+fn timestamp(value: &Scalar) -> Option<NaiveDateTime> {
+    let seconds = match value {
+        Scalar::Num(n) => *n,
+        Scalar::Str(s) => parse_timestamp(s).or_else(|| value.as_number())?,
+        Scalar::Null | Scalar::Bool(_) => return None,
+    };
+    DateTime::from_timestamp_millis((seconds * 1000.0).round() as i64).map(|t| t.naive_utc())
+}
+
+/// Scalar functions callable from expressions, with their argument counts
+const FUNCTIONS: [(&str, usize, usize); 10] = [
+    ("lower", 1, 1),
+    ("upper", 1, 1),
+    ("length", 1, 1),
+    ("trim", 1, 1),
+    ("abs", 1, 1),
+    ("round", 1, 2),
+    ("substr", 2, 3),
+    ("coalesce", 1, usize::MAX),
+    ("concat", 1, usize::MAX),
+    ("parse_ts", 1, 1),
+];
+
+impl Expr {
+    /// Resolve column names against `headers` (case-insensitively if there is
+    /// no exact match) and check function names and argument counts
+    pub fn bind(&mut self, headers: &[String]) -> Result<(), ExprError> {
+        match self {
+            Expr::Column { name, index, pos } => {
+                *index = headers
+                    .iter()
+                    .position(|h| h == name)
+                    .or_else(|| headers.iter().position(|h| h.eq_ignore_ascii_case(name)))
+                    .ok_or_else(|| ExprError::new(*pos, format!("Unknown column '{}'", name)))?;
+                Ok(())
+            }
+            Expr::Literal(_) => Ok(()),
+            Expr::Neg(e) | Expr::Not(e) => e.bind(headers),
+            Expr::IsNull { expr, .. } | Expr::Part { expr, .. } => expr.bind(headers),
+            Expr::Binary { left, right, .. } => {
+                left.bind(headers)?;
+                right.bind(headers)
+            }
+            Expr::Like { expr, pattern, .. } => {
+                expr.bind(headers)?;
+                pattern.bind(headers)
+            }
+            Expr::InList { expr, list, .. } => {
+                expr.bind(headers)?;
+                list.iter_mut().try_for_each(|e| e.bind(headers))
+            }
+            Expr::Call { name, args, pos } => {
+                let (_, min, max) = FUNCTIONS
+                    .iter()
+                    .find(|(f, _, _)| f.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| ExprError::new(*pos, format!("Unknown function '{}'", name)))?;
+                if args.len() < *min || args.len() > *max {
+                    return Err(ExprError::new(
+                        *pos,
+                        format!("Wrong number of arguments to {}", name),
+                    ));
+                }
+                args.iter_mut().try_for_each(|e| e.bind(headers))
+            }
+            Expr::Aggregate { arg, .. } => match arg {
+                Some(arg) => arg.bind(headers),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// Position of the first aggregate in this expression, if any
+    pub fn find_aggregate(&self) -> Option<usize> {
+        match self {
+            Expr::Aggregate { pos, .. } => Some(*pos),
+            Expr::Literal(_) | Expr::Column { .. } => None,
+            Expr::Neg(e) | Expr::Not(e) => e.find_aggregate(),
+            Expr::IsNull { expr, .. } | Expr::Part { expr, .. } => expr.find_aggregate(),
+            Expr::Binary { left, right, .. } => {
+                left.find_aggregate().or_else(|| right.find_aggregate())
+            }
+            Expr::Like { expr, pattern, .. } => {
+                expr.find_aggregate().or_else(|| pattern.find_aggregate())
+            }
+            Expr::InList { expr, list, .. } => expr
+                .find_aggregate()
+                .or_else(|| list.iter().find_map(Expr::find_aggregate)),
+            Expr::Call { args, .. } => args.iter().find_map(Expr::find_aggregate),
+        }
+    }
+
+    /// Replace references to columns from `base` on with the expressions of
+    /// those computed columns, so the result reads loaded columns only;
+    /// `None` marks a computed column that failed to bind and reads as NULL
+    pub fn inline(&mut self, base: usize, computed: &[Option<Expr>]) {
+        match self {
+            Expr::Column { index, .. } if *index >= base => {
+                *self = computed
+                    .get(*index - base)
+                    .cloned()
+                    .flatten()
+                    .unwrap_or(Expr::Literal(Scalar::Null));
+            }
+            Expr::Literal(_) | Expr::Column { .. } => {}
+            Expr::Neg(e) | Expr::Not(e) => e.inline(base, computed),
+            Expr::IsNull { expr, .. } | Expr::Part { expr, .. } => expr.inline(base, computed),
+            Expr::Binary { left, right, .. } => {
+                left.inline(base, computed);
+                right.inline(base, computed);
+            }
+            Expr::Like { expr, pattern, .. } => {
+                expr.inline(base, computed);
+                pattern.inline(base, computed);
+            }
+            Expr::InList { expr, list, .. } => {
+                expr.inline(base, computed);
+                list.iter_mut().for_each(|e| e.inline(base, computed));
+            }
+            Expr::Call { args, .. } => args.iter_mut().for_each(|e| e.inline(base, computed)),
+            Expr::Aggregate { arg, .. } => {
+                if let Some(arg) = arg {
+                    arg.inline(base, computed);
+                }
+            }
+        }
+    }
+
+    /// Evaluate against a single row
+    pub fn eval(&self, row: &[String]) -> Scalar {
+        self.eval_with(&|e| e.eval(row), Some(row), &[])
+    }
+
+    /// Evaluate against a group of rows: aggregates see every row, plain
+    /// columns take their value from the first row
+    pub fn eval_group(&self, rows: &[&Vec<String>]) -> Scalar {
+        let first = rows.first().map(|r| r.as_slice());
+        self.eval_with(&|e| e.eval_group(rows), first, rows)
+    }
+
+    fn eval_with(
+        &self,
+        sub: &dyn Fn(&Expr) -> Scalar,
+        row: Option<&[String]>,
+        group: &[&Vec<String>],
+    ) -> Scalar {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Column { index, .. } => row
+                .and_then(|r| r.get(*index))
+                .map(|c| Scalar::from_cell(c))
+                .unwrap_or(Scalar::Null),
+            Expr::Neg(e) => match sub(e).as_number() {
+                Some(n) => Scalar::Num(-n),
+                None => Scalar::Null,
+            },
+            Expr::Not(e) => match sub(e) {
+                Scalar::Null => Scalar::Null,
+                v => Scalar::Bool(!v.is_truthy()),
+            },
+            Expr::IsNull { expr, negated } => Scalar::Bool((sub(expr) == Scalar::Null) != *negated),
+            Expr::Binary { op, left, right } => binary(*op, sub(left), || sub(right)),
+            Expr::Like {
+                expr,
+                pattern,
+                negated,
+            } => match (sub(expr), sub(pattern)) {
+                (Scalar::Null, _) | (_, Scalar::Null) => Scalar::Null,
+                (value, pattern) => {
+                    Scalar::Bool(like(&value.to_cell(), &pattern.to_cell()) != *negated)
+                }
+            },
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = sub(expr);
+                if value == Scalar::Null {
+                    return Scalar::Null;
+                }
+                let found = list
+                    .iter()
+                    .any(|e| value.compare(&sub(e)) == Ordering::Equal);
+                Scalar::Bool(found != *negated)
+            }
+            Expr::Call { name, args, .. } => {
+                let args: Vec<Scalar> = args.iter().map(sub).collect();
+                call(&name.to_lowercase(), &args)
+            }
+            Expr::Part { expr, part, .. } => part.of(&sub(expr)),
+            Expr::Aggregate { func, arg, .. } => {
+                if group.is_empty() && row.is_some() {
+                    return Scalar::Null;
+                }
+                let Some(arg) = arg else {
+                    return Scalar::Num(group.len() as f64);
+                };
+                let cells: Vec<String> = group.iter().map(|r| arg.eval(r).to_cell()).collect();
+                func.value(cells.iter().map(String::as_str))
+            }
+        }
+    }
+}
+
+/// Apply a binary operator; the right side is only evaluated when needed
+fn binary(op: BinaryOp, left: Scalar, right: impl FnOnce() -> Scalar) -> Scalar {
+    match op {
+        BinaryOp::And => {
+            if left != Scalar::Null && !left.is_truthy() {
+                return Scalar::Bool(false);
+            }
+            let right = right();
+            if right != Scalar::Null && !right.is_truthy() {
+                Scalar::Bool(false)
+            } else if left == Scalar::Null || right == Scalar::Null {
+                Scalar::Null
+            } else {
+                Scalar::Bool(true)
+            }
+        }
+        BinaryOp::Or => {
+            if left.is_truthy() {
+                return Scalar::Bool(true);
+            }
+            let right = right();
+            if right.is_truthy() {
+                Scalar::Bool(true)
+            } else if left == Scalar::Null || right == Scalar::Null {
+                Scalar::Null
+            } else {
+                Scalar::Bool(false)
+            }
+        }
+        _ => {
+            let right = right();
+            if left == Scalar::Null || right == Scalar::Null {
+                return Scalar::Null;
+            }
+            let ordering = || left.compare(&right);
+            match op {
+                BinaryOp::Eq => Scalar::Bool(ordering() == Ordering::Equal),
+                BinaryOp::NotEq => Scalar::Bool(ordering() != Ordering::Equal),
+                BinaryOp::Lt => Scalar::Bool(ordering() == Ordering::Less),
+                BinaryOp::LtEq => Scalar::Bool(ordering() != Ordering::Greater),
+                BinaryOp::Gt => Scalar::Bool(ordering() == Ordering::Greater),
+                BinaryOp::GtEq => Scalar::Bool(ordering() != Ordering::Less),
+                BinaryOp::Concat => Scalar::Str(left.to_cell() + &right.to_cell()),
+                _ => match (left.as_number(), right.as_number()) {
+                    (Some(a), Some(b)) => match op {
+                        BinaryOp::Add => Scalar::Num(a + b),
+                        BinaryOp::Sub => Scalar::Num(a - b),
+                        BinaryOp::Mul => Scalar::Num(a * b),
+                        BinaryOp::Div if b != 0.0 => Scalar::Num(a / b),
+                        BinaryOp::Mod if b != 0.0 => Scalar::Num(a % b),
+                        _ => Scalar::Null,
+                    },
+                    _ => Scalar::Null,
+                },
+            }
+        }
+    }
+}
+
+/// Evaluate a scalar function
+fn call(name: &str, args: &[Scalar]) -> Scalar {
+    let text = |i: usize| args.get(i).map(Scalar::to_cell).unwrap_or_default();
+    let number = |i: usize| args.get(i).and_then(Scalar::as_number);
+    if args.first() == Some(&Scalar::Null) && name != "coalesce" && name != "concat" {
+        return Scalar::Null;
+    }
+
+    match name {
+        "lower" => Scalar::Str(text(0).to_lowercase()),
+        "upper" => Scalar::Str(text(0).to_uppercase()),
+        "trim" => Scalar::Str(text(0).trim().to_string()),
+        "length" => Scalar::Num(text(0).chars().count() as f64),
+        "abs" => number(0).map_or(Scalar::Null, |n| Scalar::Num(n.abs())),
+        "round" => match number(0) {
+            Some(n) => {
+                let scale = 10f64.powi(number(1).unwrap_or(0.0) as i32);
+                Scalar::Num((n * scale).round() / scale)
+            }
+            None => Scalar::Null,
+        },
+        "substr" => {
+            let start = number(1).unwrap_or(1.0).max(1.0) as usize - 1;
+            let len = number(2).map_or(usize::MAX, |n| n.max(0.0) as usize);
+            Scalar::Str(text(0).chars().skip(start).take(len).collect())
+        }
+        "coalesce" => args
+            .iter()
+            .find(|a| **a != Scalar::Null)
+            .cloned()
+            .unwrap_or(Scalar::Null),
+        "concat" => Scalar::Str(args.iter().map(Scalar::to_cell).collect()),
+        "parse_ts" => match timestamp(&args[0]) {
+            Some(time) => Scalar::Str(time.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+            None => Scalar::Null,
+        },
+        _ => Scalar::Null,
+    }
+}
+
+/// SQL LIKE matching with `%` and `_` wildcards, case-insensitive
+fn like(value: &str, pattern: &str) -> bool {
+    fn matches(value: &[char], pattern: &[char]) -> bool {
+        match pattern.split_first() {
+            None => value.is_empty(),
+            Some(('%', rest)) => (0..=value.len()).any(|i| matches(&value[i..], rest)),
+            Some(('_', rest)) => !value.is_empty() && matches(&value[1..], rest),
+            Some((c, rest)) => value.first() == Some(c) && matches(&value[1..], rest),
+        }
+    }
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    matches(&value, &pattern)
+}
+
+/// Parse a row-level expression (no aggregates) and bind it to `headers`
+pub fn parse(input: &str, headers: &[String]) -> Result<Expr, ExprError> {
+    let mut parser = Parser::new(input)?;
+    let mut expr = parser.parse_expr()?;
+    parser.expect_end()?;
+    if let Some(pos) = expr.find_aggregate() {
+        return Err(ExprError::new(pos, "Aggregates are not allowed here"));
+    }
+    expr.bind(headers)?;
+    Ok(expr)
+}
+
+/// Recursive-descent parser over a token stream, also used for SQL statements
+pub struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Result<Self, ExprError> {
+        Ok(Parser {
+            tokens: tokenize(input)?,
+            index: 0,
+        })
+    }
+
+    pub fn peek(&self) -> &Token {
+        &self.tokens[self.index]
+    }
+
+    pub fn advance(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if self.index + 1 < self.tokens.len() {
+            self.index += 1;
+        }
+        token
+    }
+
+    /// Whether the next token is the given keyword (case-insensitive)
+    pub fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Ident(w) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Consume the keyword if it is next
+    pub fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), ExprError> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("Expected {}", keyword.to_uppercase())))
+        }
+    }
+
+    /// Consume the symbol if it is next
+    pub fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek().kind == TokenKind::Symbol(symbol_ref(symbol));
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    pub fn expect_symbol(&mut self, symbol: &str) -> Result<(), ExprError> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("Expected '{}'", symbol)))
+        }
+    }
+
+    /// Error pointing at the next token
+    pub fn unexpected(&self, message: &str) -> ExprError {
+        let token = self.peek();
+        let found = match &token.kind {
+            TokenKind::End => "end of input".to_string(),
+            TokenKind::Number(n) => n.to_string(),
+            TokenKind::Str(s) => format!("'{}'", s),
+            TokenKind::Ident(s) | TokenKind::QuotedIdent(s) => format!("'{}'", s),
+            TokenKind::Symbol(s) => format!("'{}'", s),
+        };
+        ExprError::new(token.pos, format!("{}, found {}", message, found))
+    }
+
+    /// Fail unless all input has been consumed
+    pub fn expect_end(&self) -> Result<(), ExprError> {
+        match self.peek().kind {
+            TokenKind::End => Ok(()),
+            _ => Err(self.unexpected("Unexpected input")),
+        }
+    }
+
+    /// Parse a full expression
+    pub fn parse_expr(&mut self) -> Result<Expr, ExprError> {
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_and()?;
+        while self.eat_keyword("or") {
+            let right = self.parse_and()?;
+            left = binary_expr(BinaryOp::Or, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_not()?;
+        while self.eat_keyword("and") {
+            let right = self.parse_not()?;
+            left = binary_expr(BinaryOp::And, left, right);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ExprError> {
+        let left = self.parse_additive()?;
+
+        if self.eat_keyword("is") {
+            let negated = self.eat_keyword("not");
+            self.expect_keyword("null")?;
+            return Ok(Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+
+        let negated = self.eat_keyword("not");
+        if self.eat_keyword("like") {
+            let pattern = self.parse_additive()?;
+            return Ok(Expr::Like {
+                expr: Box::new(left),
+                pattern: Box::new(pattern),
+                negated,
+            });
+        }
+        if self.eat_keyword("in") {
+            self.expect_symbol("(")?;
+            let mut list = vec![self.parse_expr()?];
+            while self.eat_symbol(",") {
+                list.push(self.parse_expr()?);
+            }
+            self.expect_symbol(")")?;
+            return Ok(Expr::InList {
+                expr: Box::new(left),
+                list,
+                negated,
+            });
+        }
+        if negated {
+            return Err(self.unexpected("Expected LIKE or IN after NOT"));
+        }
+
+        let op = match self.peek().kind {
+            TokenKind::Symbol("=") => BinaryOp::Eq,
+            TokenKind::Symbol("!=") | TokenKind::Symbol("<>") => BinaryOp::NotEq,
+            TokenKind::Symbol("<") => BinaryOp::Lt,
+            TokenKind::Symbol("<=") => BinaryOp::LtEq,
+            TokenKind::Symbol(">") => BinaryOp::Gt,
+            TokenKind::Symbol(">=") => BinaryOp::GtEq,
+            _ => return Ok(left),
+        };
+        self.advance();
+        let right = self.parse_additive()?;
+        Ok(binary_expr(op, left, right))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Symbol("+") => BinaryOp::Add,
+                TokenKind::Symbol("-") => BinaryOp::Sub,
+                TokenKind::Symbol("||") => BinaryOp::Concat,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_multiplicative()?;
+            left = binary_expr(op, left, right);
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr, ExprError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Symbol("*") => BinaryOp::Mul,
+                TokenKind::Symbol("/") => BinaryOp::Div,
+                TokenKind::Symbol("%") => BinaryOp::Mod,
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_unary()?;
+            left = binary_expr(op, left, right);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat_symbol("-") {
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix()
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.parse_primary()?;
+        while self.eat_symbol(".") {
+            let token = self.advance();
+            let part = match &token.kind {
+                TokenKind::Ident(name) => TimePart::named(name),
+                _ => None,
+            }
+            .ok_or_else(|| {
+                let names: Vec<&str> = TimePart::ALL.iter().map(|(n, _)| *n).collect();
+                ExprError::new(
+                    token.pos,
+                    format!("Expected a timestamp part: {}", names.join(", ")),
+                )
+            })?;
+            expr = Expr::Part {
+                expr: Box::new(expr),
+                part,
+                pos: token.pos,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExprError> {
+        let token = self.peek().clone();
+        match token.kind {
+            TokenKind::Number(n) => {
+                self.advance();
+                Ok(Expr::Literal(Scalar::Num(n)))
+            }
+            TokenKind::Str(s) => {
+                self.advance();
+                Ok(Expr::Literal(Scalar::Str(s)))
+            }
+            TokenKind::QuotedIdent(name) => {
+                self.advance();
+                Ok(column(name, token.pos))
+            }
+            TokenKind::Symbol("(") => {
+                self.advance();
+                let expr = self.parse_expr()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            TokenKind::Ident(word) => {
+                self.advance();
+                match word.to_lowercase().as_str() {
+                    "null" => return Ok(Expr::Literal(Scalar::Null)),
+                    "true" => return Ok(Expr::Literal(Scalar::Bool(true))),
+                    "false" => return Ok(Expr::Literal(Scalar::Bool(false))),
+                    _ => {}
+                }
+                if !self.eat_symbol("(") {
+                    return Ok(column(word, token.pos));
+                }
+                if let Some(func) = aggregate_named(&word) {
+                    return self.parse_aggregate(func, token.pos);
+                }
+                let mut args = Vec::new();
+                if !self.eat_symbol(")") {
+                    args.push(self.parse_expr()?);
+                    while self.eat_symbol(",") {
+                        args.push(self.parse_expr()?);
+                    }
+                    self.expect_symbol(")")?;
+                }
+                Ok(Expr::Call {
+                    name: word,
+                    args,
+                    pos: token.pos,
+                })
+            }
+            _ => Err(self.unexpected("Expected an expression")),
+        }
+    }
+
+    /// Parse the arguments of an aggregate call after its opening parenthesis
+    fn parse_aggregate(&mut self, func: Aggregate, pos: usize) -> Result<Expr, ExprError> {
+        if func == Aggregate::Count && self.eat_symbol("*") {
+            self.expect_symbol(")")?;
+            return Ok(Expr::Aggregate {
+                func,
+                arg: None,
+                pos,
+            });
+        }
+        let func = if func == Aggregate::Count && self.eat_keyword("distinct") {
+            Aggregate::DistinctCount
+        } else {
+            func
+        };
+        let arg = self.parse_expr()?;
+        if let Some(inner) = arg.find_aggregate() {
+            return Err(ExprError::new(inner, "Aggregates cannot be nested"));
+        }
+        self.expect_symbol(")")?;
+        Ok(Expr::Aggregate {
+            func,
+            arg: Some(Box::new(arg)),
+            pos,
+        })
+    }
+}
+
+fn binary_expr(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+    Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn column(name: String, pos: usize) -> Expr {
+    Expr::Column {
+        name,
+        index: usize::MAX,
+        pos,
+    }
+}
+
+/// Aggregate function for a call name, if it is one
+fn aggregate_named(name: &str) -> Option<Aggregate> {
+    match name.to_lowercase().as_str() {
+        "count" => Some(Aggregate::Count),
+        "sum" => Some(Aggregate::Sum),
+        "avg" => Some(Aggregate::Avg),
+        "min" => Some(Aggregate::Min),
+        "max" => Some(Aggregate::Max),
+        _ => None,
+    }
+}
+
+/// Map a symbol string onto the static symbol table so tokens compare equal
+fn symbol_ref(symbol: &str) -> &'static str {
+    SYMBOLS
+        .iter()
+        .find(|s| **s == symbol)
+        .copied()
+        .unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        ["host", "status", "bytes", "first name"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn row(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn eval(input: &str, cells: &[&str]) -> Scalar {
+        parse(input, &headers()).unwrap().eval(&row(cells))
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(3.0), "3");
+        assert_eq!(format_number(2.5), "2.5");
+        assert_eq!(format_number(1.0 / 3.0), "0.3333");
+        assert_eq!(Scalar::Num(-0.5).to_cell(), "-0.5");
+    }
+
+    #[test]
+    fn test_tokenize_positions() {
+        let tokens = tokenize("status >= 500").unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Ident("status".to_string()));
+        assert_eq!(tokens[1].kind, TokenKind::Symbol(">="));
+        assert_eq!(tokens[1].pos, 7);
+        assert_eq!(tokens[2].kind, TokenKind::Number(500.0));
+        assert_eq!(tokens[3].kind, TokenKind::End);
+    }
+
+    #[test]
+    fn test_numeric_and_string_comparison() {
+        assert_eq!(
+            eval("status >= 500", &["a", "503", "", ""]),
+            Scalar::Bool(true)
+        );
+        assert_eq!(
+            eval("status >= 500", &["a", "99", "", ""]),
+            Scalar::Bool(false)
+        );
+        assert_eq!(
+            eval("host = 'web1'", &["web1", "", "", ""]),
+            Scalar::Bool(true)
+        );
+        assert_eq!(eval("bytes > 10", &["a", "", "", ""]), Scalar::Null);
+    }
+
+    #[test]
+    fn test_arithmetic_and_functions() {
+        assert_eq!(
+            eval("bytes / 1024", &["", "", "2048", ""]),
+            Scalar::Num(2.0)
+        );
+        assert_eq!(
+            eval(
+                "concat(host, \" \", upper(`first name`))",
+                &["a", "", "", "bob"]
+            ),
+            Scalar::Str("a BOB".to_string())
+        );
+        assert_eq!(
+            eval("round(bytes / 3, 2)", &["", "", "10", ""]),
+            Scalar::Num(3.33)
+        );
+        assert_eq!(
+            eval("coalesce(bytes, 0) + 1", &["", "", "", ""]),
+            Scalar::Num(1.0)
+        );
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let cells = &["", "", "1700000000", "2024-02-29T23:30:05+01:00"];
+        assert_eq!(
+            eval("parse_ts(`first name`)", cells),
+            Scalar::Str("2024-02-29 22:30:05".to_string())
+        );
+        assert_eq!(
+            eval("parse_ts(`first name`).hour", cells),
+            Scalar::Num(22.0)
+        );
+        assert_eq!(eval("`first name`.weekday", cells), Scalar::Num(4.0));
+        assert_eq!(
+            eval("parse_ts(bytes).date", cells),
+            Scalar::Str("2023-11-14".to_string())
+        );
+        assert_eq!(eval("host.year", &["soon", "", "", ""]), Scalar::Null);
+        assert_eq!(parse("bytes.hours", &headers()).unwrap_err().pos, 6);
+    }
+
+    #[test]
+    fn test_logic_like_in_null() {
+        let cells = &["web1", "404", "", ""];
+        assert!(eval("host LIKE 'WEB%' AND status IN (404, 500)", cells).is_truthy());
+        assert!(eval("bytes IS NULL OR status < 0", cells).is_truthy());
+        assert!(eval("NOT (host NOT LIKE '_eb1')", cells).is_truthy());
+        assert_eq!(eval("bytes > 1 AND status = 404", cells), Scalar::Null);
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let err = parse("status >= ", &headers()).unwrap_err();
+        assert_eq!(err.pos, 10);
+
+        let err = parse("hst = 1", &headers()).unwrap_err();
+        assert_eq!(err, ExprError::new(0, "Unknown column 'hst'"));
+
+        let err = parse("sum(max(bytes))", &headers()).unwrap_err();
+        assert_eq!(err.pos, 4);
+
+        let err = parse("host = 'x", &headers()).unwrap_err();
+        assert_eq!(err.pos, 7);
+    }
+
+    #[test]
+    fn test_aggregates_over_group() {
+        let mut parser = Parser::new("count(*) + sum(bytes)").unwrap();
+        let mut expr = parser.parse_expr().unwrap();
+        expr.bind(&headers()).unwrap();
+        let rows = [row(&["a", "", "10", ""]), row(&["a", "", "5", ""])];
+        let refs: Vec<&Vec<String>> = rows.iter().collect();
+        assert_eq!(expr.eval_group(&refs), Scalar::Num(17.0));
+    }
+}
@@ -0,0 +1,278 @@
+//! Fuzzy file finder
+//!
+//! Indexes the data files under a directory on a background thread
+//! (honouring `.gitignore` and skipping hidden files) and ranks them against a
+//! query by subsequence matching, favouring consecutive characters, word
+//! boundaries and matches in the file name.
+
+use crate::browser;
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+/// Paths sent from the indexing thread per message
+const BATCH_SIZE: usize = 256;
+
+/// Score for each matched character
+const SCORE_MATCH: i64 = 16;
+/// Bonus for a character directly following the previous match
+const BONUS_CONSECUTIVE: i64 = 12;
+/// Bonus for a match at the start of a word (after `/`, `_`, `-`, `.` or a space)
+const BONUS_BOUNDARY: i64 = 10;
+/// Bonus for a lowercase-to-uppercase transition, as in `camelCase`
+const BONUS_CAMEL: i64 = 8;
+/// Bonus for a match in the file name rather than its directories
+const BONUS_FILE_NAME: i64 = 4;
+/// Penalty for each character skipped between two matches
+const PENALTY_GAP: i64 = 1;
+
+/// Data files found under a directory, filled in by a background thread
+pub struct FileIndex {
+    pub root: PathBuf,
+    /// Paths relative to `root`, using `/` as the separator
+    pub files: Vec<String>,
+    /// Whether the walk has finished
+    pub done: bool,
+    receiver: Receiver<Vec<String>>,
+}
+
+impl FileIndex {
+    /// Start indexing the supported files under `root`
+    pub fn spawn(root: &Path) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let walk_root = root.to_path_buf();
+        thread::spawn(move || {
+            let walker = ignore::WalkBuilder::new(&walk_root)
+                .require_git(false)
+                .build();
+            let mut batch = Vec::new();
+            for entry in walker.flatten() {
+                let path = entry.path();
+                if !entry.file_type().is_some_and(|t| t.is_file()) || !browser::is_supported(path) {
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&walk_root) else {
+                    continue;
+                };
+                let parts: Vec<_> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                batch.push(parts.join("/"));
+                if batch.len() == BATCH_SIZE {
+                    // The finder was closed: stop walking
+                    if sender.send(std::mem::take(&mut batch)).is_err() {
+                        return;
+                    }
+                }
+            }
+            let _ = sender.send(batch);
+        });
+
+        FileIndex {
+            root: root.to_path_buf(),
+            files: Vec::new(),
+            done: false,
+            receiver,
+        }
+    }
+
+    /// Collect the paths found since the last call; returns whether any arrived
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(batch) => {
+                    changed |= !batch.is_empty();
+                    self.files.extend(batch);
+                }
+                Err(TryRecvError::Empty) => return changed,
+                Err(TryRecvError::Disconnected) => {
+                    changed |= !self.done;
+                    self.done = true;
+                    return changed;
+                }
+            }
+        }
+    }
+}
+
+/// A candidate that matched the query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    /// Character positions of the matched query characters in the candidate
+    pub positions: Vec<usize>,
+}
+
+/// Score `candidate` against `query`, or `None` if the query characters do
+/// not all appear in order
+///
+/// Matching ignores case unless the query contains an uppercase letter.
+pub fn score(query: &str, candidate: &str) -> Option<Match> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let text: Vec<char> = candidate.chars().collect();
+    let case_sensitive = query.iter().any(|c| c.is_uppercase());
+    let eq = |a: char, b: char| {
+        if case_sensitive {
+            a == b
+        } else {
+            a.to_lowercase().eq(b.to_lowercase())
+        }
+    };
+
+    let name_start = text.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+    let bonus = |j: usize| {
+        let mut bonus = if j >= name_start { BONUS_FILE_NAME } else { 0 };
+        match j.checked_sub(1).map(|i| text[i]) {
+            None | Some('/' | '_' | '-' | '.' | ' ') => bonus += BONUS_BOUNDARY,
+            Some(prev) if prev.is_lowercase() && text[j].is_uppercase() => bonus += BONUS_CAMEL,
+            _ => {}
+        }
+        bonus
+    };
+
+    // best[i][j]: best score with query[i] matched at text[j], and the
+    // position query[i - 1] was matched at to get it
+    let n = text.len();
+    let mut best: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; n]; query.len()];
+    for (i, &q) in query.iter().enumerate() {
+        // Best earlier match of query[i - 1] seen so far, adjusted so the gap
+        // penalty can be applied from its position: (score + k * gap, k)
+        let mut running: Option<(i64, usize)> = None;
+        for j in 0..n {
+            // Predecessors with a gap end at j - 2; j - 1 is the consecutive
+            // case handled below
+            if i > 0 && j >= 2 {
+                if let Some((s, _)) = best[i - 1][j - 2] {
+                    let adjusted = s + (j as i64 - 2) * PENALTY_GAP;
+                    if running.is_none_or(|(r, _)| adjusted > r) {
+                        running = Some((adjusted, j - 2));
+                    }
+                }
+            }
+            if !eq(q, text[j]) {
+                continue;
+            }
+            let here = SCORE_MATCH + bonus(j);
+            best[i][j] = if i == 0 {
+                Some((here, 0))
+            } else {
+                let gapped = running.map(|(r, k)| (r - (j as i64 - 1) * PENALTY_GAP + here, k));
+                let consecutive = j
+                    .checked_sub(1)
+                    .and_then(|k| best[i - 1][k])
+                    .map(|(s, _)| (s + here + BONUS_CONSECUTIVE, j - 1));
+                match (gapped, consecutive) {
+                    (Some(g), Some(c)) => Some(if c.0 >= g.0 { c } else { g }),
+                    (g, c) => g.or(c),
+                }
+            };
+        }
+    }
+
+    let last = query.len() - 1;
+    let (mut j, (score, _)) = best[last]
+        .iter()
+        .enumerate()
+        .filter_map(|(j, cell)| cell.map(|c| (j, c)))
+        .max_by_key(|(j, (s, _))| (*s, std::cmp::Reverse(*j)))?;
+
+    let mut positions = vec![0; query.len()];
+    for i in (0..query.len()).rev() {
+        positions[i] = j;
+        if i > 0 {
+            j = best[i][j].expect("matched cell").1;
+        }
+    }
+    Some(Match { score, positions })
+}
+
+/// Rank candidates against a query, best first, keeping at most `limit`
+///
+/// Ties go to the shorter path, then to alphabetical order.
+pub fn rank(query: &str, candidates: &[String], limit: usize) -> Vec<(usize, Match)> {
+    let mut matches: Vec<(usize, Match)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| score(query, c).map(|m| (i, m)))
+        .collect();
+    matches.sort_by(|(a, ma), (b, mb)| {
+        mb.score
+            .cmp(&ma.score)
+            .then_with(|| candidates[*a].len().cmp(&candidates[*b].len()))
+            .then_with(|| candidates[*a].cmp(&candidates[*b]))
+    });
+    matches.truncate(limit);
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, time::Duration};
+
+    #[test]
+    fn test_score_positions_and_case() {
+        let m = score("evt", "logs/events.csv").unwrap();
+        assert_eq!(m.positions, vec![5, 6, 9]);
+        assert!(score("xyz", "logs/events.csv").is_none());
+        assert!(score("EV", "logs/events.csv").is_none());
+        assert!(score("ev", "logs/Events.csv").is_some());
+        assert_eq!(score("", "a.csv").unwrap().score, 0);
+    }
+
+    #[test]
+    fn test_score_prefers_file_name_and_boundaries() {
+        // The file name match beats scattered matches in the directories
+        let m = score("data", "dir/a/t/a/data.csv").unwrap();
+        assert_eq!(m.positions, vec![10, 11, 12, 13]);
+
+        let files: Vec<String> = ["reports/by_user_year.csv", "bu/y.json", "users/buy.json"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let ranked: Vec<&str> = rank("buy", &files, 10)
+            .into_iter()
+            .map(|(i, _)| files[i].as_str())
+            .collect();
+        assert_eq!(ranked[0], "users/buy.json");
+        assert_eq!(ranked.len(), 3);
+        assert_eq!(rank("buy", &files, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_index_respects_gitignore() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_finder_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested/deeper")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        fs::write(dir.join("top.csv"), "a\n1\n").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        fs::write(dir.join("nested/deeper/events.json.gz"), "").unwrap();
+        fs::write(dir.join("target/build.csv"), "a\n1\n").unwrap();
+
+        let mut index = FileIndex::spawn(&dir);
+        for _ in 0..500 {
+            index.poll();
+            if index.done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(index.done);
+        index.files.sort();
+        assert_eq!(index.files, ["nested/deeper/events.json.gz", "top.csv"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
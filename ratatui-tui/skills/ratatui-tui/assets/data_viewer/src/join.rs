@@ -0,0 +1,291 @@
+//! Joining two tables on a key column
+//!
+//! Rows are matched on equal key values, compared as text with surrounding
+//! whitespace ignored; empty keys never match. Column names present in both
+//! tables are prefixed with their table's name (`hosts.name`,
+//! `inventory.name`) so every output column stays distinct.
+
+use std::collections::{HashMap, HashSet};
+
+/// Which rows a join keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    /// Only rows whose key is in both tables
+    Inner,
+    /// Every left row, with empty cells where the right table has no match
+    Left,
+    /// Every row of both tables
+    Outer,
+}
+
+impl JoinKind {
+    pub const ALL: [JoinKind; 3] = [JoinKind::Inner, JoinKind::Left, JoinKind::Outer];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Outer => "outer",
+        }
+    }
+
+    /// One-line explanation shown in the join wizard
+    pub fn description(&self) -> &'static str {
+        match self {
+            JoinKind::Inner => "only rows whose key is in both files",
+            JoinKind::Left => "every row of the first file, matched where possible",
+            JoinKind::Outer => "every row of both files, matched where possible",
+        }
+    }
+}
+
+/// One side of a join
+#[derive(Debug, Clone, Copy)]
+pub struct Table<'a> {
+    /// Prefix for colliding column names, usually the file name without
+    /// extensions
+    pub name: &'a str,
+    pub headers: &'a [String],
+    pub rows: &'a [Vec<String>],
+    /// Index of the key column
+    pub key: usize,
+}
+
+/// A key value found on only one side, with the number of rows carrying it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmatchedKey {
+    pub key: String,
+    pub rows: usize,
+}
+
+/// Joined table and the keys that found no partner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Joined {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Left keys missing from the right table, in order of first appearance
+    pub unmatched_left: Vec<UnmatchedKey>,
+    /// Right keys missing from the left table, in order of first appearance
+    pub unmatched_right: Vec<UnmatchedKey>,
+}
+
+/// Join two tables on their key columns
+///
+/// Output rows hold every left column followed by every right column; a key
+/// that appears several times on both sides produces every pairing, in left
+/// row order. Right rows without a match come last in an outer join.
+pub fn join(left: Table, right: Table, kind: JoinKind) -> Joined {
+    let key_of = |table: &Table, row: usize| {
+        table.rows[row]
+            .get(table.key)
+            .map_or("", |cell| cell.trim())
+            .to_string()
+    };
+
+    let mut right_index: HashMap<String, Vec<usize>> = HashMap::new();
+    for row in 0..right.rows.len() {
+        let key = key_of(&right, row);
+        if !key.is_empty() {
+            right_index.entry(key).or_default().push(row);
+        }
+    }
+
+    let cells = |table: &Table, row: Option<usize>| -> Vec<String> {
+        (0..table.headers.len())
+            .map(|col| {
+                row.and_then(|r| table.rows[r].get(col))
+                    .cloned()
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+
+    let mut rows = Vec::new();
+    let mut unmatched_left = Unmatched::default();
+    let mut matched_right = HashSet::new();
+    for row in 0..left.rows.len() {
+        let key = key_of(&left, row);
+        match right_index.get(&key).filter(|_| !key.is_empty()) {
+            Some(partners) => {
+                for &partner in partners {
+                    let mut joined = cells(&left, Some(row));
+                    joined.extend(cells(&right, Some(partner)));
+                    rows.push(joined);
+                }
+                matched_right.insert(key);
+            }
+            None => {
+                unmatched_left.add(key);
+                if kind != JoinKind::Inner {
+                    let mut joined = cells(&left, Some(row));
+                    joined.extend(cells(&right, None));
+                    rows.push(joined);
+                }
+            }
+        }
+    }
+
+    let mut unmatched_right = Unmatched::default();
+    for row in 0..right.rows.len() {
+        let key = key_of(&right, row);
+        if key.is_empty() || !matched_right.contains(&key) {
+            unmatched_right.add(key);
+            if kind == JoinKind::Outer {
+                let mut joined = cells(&left, None);
+                joined.extend(cells(&right, Some(row)));
+                rows.push(joined);
+            }
+        }
+    }
+
+    Joined {
+        headers: joined_headers(&left, &right),
+        rows,
+        unmatched_left: unmatched_left.keys,
+        unmatched_right: unmatched_right.keys,
+    }
+}
+
+/// Unmatched keys with their row counts, in order of first appearance
+#[derive(Default)]
+struct Unmatched {
+    keys: Vec<UnmatchedKey>,
+    positions: HashMap<String, usize>,
+}
+
+impl Unmatched {
+    fn add(&mut self, key: String) {
+        match self.positions.get(&key) {
+            Some(&i) => self.keys[i].rows += 1,
+            None => {
+                self.positions.insert(key.clone(), self.keys.len());
+                self.keys.push(UnmatchedKey { key, rows: 1 });
+            }
+        }
+    }
+}
+
+/// Column names of the joined table, prefixing names found on both sides
+fn joined_headers(left: &Table, right: &Table) -> Vec<String> {
+    // Joining a file with itself still needs two distinct prefixes
+    let right_name = if left.name == right.name {
+        format!("{}_2", right.name)
+    } else {
+        right.name.to_string()
+    };
+    let prefix = |name: &str, header: &String, other: &[String]| {
+        if other.contains(header) {
+            format!("{}.{}", name, header)
+        } else {
+            header.clone()
+        }
+    };
+    let mut headers: Vec<String> = left
+        .headers
+        .iter()
+        .map(|h| prefix(left.name, h, right.headers))
+        .collect();
+    headers.extend(
+        right
+            .headers
+            .iter()
+            .map(|h| prefix(&right_name, h, left.headers)),
+    );
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn rows(items: &[&[&str]]) -> Vec<Vec<String>> {
+        items.iter().map(|r| strings(r)).collect()
+    }
+
+    #[test]
+    fn test_join_kinds() {
+        let host_headers = strings(&["host", "ip"]);
+        let hosts = rows(&[&["web1", "10.0.0.1"], &["web2", "10.0.0.2"], &["db1", ""]]);
+        let inventory_headers = strings(&["name", "host", "rack"]);
+        let inventory = rows(&[
+            &["Web one", " web1", "A1"],
+            &["Cache", "cache1", "B2"],
+            &["Web one again", "web1", "A2"],
+            &["Nameless", "", "C3"],
+        ]);
+        let left = Table {
+            name: "hosts",
+            headers: &host_headers,
+            rows: &hosts,
+            key: 0,
+        };
+        let right = Table {
+            name: "inventory",
+            headers: &inventory_headers,
+            rows: &inventory,
+            key: 1,
+        };
+
+        let inner = join(left, right, JoinKind::Inner);
+        assert_eq!(
+            inner.headers,
+            ["hosts.host", "ip", "name", "inventory.host", "rack"]
+        );
+        assert_eq!(
+            inner.rows,
+            rows(&[
+                &["web1", "10.0.0.1", "Web one", " web1", "A1"],
+                &["web1", "10.0.0.1", "Web one again", "web1", "A2"],
+            ])
+        );
+        let keys = |unmatched: &[UnmatchedKey]| {
+            unmatched
+                .iter()
+                .map(|u| (u.key.clone(), u.rows))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(&inner.unmatched_left),
+            [("web2".to_string(), 1), ("db1".to_string(), 1)]
+        );
+        assert_eq!(
+            keys(&inner.unmatched_right),
+            [("cache1".to_string(), 1), ("".to_string(), 1)]
+        );
+
+        let left_join = join(left, right, JoinKind::Left);
+        assert_eq!(left_join.rows.len(), 4);
+        assert_eq!(
+            left_join.rows[2],
+            strings(&["web2", "10.0.0.2", "", "", ""])
+        );
+
+        let outer = join(left, right, JoinKind::Outer);
+        assert_eq!(outer.rows.len(), 6);
+        assert_eq!(outer.rows[4], strings(&["", "", "Cache", "cache1", "B2"]));
+        assert_eq!(outer.unmatched_left, inner.unmatched_left);
+    }
+
+    #[test]
+    fn test_self_join_prefixes() {
+        let headers = strings(&["id", "parent"]);
+        let data = rows(&[&["1", ""], &["2", "1"]]);
+        let child = Table {
+            name: "tree",
+            headers: &headers,
+            rows: &data,
+            key: 1,
+        };
+        let parent = Table { key: 0, ..child };
+        let joined = join(child, parent, JoinKind::Inner);
+        assert_eq!(
+            joined.headers,
+            ["tree.id", "tree.parent", "tree_2.id", "tree_2.parent"]
+        );
+        assert_eq!(joined.rows, rows(&[&["2", "1", "1", ""]]));
+    }
+}
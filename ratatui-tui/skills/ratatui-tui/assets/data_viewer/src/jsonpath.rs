@@ -0,0 +1,342 @@
+//! Path expressions that pick the part of a JSON document to tabulate
+//!
+//! Accepts the common subset of jq (`.data.items[]`, `.["odd key"][0]`) and
+//! JSONPath (`$.data.items[*]`, `$['odd key'][0]`) syntax. Also finds the
+//! largest array of objects in a document to suggest as a starting point.
+
+use crate::expr::ExprError;
+use serde_json::Value;
+
+/// One step of a path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Object member by name
+    Key(String),
+    /// Array element; negative indices count from the end
+    Index(i64),
+    /// Every element of an array (or value of an object): `[]`, `[*]`, `.*`
+    Each,
+}
+
+/// A parsed path, with the input position of each step for error reporting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    pub steps: Vec<(Step, usize)>,
+}
+
+/// Parse a jq- or JSONPath-style path; an empty path selects the whole document
+pub fn parse(input: &str) -> Result<JsonPath, ExprError> {
+    let chars: Vec<char> = input.trim_end().chars().collect();
+    let mut i = chars.iter().take_while(|c| c.is_whitespace()).count();
+    let mut steps = Vec::new();
+
+    if chars.get(i) == Some(&'$') {
+        i += 1;
+    }
+
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            '.' => {
+                i += 1;
+                match chars.get(i) {
+                    None | Some('[') => {}
+                    Some('*') => {
+                        i += 1;
+                        steps.push((Step::Each, start));
+                    }
+                    Some('"') => {
+                        let (key, next) = quoted(&chars, i)?;
+                        i = next;
+                        steps.push((Step::Key(key), start));
+                    }
+                    Some(c) if is_name_char(*c) => {
+                        let end = i + chars[i..].iter().take_while(|c| is_name_char(**c)).count();
+                        steps.push((Step::Key(chars[i..end].iter().collect()), start));
+                        i = end;
+                    }
+                    Some(c) => {
+                        return Err(ExprError::new(i, format!("Unexpected '{}' after '.'", c)));
+                    }
+                }
+            }
+            '[' => {
+                i += 1;
+                let step = match chars.get(i) {
+                    Some(']') => Step::Each,
+                    Some('*') => {
+                        i += 1;
+                        Step::Each
+                    }
+                    Some('"') | Some('\'') => {
+                        let (key, next) = quoted(&chars, i)?;
+                        i = next;
+                        Step::Key(key)
+                    }
+                    Some(c) if c.is_ascii_digit() || *c == '-' => {
+                        let end = i
+                            + 1
+                            + chars[i + 1..]
+                                .iter()
+                                .take_while(|c| c.is_ascii_digit())
+                                .count();
+                        let text: String = chars[i..end].iter().collect();
+                        let index = text
+                            .parse()
+                            .map_err(|_| ExprError::new(i, format!("Invalid index '{}'", text)))?;
+                        i = end;
+                        Step::Index(index)
+                    }
+                    _ => return Err(ExprError::new(i, "Expected an index, a quoted key, * or ]")),
+                };
+                if chars.get(i) != Some(&']') {
+                    return Err(ExprError::new(i, "Expected ']'"));
+                }
+                i += 1;
+                steps.push((step, start));
+            }
+            c => return Err(ExprError::new(i, format!("Unexpected '{}'", c))),
+        }
+    }
+
+    Ok(JsonPath { steps })
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '$'
+}
+
+/// Read a quoted key starting at `start`, returning it and the index after
+/// the closing quote
+fn quoted(chars: &[char], start: usize) -> Result<(String, usize), ExprError> {
+    let quote = chars[start];
+    let mut key = String::new();
+    let mut i = start + 1;
+    loop {
+        match chars.get(i) {
+            None => return Err(ExprError::new(start, "Unterminated quoted key")),
+            Some('\\') if i + 1 < chars.len() => {
+                key.push(chars[i + 1]);
+                i += 2;
+            }
+            Some(c) if *c == quote => return Ok((key, i + 1)),
+            Some(c) => {
+                key.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Name of a JSON value's type, for error messages
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl JsonPath {
+    /// Apply the path to a document
+    ///
+    /// A path containing `[]` yields an array of everything it selected;
+    /// otherwise the single selected value is returned as is.
+    pub fn select(&self, root: &Value) -> Result<Value, ExprError> {
+        let mut current: Vec<&Value> = vec![root];
+
+        for (step, pos) in &self.steps {
+            let mut next = Vec::new();
+            for value in &current {
+                match (step, value) {
+                    (Step::Key(key), Value::Object(obj)) => next.extend(obj.get(key)),
+                    (Step::Index(index), Value::Array(arr)) => {
+                        let index = if *index < 0 {
+                            arr.len() as i64 + index
+                        } else {
+                            *index
+                        };
+                        next.extend(usize::try_from(index).ok().and_then(|i| arr.get(i)));
+                    }
+                    (Step::Each, Value::Array(arr)) => next.extend(arr.iter()),
+                    (Step::Each, Value::Object(obj)) => next.extend(obj.values()),
+                    (Step::Key(key), other) if current.len() == 1 => {
+                        return Err(ExprError::new(
+                            *pos,
+                            format!("Cannot look up '{}' in {}", key, type_name(other)),
+                        ));
+                    }
+                    (Step::Index(_), other) | (Step::Each, other) if current.len() == 1 => {
+                        return Err(ExprError::new(
+                            *pos,
+                            format!("Cannot index into {}", type_name(other)),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            let empty_containers =
+                *step == Step::Each && current.iter().all(|v| v.is_array() || v.is_object());
+            if next.is_empty() && !current.is_empty() && !empty_containers {
+                let message = match step {
+                    Step::Key(key) => format!("No key '{}'", key),
+                    Step::Index(index) => format!("Index {} is out of range", index),
+                    Step::Each => "Nothing to iterate over".to_string(),
+                };
+                return Err(ExprError::new(*pos, message));
+            }
+            current = next;
+        }
+
+        if self.steps.iter().any(|(step, _)| *step == Step::Each) {
+            Ok(Value::Array(current.into_iter().cloned().collect()))
+        } else {
+            Ok(current.first().map(|v| (*v).clone()).unwrap_or(Value::Null))
+        }
+    }
+}
+
+/// Write a step in jq syntax
+fn push_step(path: &mut String, step: &Step) {
+    match step {
+        Step::Key(key)
+            if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+        {
+            path.push('.');
+            path.push_str(key);
+        }
+        Step::Key(key) => path.push_str(&format!(".[{}]", Value::String(key.clone()))),
+        Step::Index(index) => path.push_str(&format!("[{}]", index)),
+        Step::Each => path.push_str("[]"),
+    }
+}
+
+/// Find the largest array of objects in a document, returning a jq path that
+/// tabulates it and the array's length
+///
+/// Only the first element of each array is searched, which keeps this cheap
+/// on large documents.
+pub fn suggest(root: &Value) -> Option<(String, usize)> {
+    fn walk(value: &Value, path: &mut String, best: &mut Option<(String, usize)>) {
+        match value {
+            Value::Array(arr) => {
+                let objects = arr.iter().filter(|v| v.is_object()).count();
+                if objects > 0
+                    && objects * 2 >= arr.len()
+                    && best.as_ref().is_none_or(|(_, n)| arr.len() > *n)
+                {
+                    let mut found = path.clone();
+                    push_step(&mut found, &Step::Each);
+                    *best = Some((found, arr.len()));
+                }
+                if let Some(first) = arr.first() {
+                    let len = path.len();
+                    push_step(path, &Step::Index(0));
+                    walk(first, path, best);
+                    path.truncate(len);
+                }
+            }
+            Value::Object(obj) => {
+                for (key, child) in obj {
+                    let len = path.len();
+                    push_step(path, &Step::Key(key.clone()));
+                    walk(child, path, best);
+                    path.truncate(len);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut best = None;
+    walk(root, &mut String::new(), &mut best);
+    best.map(|(path, len)| {
+        if path.starts_with('[') {
+            (format!(".{}", path), len)
+        } else {
+            (path, len)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc() -> Value {
+        json!({
+            "data": {"items": [{"id": 1}, {"id": 2}, {"id": 3}], "total": 3},
+            "meta": {"pages": [{"n": 1}]},
+            "odd key": [10, 20]
+        })
+    }
+
+    #[test]
+    fn test_jq_and_jsonpath_forms_agree() {
+        let jq = parse(".data.items[]").unwrap();
+        let jsonpath = parse("$.data.items[*]").unwrap();
+        let bracketed = parse("$['data'][\"items\"][*]").unwrap();
+        assert_eq!(
+            jq.steps.iter().map(|(s, _)| s).collect::<Vec<_>>(),
+            jsonpath.steps.iter().map(|(s, _)| s).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            jq.steps.iter().map(|(s, _)| s).collect::<Vec<_>>(),
+            bracketed.steps.iter().map(|(s, _)| s).collect::<Vec<_>>()
+        );
+        assert!(parse(".").unwrap().steps.is_empty());
+        assert!(parse("$").unwrap().steps.is_empty());
+    }
+
+    #[test]
+    fn test_select() {
+        let doc = doc();
+        assert_eq!(
+            parse(".data.items[]").unwrap().select(&doc).unwrap(),
+            json!([{"id": 1}, {"id": 2}, {"id": 3}])
+        );
+        assert_eq!(
+            parse(".data.items[-1].id").unwrap().select(&doc).unwrap(),
+            json!(3)
+        );
+        assert_eq!(
+            parse(".data.items[].id").unwrap().select(&doc).unwrap(),
+            json!([1, 2, 3])
+        );
+        assert_eq!(
+            parse(".[\"odd key\"]").unwrap().select(&doc).unwrap(),
+            json!([10, 20])
+        );
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let doc = doc();
+        let err = parse(".data.itms[]").unwrap().select(&doc).unwrap_err();
+        assert_eq!(err, ExprError::new(5, "No key 'itms'"));
+
+        let err = parse(".data.total[]").unwrap().select(&doc).unwrap_err();
+        assert_eq!(err.pos, 11);
+
+        let err = parse(".data[0").unwrap_err();
+        assert_eq!(err.pos, 7);
+    }
+
+    #[test]
+    fn test_suggest_largest_array_of_objects() {
+        assert_eq!(suggest(&doc()), Some((".data.items[]".to_string(), 3)));
+        assert_eq!(
+            suggest(&json!([{"a": 1}, {"a": 2}])),
+            Some((".[]".to_string(), 2))
+        );
+        assert_eq!(
+            suggest(&json!({"x y": [{"rows": [{}, {}, {}, {}]}]})),
+            Some((".[\"x y\"][0].rows[]".to_string(), 4))
+        );
+        assert_eq!(suggest(&json!({"a": [1, 2, 3]})), None);
+    }
+}
@@ -0,0 +1,114 @@
+//! Streaming the elements of a top-level JSON array
+//!
+//! A large JSON file is almost always one big array of records. Rather than
+//! building the whole document as a `serde_json::Value`, the array is walked
+//! element by element so only the current element is held while the caller
+//! turns it into a row (or just counts it).
+
+use crate::compression::Counted;
+use serde::de::{DeserializeOwned, Deserializer as _, SeqAccess, Visitor};
+use std::{fmt, io::BufRead, marker::PhantomData};
+
+/// Call `f` with each element of the top-level array in `reader`, in order
+///
+/// Returns the number of bytes read, or `None` without consuming anything
+/// past leading whitespace when the document is not an array. Errors stop
+/// the walk; elements already passed to `f` are not taken back.
+pub fn for_each_element<T, R>(mut reader: R, f: impl FnMut(T)) -> serde_json::Result<Option<u64>>
+where
+    T: DeserializeOwned,
+    R: BufRead,
+{
+    let mut skipped = 0;
+    loop {
+        let buf = reader.fill_buf().map_err(serde_json::Error::io)?;
+        let Some(&first) = buf.first() else {
+            return Ok(None);
+        };
+        if buf.starts_with(b"\xEF\xBB\xBF") {
+            reader.consume(3);
+            skipped += 3;
+        } else if first.is_ascii_whitespace() {
+            reader.consume(1);
+            skipped += 1;
+        } else if first == b'[' {
+            break;
+        } else {
+            return Ok(None);
+        }
+    }
+
+    let mut counted = Counted {
+        inner: reader,
+        bytes: skipped,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(&mut counted);
+    deserializer.deserialize_seq(Elements {
+        f,
+        element: PhantomData,
+    })?;
+    deserializer.end()?;
+    Ok(Some(counted.bytes))
+}
+
+/// Visitor handing each array element to a callback as soon as it is parsed
+struct Elements<T, F> {
+    f: F,
+    element: PhantomData<T>,
+}
+
+impl<'de, T, F> Visitor<'de> for Elements<T, F>
+where
+    T: DeserializeOwned,
+    F: FnMut(T),
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(element) = seq.next_element::<T>()? {
+            (self.f)(element);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::IgnoredAny;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_streams_array_elements() {
+        let input = b"\xEF\xBB\xBF\n [{\"id\": 1}, {\"id\": [2, 3]}, \"x\"]\n";
+        let mut items = Vec::new();
+        let read = for_each_element(&input[..], |item: Value| items.push(item)).unwrap();
+        assert_eq!(read, Some(input.len() as u64));
+        assert_eq!(items, [json!({"id": 1}), json!({"id": [2, 3]}), json!("x")]);
+
+        let mut count = 0;
+        for_each_element(&b"[1, [2], {}]"[..], |_: IgnoredAny| count += 1).unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_other_documents_and_errors() {
+        let mut count = 0;
+        let result = for_each_element(&b"  {\"a\": 1}"[..], |_: IgnoredAny| count += 1);
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(
+            for_each_element(&b""[..], |_: IgnoredAny| ()).unwrap(),
+            None
+        );
+
+        // Elements before the error have been seen
+        let result = for_each_element(&b"[1, 2, oops]"[..], |_: IgnoredAny| count += 1);
+        assert!(result.is_err());
+        assert_eq!(count, 2);
+        assert!(for_each_element(&b"[1] 2"[..], |_: IgnoredAny| ()).is_err());
+    }
+}
@@ -0,0 +1,478 @@
+//! Key bindings of the main views, in one table
+//!
+//! `App::handle_key` finds the action for a key by looking it up in
+//! [`KEYMAP`], and the help overlay and which-key popup list the same table,
+//! so what is shown can't drift from what the keys do. List popups (pivot
+//! setup, pickers, the join wizard) have a context of their own; popups with
+//! an input line (filter, SQL, paths) keep their own keys, listed in their
+//! borders.
+
+use crossterm::event::KeyCode::{
+    self, BackTab, Backspace, Char, Down, Enter, Esc, Left, Right, Tab, Up,
+};
+use KeyContext::*;
+
+/// When a binding applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyContext {
+    /// Anywhere without a popup open
+    Global,
+    FileList,
+    /// The data view panel, whatever its tab
+    DataView,
+    /// The table tab showing one row as a record
+    Record,
+    Raw,
+    Chart,
+    /// While a visual range is being marked
+    Visual,
+    /// While some rows are marked
+    Marks,
+    /// While a workbook with several sheets is loaded
+    Workbook,
+    /// The key after a prefix key, listed in the which-key popup
+    Prefix(char),
+    Modal,
+    Help,
+    Finder,
+    PivotEditor,
+    ChartPicker,
+    ColumnPicker,
+    RecentPicker,
+    BufferPicker,
+    SheetPicker,
+    JoinWizard,
+}
+
+impl KeyContext {
+    /// Heading of the context's section in the help overlay
+    pub fn title(self) -> String {
+        match self {
+            KeyContext::Global => "Everywhere".to_string(),
+            KeyContext::FileList => "File list".to_string(),
+            KeyContext::DataView => "Data view".to_string(),
+            KeyContext::Record => "Record view (T)".to_string(),
+            KeyContext::Raw => "Raw tab".to_string(),
+            KeyContext::Chart => "Chart tab".to_string(),
+            KeyContext::Visual => "Visual range (V)".to_string(),
+            KeyContext::Marks => "With marked rows".to_string(),
+            KeyContext::Workbook => "Workbooks".to_string(),
+            KeyContext::Prefix(c) => format!("After {}", c),
+            KeyContext::Modal => "Message dialogs".to_string(),
+            KeyContext::Help => "This help".to_string(),
+            KeyContext::Finder => "File finder (Ctrl-p)".to_string(),
+            KeyContext::PivotEditor => "Pivot setup (P)".to_string(),
+            KeyContext::ChartPicker => "Chart columns (c)".to_string(),
+            KeyContext::ColumnPicker => "Columns (C)".to_string(),
+            KeyContext::RecentPicker => "Recent files (R)".to_string(),
+            KeyContext::BufferPicker => "Buffers (b)".to_string(),
+            KeyContext::SheetPicker => "Sheets (.)".to_string(),
+            KeyContext::JoinWizard => "Join wizard (J)".to_string(),
+        }
+    }
+}
+
+/// What a key does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    SwitchPanel,
+    NextTab,
+    /// Select the tab numbered by the key
+    SelectTab,
+    ShowHelp,
+    CloseHelp,
+    HelpDown,
+    HelpUp,
+    DismissModal,
+    OpenFinder,
+    FinderUp,
+    FinderDown,
+    MoveDown,
+    MoveUp,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    /// Jump to a row, file or raw line by number
+    GotoNumber,
+    /// Jump to a column, record field or file by name
+    GotoName,
+    OpenSelected,
+    ParentDir,
+    CycleFileSort,
+    ReverseFileSort,
+    ToggleHidden,
+    ToggleAllFiles,
+    GlobFilter,
+    Refresh,
+    DrillDown,
+    Back,
+    ScrollLeft,
+    ScrollRight,
+    ToggleErrors,
+    ToggleCsvMode,
+    CycleEncoding,
+    Pivot,
+    Query,
+    JsonPath,
+    SheetPicker,
+    Schema,
+    InvalidRows,
+    ToggleMark,
+    ToggleVisual,
+    CancelVisual,
+    NextMark,
+    PrevMark,
+    /// Read the next page of a database table
+    NextDbPage,
+    PrevDbPage,
+    /// Allow deleting marked rows, or stop
+    ToggleEditing,
+    Filter,
+    Columns,
+    Recent,
+    Buffers,
+    NextBuffer,
+    PrevBuffer,
+    Join,
+    ToggleRecord,
+    CloseRecord,
+    NextField,
+    PrevField,
+    NextRecord,
+    PrevRecord,
+    RawDown,
+    RawUp,
+    ChartColumns,
+    ChartKind,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    PanLeft,
+    PanRight,
+    /// Wait for the next key and show what it can be
+    Prefix(char),
+    ExportMarked,
+    ExportMarkedShown,
+    CopyMarked,
+    CopyMarkedShown,
+    FilterMarked,
+    DeleteMarked,
+    ClearMarks,
+    /// Move the selection of a list popup
+    ListDown,
+    ListUp,
+    /// Open, apply or switch to what a list popup has selected
+    Choose,
+    ClosePopup,
+    /// Go back a step of the join wizard, closing it from the first
+    StepBack,
+    /// Remove the selected recent file or buffer from its list
+    RemoveEntry,
+    PivotGroupBy,
+    /// Toggle the aggregate whose key was pressed
+    PivotAggregate,
+    ChartSeries,
+    ChartX,
+    SortColumn,
+    HideColumn,
+    PinColumn,
+    MoveColumnDown,
+    MoveColumnUp,
+    ResetColumns,
+    AddComputed,
+    RemoveComputed,
+}
+
+/// Keys bound to an action in a context
+#[derive(Debug)]
+pub struct Binding {
+    pub context: KeyContext,
+    pub keys: &'static [KeyCode],
+    /// Whether the keys are pressed with Ctrl
+    pub ctrl: bool,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+const fn bind(
+    context: KeyContext,
+    keys: &'static [KeyCode],
+    action: Action,
+    description: &'static str,
+) -> Binding {
+    Binding {
+        context,
+        keys,
+        ctrl: false,
+        action,
+        description,
+    }
+}
+
+const fn ctrl(
+    context: KeyContext,
+    keys: &'static [KeyCode],
+    action: Action,
+    description: &'static str,
+) -> Binding {
+    Binding {
+        ctrl: true,
+        ..bind(context, keys, action, description)
+    }
+}
+
+/// Every binding, earlier ones winning when several apply
+#[rustfmt::skip]
+pub const KEYMAP: &[Binding] = &[
+    bind(Modal, &[Enter, Esc, Char(' ')], Action::DismissModal, "close the dialog"),
+    bind(Help, &[Char('j'), Down], Action::HelpDown, "scroll down"),
+    bind(Help, &[Char('k'), Up], Action::HelpUp, "scroll up"),
+    bind(Help, &[Char('?'), Esc, Char('q')], Action::CloseHelp, "close the help"),
+    ctrl(Finder, &[Char('p')], Action::FinderUp, "previous match"),
+    ctrl(Finder, &[Char('n')], Action::FinderDown, "next match"),
+    bind(Visual, &[Esc], Action::CancelVisual, "cancel the range"),
+    bind(Record, &[Esc], Action::CloseRecord, "back to the table"),
+    bind(Global, &[Char('q'), Esc], Action::Quit, "quit"),
+    bind(Global, &[Char('?')], Action::ShowHelp, "show this help"),
+    bind(Global, &[Tab], Action::SwitchPanel, "switch between the file list and data view"),
+    bind(Global, &[BackTab], Action::NextTab, "next tab"),
+    bind(Global, &[Char('1'), Char('2'), Char('3')], Action::SelectTab, "table, raw or chart tab"),
+    ctrl(Global, &[Char('p')], Action::OpenFinder, "find a file under the current directory"),
+    bind(Chart, &[Char('c')], Action::ChartColumns, "choose the x column and series"),
+    bind(Chart, &[Char('t')], Action::ChartKind, "switch line, scatter and bar charts"),
+    bind(Chart, &[Char('+'), Char('=')], Action::ZoomIn, "zoom in"),
+    bind(Chart, &[Char('-')], Action::ZoomOut, "zoom out"),
+    bind(Chart, &[Char('0')], Action::ResetZoom, "show the whole range"),
+    bind(Chart, &[Char('h'), Left], Action::PanLeft, "pan left"),
+    bind(Chart, &[Char('l'), Right], Action::PanRight, "pan right"),
+    bind(Record, &[Char('j'), Down], Action::NextField, "next field"),
+    bind(Record, &[Char('k'), Up], Action::PrevField, "previous field"),
+    bind(Record, &[Char('J')], Action::NextRecord, "next record"),
+    bind(Record, &[Char('K')], Action::PrevRecord, "previous record"),
+    bind(DataView, &[Char('T')], Action::ToggleRecord, "show the selected row as a record"),
+    bind(Raw, &[Char('j'), Down], Action::RawDown, "scroll down"),
+    bind(Raw, &[Char('k'), Up], Action::RawUp, "scroll up"),
+    bind(Global, &[Char('j'), Down], Action::MoveDown, "next row or file"),
+    bind(Global, &[Char('k'), Up], Action::MoveUp, "previous row or file"),
+    bind(Global, &[KeyCode::PageDown], Action::PageDown, "next page"),
+    bind(Global, &[KeyCode::PageUp], Action::PageUp, "previous page"),
+    ctrl(Global, &[Char('d')], Action::HalfPageDown, "half a page down"),
+    ctrl(Global, &[Char('u')], Action::HalfPageUp, "half a page up"),
+    bind(Global, &[Char('g'), KeyCode::Home], Action::Top, "first row, file or line"),
+    bind(Global, &[Char('G'), KeyCode::End], Action::Bottom, "last row, file or line"),
+    bind(Global, &[Char(':')], Action::GotoNumber, "go to a row, file or line by number"),
+    bind(Global, &[Char('c')], Action::GotoName, "go to a column (in the file list, a file) by name"),
+    bind(FileList, &[Enter, Char('l'), Right], Action::OpenSelected, "open the file, directory or table, or expand a database"),
+    bind(DataView, &[Enter], Action::DrillDown, "drill into the pivot group"),
+    bind(DataView, &[Backspace], Action::Back, "leave the query, pivot, subset, filter or sort"),
+    bind(FileList, &[Char('h'), Left], Action::ParentDir, "parent directory (on a table, collapse its database)"),
+    bind(Global, &[Char('h'), Left], Action::ScrollLeft, "scroll the columns left"),
+    bind(Global, &[Char('l'), Right], Action::ScrollRight, "scroll the columns right"),
+    bind(FileList, &[Char('s')], Action::CycleFileSort, "sort by name, size or time"),
+    bind(FileList, &[Char('S')], Action::ReverseFileSort, "reverse the sort"),
+    bind(FileList, &[Char('H')], Action::ToggleHidden, "show hidden files"),
+    bind(FileList, &[Char('A')], Action::ToggleAllFiles, "show all files"),
+    bind(FileList, &[Char('/')], Action::GlobFilter, "filter names with a glob"),
+    bind(Global, &[Char('r')], Action::Refresh, "refresh the file list"),
+    bind(Global, &[Char('e')], Action::ToggleErrors, "show parse and schema errors"),
+    bind(Global, &[Char('M')], Action::ToggleCsvMode, "switch strict and lenient CSV parsing"),
+    bind(Global, &[Char('E')], Action::CycleEncoding, "cycle the forced encoding"),
+    bind(Global, &[Char('P')], Action::Pivot, "group-by pivot"),
+    bind(Global, &[Char('Q')], Action::Query, "SQL console"),
+    bind(Workbook, &[Char('.')], Action::SheetPicker, "choose the sheet to show"),
+    bind(Global, &[Char('.')], Action::JsonPath, "choose the JSON array to tabulate"),
+    bind(Global, &[Char('v')], Action::Schema, "validate JSON rows against a schema"),
+    bind(Global, &[Char('I')], Action::InvalidRows, "show only the invalid rows"),
+    bind(DataView, &[Char(' ')], Action::ToggleMark, "mark the row"),
+    bind(DataView, &[Char('V')], Action::ToggleVisual, "mark a visual range"),
+    bind(DataView, &[Char(']')], Action::NextMark, "next marked row"),
+    bind(DataView, &[Char('[')], Action::PrevMark, "previous marked row"),
+    bind(DataView, &[Char('>')], Action::NextDbPage, "next page of a database table"),
+    bind(DataView, &[Char('<')], Action::PrevDbPage, "previous page of a database table"),
+    bind(DataView, &[Char('i')], Action::ToggleEditing, "edit the table, so marked rows can be deleted"),
+    bind(DataView, &[Char('f')], Action::Filter, "filter rows with an expression"),
+    bind(DataView, &[Char('C')], Action::Columns, "sort, hide, reorder, pin and add columns"),
+    bind(Global, &[Char('R')], Action::Recent, "recent files"),
+    bind(Global, &[Char('b')], Action::Buffers, "open buffers"),
+    bind(Global, &[Char('J')], Action::Join, "join with another file"),
+    bind(Global, &[Char('}')], Action::NextBuffer, "next buffer"),
+    bind(Global, &[Char('{')], Action::PrevBuffer, "previous buffer"),
+    bind(Marks, &[Char('m')], Action::Prefix('m'), "marked row actions"),
+    bind(Prefix('m'), &[Char('e')], Action::ExportMarked, "export to a new CSV file"),
+    bind(Prefix('m'), &[Char('E')], Action::ExportMarkedShown, "export the shown columns only"),
+    bind(Prefix('m'), &[Char('y')], Action::CopyMarked, "copy as tab-separated text"),
+    bind(Prefix('m'), &[Char('Y')], Action::CopyMarkedShown, "copy the shown columns only"),
+    bind(Prefix('m'), &[Char('f')], Action::FilterMarked, "show only the marked rows"),
+    bind(Prefix('m'), &[Char('d')], Action::DeleteMarked, "delete from the table while editing (file unchanged)"),
+    bind(Prefix('m'), &[Char('u')], Action::ClearMarks, "clear all marks"),
+    bind(PivotEditor, &[Char('j'), Down], Action::ListDown, "next column"),
+    bind(PivotEditor, &[Char('k'), Up], Action::ListUp, "previous column"),
+    bind(PivotEditor, &[Char('g'), Char(' ')], Action::PivotGroupBy, "group by the column"),
+    bind(PivotEditor, &[Char('c'), Char('s'), Char('a'), Char('n'), Char('x'), Char('d')], Action::PivotAggregate, "count, sum, avg, min, max or distinct count"),
+    bind(PivotEditor, &[Enter], Action::Choose, "show the pivot"),
+    bind(PivotEditor, &[Esc], Action::ClosePopup, "cancel"),
+    bind(ChartPicker, &[Char('j'), Down], Action::ListDown, "next column"),
+    bind(ChartPicker, &[Char('k'), Up], Action::ListUp, "previous column"),
+    bind(ChartPicker, &[Char('y'), Char(' ')], Action::ChartSeries, "plot the column as a series"),
+    bind(ChartPicker, &[Char('x')], Action::ChartX, "use the column as the x axis"),
+    bind(ChartPicker, &[Esc, Enter, Char('c')], Action::ClosePopup, "close"),
+    bind(ColumnPicker, &[Char('j'), Down], Action::ListDown, "next column"),
+    bind(ColumnPicker, &[Char('k'), Up], Action::ListUp, "previous column"),
+    bind(ColumnPicker, &[Char('s')], Action::SortColumn, "sort ascending, descending or not by the column"),
+    bind(ColumnPicker, &[Char(' '), Char('h')], Action::HideColumn, "hide or show the column"),
+    bind(ColumnPicker, &[Char('p')], Action::PinColumn, "pin the column to the left"),
+    bind(ColumnPicker, &[Char('J')], Action::MoveColumnDown, "move the column down"),
+    bind(ColumnPicker, &[Char('K')], Action::MoveColumnUp, "move the column up"),
+    bind(ColumnPicker, &[Char('r')], Action::ResetColumns, "show every column in file order, unpinned"),
+    bind(ColumnPicker, &[Char('a')], Action::AddComputed, "add a computed column"),
+    bind(ColumnPicker, &[Char('x')], Action::RemoveComputed, "delete the computed column"),
+    bind(ColumnPicker, &[Esc, Enter, Char('C')], Action::ClosePopup, "close"),
+    bind(RecentPicker, &[Char('j'), Down], Action::ListDown, "next file"),
+    bind(RecentPicker, &[Char('k'), Up], Action::ListUp, "previous file"),
+    bind(RecentPicker, &[Enter], Action::Choose, "open the file"),
+    bind(RecentPicker, &[Char('d'), KeyCode::Delete], Action::RemoveEntry, "forget the file"),
+    bind(RecentPicker, &[Esc, Char('R')], Action::ClosePopup, "close"),
+    bind(BufferPicker, &[Char('j'), Down], Action::ListDown, "next buffer"),
+    bind(BufferPicker, &[Char('k'), Up], Action::ListUp, "previous buffer"),
+    bind(BufferPicker, &[Enter], Action::Choose, "switch to the buffer"),
+    bind(BufferPicker, &[Char('d'), KeyCode::Delete], Action::RemoveEntry, "close the buffer"),
+    bind(BufferPicker, &[Esc, Char('b')], Action::ClosePopup, "close"),
+    bind(SheetPicker, &[Char('j'), Down], Action::ListDown, "next sheet"),
+    bind(SheetPicker, &[Char('k'), Up], Action::ListUp, "previous sheet"),
+    bind(SheetPicker, &[Enter], Action::Choose, "show the sheet"),
+    bind(SheetPicker, &[Esc, Char('.')], Action::ClosePopup, "close"),
+    bind(JoinWizard, &[Char('j'), Down], Action::ListDown, "next choice"),
+    bind(JoinWizard, &[Char('k'), Up], Action::ListUp, "previous choice"),
+    bind(JoinWizard, &[Enter], Action::Choose, "next step (from the last, join)"),
+    bind(JoinWizard, &[Esc], Action::StepBack, "previous step (from the first, cancel)"),
+];
+
+/// Sections of the help overlay, in order
+pub const HELP_CONTEXTS: &[KeyContext] = &[
+    Global,
+    FileList,
+    DataView,
+    Marks,
+    Prefix('m'),
+    Visual,
+    Record,
+    Raw,
+    Chart,
+    Workbook,
+    Finder,
+    PivotEditor,
+    ChartPicker,
+    ColumnPicker,
+    RecentPicker,
+    BufferPicker,
+    SheetPicker,
+    JoinWizard,
+    Modal,
+    Help,
+];
+
+/// The action of the first binding for `key` in one of `contexts`
+pub fn lookup(contexts: &[KeyContext], key: KeyCode, ctrl: bool) -> Option<Action> {
+    KEYMAP
+        .iter()
+        .find(|b| b.ctrl == ctrl && b.keys.contains(&key) && contexts.contains(&b.context))
+        .map(|b| b.action)
+}
+
+/// Bindings of one context, in table order
+pub fn bindings(context: KeyContext) -> impl Iterator<Item = &'static Binding> {
+    KEYMAP.iter().filter(move |b| b.context == context)
+}
+
+/// Keys of a binding as shown in the help, like `j/↓` or `Ctrl-p`
+pub fn keys_label(binding: &Binding) -> String {
+    let keys: Vec<String> = binding
+        .keys
+        .iter()
+        .map(|key| {
+            let name = match key {
+                Char(' ') => "Space".to_string(),
+                Char(c) => c.to_string(),
+                Up => "↑".to_string(),
+                Down => "↓".to_string(),
+                Left => "←".to_string(),
+                Right => "→".to_string(),
+                BackTab => "Shift-Tab".to_string(),
+                other => format!("{:?}", other),
+            };
+            if binding.ctrl {
+                format!("Ctrl-{}", name)
+            } else {
+                name
+            }
+        })
+        .collect();
+    keys.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pivot::Aggregate;
+
+    #[test]
+    fn test_lookup_prefers_earlier_contexts() {
+        assert_eq!(lookup(&[Global], Char('j'), false), Some(Action::MoveDown));
+        assert_eq!(
+            lookup(&[Global, DataView, Record], Down, false),
+            Some(Action::NextField)
+        );
+        assert_eq!(
+            lookup(&[Global, FileList], Left, false),
+            Some(Action::ParentDir)
+        );
+        assert_eq!(lookup(&[Global], Char('m'), false), None);
+        assert_eq!(lookup(&[Global], Char('p'), true), Some(Action::OpenFinder));
+        assert_eq!(
+            lookup(&[Global, Finder], Char('p'), true),
+            Some(Action::FinderUp)
+        );
+        assert_eq!(
+            lookup(&[Prefix('m')], Char('e'), false),
+            Some(Action::ExportMarked)
+        );
+        assert_eq!(
+            lookup(&[Global, Workbook], Char('.'), false),
+            Some(Action::SheetPicker)
+        );
+        assert_eq!(lookup(&[Global], Char('.'), false), Some(Action::JsonPath));
+        assert_eq!(
+            lookup(&[ColumnPicker], Char('x'), false),
+            Some(Action::RemoveComputed)
+        );
+    }
+
+    #[test]
+    fn test_keymap_is_unambiguous_and_documented() {
+        for (i, binding) in KEYMAP.iter().enumerate() {
+            assert!(HELP_CONTEXTS.contains(&binding.context), "{:?}", binding);
+            for other in &KEYMAP[i + 1..] {
+                let clash = other.context == binding.context
+                    && other.ctrl == binding.ctrl
+                    && other.keys.iter().any(|k| binding.keys.contains(k));
+                assert!(!clash, "{:?} shadows {:?}", binding, other);
+            }
+        }
+        assert_eq!(keys_label(&KEYMAP[0]), "Enter/Esc/Space");
+        assert_eq!(keys_label(&KEYMAP[4]), "Ctrl-p");
+    }
+
+    #[test]
+    fn test_pivot_aggregate_keys_match_aggregates() {
+        let binding = KEYMAP
+            .iter()
+            .find(|b| b.action == Action::PivotAggregate)
+            .unwrap();
+        let keys: Vec<KeyCode> = Aggregate::ALL.iter().map(|a| Char(a.key())).collect();
+        assert_eq!(binding.keys, keys);
+    }
+}
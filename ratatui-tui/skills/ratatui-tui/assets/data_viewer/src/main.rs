@@ -7,29 +7,75 @@
 //! - Tabs widget for switching views
 //! - Two-panel split layout (file list + data view)
 
+mod browser;
+mod chart;
+mod columnar;
+mod compression;
+mod encoding;
+mod expr;
+mod finder;
+mod join;
+mod jsonpath;
+mod jsonstream;
+mod keymap;
+mod pager;
+mod pivot;
+mod print;
+mod schema;
+mod session;
+mod spreadsheet;
+mod sql;
+mod sqlite;
+mod view;
+
 use anyhow::{Context, Result};
+use base64::Engine;
+use browser::{EntryKind, FileEntry, ListOptions};
+use chart::{ChartKind, ChartSettings, XScale};
+use chrono::{DateTime, Datelike, Local};
+use columnar::RowGroups;
+use compression::{Compression, Counted, SizeInfo};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use encoding::EncodingInfo;
+use encoding_rs::{Encoding, UTF_8};
+use expr::ExprError;
+use finder::FileIndex;
+use join::JoinKind;
+use keymap::{Action, KeyContext};
+use pager::{FilePager, RawText};
+use pivot::{Aggregate, PivotSpec};
+use print::{Command, Format, PrintOptions};
 use ratatui::{
-    backend::CrosstermBackend,
+    backend::{CrosstermBackend, TestBackend},
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
+    symbols,
     text::{Line, Span},
     widgets::{
-        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table,
-        TableState, Tabs, Wrap,
+        Axis, BarChart, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, List, ListItem,
+        ListState, Paragraph, Row, Table, TableState, Tabs, Wrap,
     },
     Frame, Terminal,
 };
+use schema::{Schema, SchemaError};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use session::{FileState, Session};
+use spreadsheet::Sheet;
+use sqlite::{TableWindow, PAGE_ROWS};
 use std::{
-    fs,
-    io::{self, Stdout},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{self, BufRead, BufReader, Read, Stdout, Write},
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
+use view::{Computed, ViewSettings};
 
 /// Active panel in the two-panel layout
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,10 +85,12 @@ enum ActivePanel {
 }
 
 /// Active tab in the data view
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum ActiveTab {
     Table,
     Raw,
+    Chart,
 }
 
 impl ActiveTab {
@@ -50,22 +98,82 @@ impl ActiveTab {
         match self {
             ActiveTab::Table => 0,
             ActiveTab::Raw => 1,
+            ActiveTab::Chart => 2,
         }
     }
 
     fn next(&self) -> Self {
         match self {
             ActiveTab::Table => ActiveTab::Raw,
-            ActiveTab::Raw => ActiveTab::Table,
+            ActiveTab::Raw => ActiveTab::Chart,
+            ActiveTab::Chart => ActiveTab::Table,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(ActiveTab::Table),
+            1 => Some(ActiveTab::Raw),
+            2 => Some(ActiveTab::Chart),
+            _ => None,
+        }
+    }
+}
+
+/// How malformed CSV records are handled while loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CsvMode {
+    /// Bad records are reported and left out of the table
+    Strict,
+    /// Bad records are repaired (padded, truncated, lossy UTF-8) and kept
+    Lenient,
+}
+
+impl CsvMode {
+    fn label(&self) -> &'static str {
+        match self {
+            CsvMode::Strict => "strict",
+            CsvMode::Lenient => "lenient",
+        }
+    }
+
+    fn toggle(&self) -> Self {
+        match self {
+            CsvMode::Strict => CsvMode::Lenient,
+            CsvMode::Lenient => CsvMode::Strict,
         }
     }
 }
 
+/// A malformed record found while parsing a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordError {
+    /// 1-based line number where the record starts
+    line: u64,
+    /// Byte offset of the record in the file
+    byte: u64,
+    /// Short name of the csv error kind
+    kind: String,
+    /// Human readable detail, including what was done with the record
+    message: String,
+}
+
+/// Repair applied to a ragged row in lenient mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RowFix {
+    /// The row was short by this many fields; trailing cells are placeholders
+    Padded(usize),
+    /// The row had these extra fields, which were dropped
+    Truncated(Vec<String>),
+}
+
 /// Modal dialog state
 #[derive(Debug, Clone)]
 struct ModalDialog {
     title: String,
     message: String,
+    /// Whether the dialog reports an error, drawn in red, rather than a report
+    error: bool,
 }
 
 /// Loaded data representation
@@ -73,15 +181,295 @@ struct ModalDialog {
 struct LoadedData {
     file_name: String,
     file_type: String,
-    raw_content: String,
+    /// Compression the file was stored with
+    compression: Compression,
+    /// Size of the file contents after decompression
+    decoded_size: u64,
+    /// Text shown by the raw view
+    raw: RawText,
     headers: Vec<String>,
     rows: Vec<Vec<String>>,
+    /// Character encoding the file was decoded with
+    encoding: EncodingInfo,
+    /// Malformed records encountered while parsing
+    errors: Vec<RecordError>,
+    /// Repairs applied to ragged rows, keyed by row index
+    row_fixes: BTreeMap<usize, RowFix>,
+    /// JSON path the rows were selected with, if any
+    json_path: Option<String>,
+    /// Sheet of a workbook the rows come from
+    sheet: Option<String>,
+    /// Schema the rows were validated against, if any
+    schema: Option<PathBuf>,
+    /// Schema violations, keyed by row index
+    schema_errors: BTreeMap<usize, Vec<SchemaError>>,
+    /// Whether rows were deleted since the file was read
+    modified: bool,
+    /// Which rows of a database table are loaded, for tables opened from a
+    /// SQLite database
+    window: Option<TableWindow>,
+    /// Row groups of a Parquet or Arrow file, which are read as the
+    /// selection nears the last loaded row
+    groups: Option<RowGroups>,
+}
+
+impl LoadedData {
+    /// Approximate heap memory held by the parsed rows and raw text, in bytes
+    fn memory_usage(&self) -> usize {
+        let strings = |cells: &[String]| {
+            std::mem::size_of_val(cells) + cells.iter().map(String::capacity).sum::<usize>()
+        };
+        self.raw.memory_usage()
+            + strings(&self.headers)
+            + std::mem::size_of_val(&self.rows[..])
+            + self.rows.iter().map(|row| strings(row)).sum::<usize>()
+    }
+
+    /// Whether only some rows of a database table, or only some row groups
+    /// of a Parquet or Arrow file, are loaded
+    fn is_partial(&self) -> bool {
+        self.window
+            .as_ref()
+            .is_some_and(|window| self.rows.len() < window.total)
+            || self.groups.as_ref().is_some_and(RowGroups::remaining)
+    }
+
+    /// Load every row of a database table in place of its current page, and
+    /// every row group not read yet
+    fn read_all(&mut self) -> Result<()> {
+        if let Some(window) = &mut self.window {
+            if self.rows.len() < window.total {
+                self.rows = sqlite::read_page(&window.db, &window.name, 0, window.total)?.rows;
+                window.offset = 0;
+            }
+        }
+        if let Some(groups) = &mut self.groups {
+            while groups.remaining() {
+                self.rows.extend(groups.read_next()?);
+            }
+        }
+        Ok(())
+    }
+
+    /// This table as one side of a join on `key`, prefixing colliding column
+    /// names with the file name up to its first dot
+    fn join_table(&self, key: usize) -> join::Table<'_> {
+        join::Table {
+            name: self.file_name.split('.').next().unwrap_or_default(),
+            headers: &self.headers,
+            rows: &self.rows,
+            key,
+        }
+    }
+}
+
+/// Pivot setup being edited in the pivot popup
+#[derive(Debug, Clone)]
+struct PivotEditor {
+    spec: PivotSpec,
+    /// Selected column in the popup list
+    state: ListState,
+}
+
+/// Group-by table derived from the loaded data
+#[derive(Debug, Clone)]
+struct PivotView {
+    spec: PivotSpec,
+    /// Derived table, shown with the same renderer as the loaded data
+    data: LoadedData,
+    /// Loaded data row indices behind each pivot row
+    groups: Vec<Vec<usize>>,
+    /// State for the pivot table widget
+    state: TableState,
+    /// Group whose underlying rows are currently shown, if drilled down
+    drilled: Option<usize>,
+    /// Row selected in the loaded data before the pivot was opened
+    base_selected: Option<usize>,
+}
+
+/// SQL input pane state
+#[derive(Debug, Clone, Default)]
+struct QueryConsole {
+    /// Statement being edited
+    input: String,
+    /// Position in the query history while browsing it with Up/Down
+    history_index: Option<usize>,
+    /// Error from the last run, pointing into `input`
+    error: Option<ExprError>,
+}
+
+/// Result table of a SQL query
+#[derive(Debug, Clone)]
+struct QueryView {
+    /// Result rows, shown with the same renderer as the loaded data
+    data: LoadedData,
+    /// State for the result table widget
+    state: TableState,
+}
+
+/// Selected row of the shown table, laid out for the record view
+#[derive(Debug, Clone)]
+struct Record {
+    file_name: String,
+    /// Display position of the row and number of rows on display
+    position: usize,
+    count: usize,
+    /// Field names and values, in the table's column order
+    fields: Vec<(String, String)>,
+}
+
+/// JSON path input popup state
+#[derive(Debug, Clone)]
+struct JsonPathPrompt {
+    /// Path being edited
+    input: String,
+    /// Parsed document the path is applied to
+    document: Value,
+    /// Largest array of objects in the document, as (path, length)
+    suggestion: Option<(String, usize)>,
+    /// Error from the last attempt, pointing into `input`
+    error: Option<ExprError>,
+}
+
+/// Sheet picker popup state for a workbook
+#[derive(Debug, Clone)]
+struct SheetPicker {
+    names: Vec<String>,
+    /// Sheet on display
+    current: Option<String>,
+    state: ListState,
+}
+
+/// Subset of the loaded rows shown in place of the full table
+#[derive(Debug, Clone)]
+struct RowFilter {
+    /// Description shown in the table title, e.g. "marked rows"
+    label: String,
+    /// Loaded data row indices to show, in display order
+    rows: Vec<usize>,
+}
+
+/// Expression input popup state, for the row filter or a computed column
+#[derive(Debug, Clone, Default)]
+struct FilterPrompt {
+    /// Expression being edited
+    input: String,
+    /// Error from the last attempt, pointing into `input`
+    error: Option<ExprError>,
+}
+
+/// Goto popup state: a number for `:`, or a name with completion
+#[derive(Debug, Clone)]
+struct GotoPrompt {
+    /// What is jumped to, as shown in the title ("row", "column", ...)
+    target: &'static str,
+    /// Names to complete, or `None` when jumping by number
+    names: Option<Vec<String>>,
+    input: String,
+    /// Names matching the input, prefix matches first
+    matches: Vec<String>,
+    /// Match last filled in by Tab
+    completed: Option<usize>,
+    error: Option<String>,
+}
+
+impl GotoPrompt {
+    /// Recompute the matches after the input changed
+    fn update_matches(&mut self) {
+        let input = self.input.to_lowercase();
+        let names = self.names.as_deref().unwrap_or_default();
+        let (mut matches, rest): (Vec<String>, Vec<String>) = names
+            .iter()
+            .filter(|name| name.to_lowercase().contains(&input))
+            .cloned()
+            .partition(|name| name.to_lowercase().starts_with(&input));
+        matches.extend(rest);
+        self.matches = matches;
+        self.completed = None;
+        self.error = None;
+    }
+}
+
+/// An open file that is not currently shown, with everything about how it
+/// was being viewed
+struct Buffer {
+    file: PathBuf,
+    data: LoadedData,
+    table_state: TableState,
+    pivot: Option<PivotView>,
+    query: Option<QueryView>,
+    marks: BTreeSet<usize>,
+    row_filter: Option<RowFilter>,
+    view: ViewSettings,
+    column_offset: usize,
+    view_rows: Option<Vec<usize>>,
+    chart: ChartSettings,
+    active_tab: ActiveTab,
+}
+
+/// Fuzzy file finder popup state
+struct FileFinder {
+    /// Data files under the directory the finder was opened in
+    index: FileIndex,
+    /// Text typed so far
+    query: String,
+    /// Best matches as (index into `index.files`, match), best first
+    matches: Vec<(usize, finder::Match)>,
+    /// Selection in the match list
+    state: ListState,
+}
+
+/// Step of the join wizard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JoinStep {
+    /// Choosing the file to join with
+    File,
+    /// Choosing the key column of the loaded file
+    LeftKey,
+    /// Choosing the key column of the second file
+    RightKey,
+    /// Choosing inner, left or outer
+    Kind,
+}
+
+/// Join wizard popup state
+struct JoinWizard {
+    step: JoinStep,
+    /// Files the loaded one can be joined with: the other open buffers, then
+    /// the data files of the current directory
+    files: Vec<PathBuf>,
+    /// Second file and its data, once chosen
+    other: Option<(PathBuf, LoadedData)>,
+    /// Key column of the loaded file
+    left_key: usize,
+    /// Key column of the second file
+    right_key: usize,
+    /// Selection in the list of the current step
+    state: ListState,
+}
+
+/// Metadata column of the file panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileColumn {
+    Size,
+    /// Decompressed size of compressed files
+    Uncompressed,
+    Rows,
+    Modified,
 }
 
 /// Main application state
 struct App {
-    /// List of files in the current directory
-    file_list: Vec<PathBuf>,
+    /// Entries of the current directory, as shown in the file panel
+    file_list: Vec<FileEntry>,
+    /// Sorting and filtering of the file panel
+    list_options: ListOptions,
+    /// Glob filter input popup for the file panel (if open)
+    glob_prompt: Option<String>,
+    /// Databases expanded in the file panel to list their tables
+    expanded: BTreeSet<PathBuf>,
+    /// Compressed and uncompressed sizes of compressed files in the list
+    file_sizes: HashMap<PathBuf, SizeInfo>,
     /// State for the file list widget
     file_list_state: ListState,
     /// Currently selected file path
@@ -90,14 +478,101 @@ struct App {
     table_data: Option<LoadedData>,
     /// State for the table widget
     table_state: TableState,
+    /// Group-by view of the loaded data (if any)
+    pivot: Option<PivotView>,
+    /// Pivot setup popup (if open)
+    pivot_editor: Option<PivotEditor>,
+    /// Result of the last SQL query (if shown)
+    query: Option<QueryView>,
+    /// SQL input pane (if open)
+    query_console: Option<QueryConsole>,
+    /// Previously run SQL statements, oldest first
+    query_history: Vec<String>,
+    /// JSON path chosen for each JSON file, applied whenever it is loaded
+    json_paths: HashMap<PathBuf, String>,
+    /// JSON path input popup (if open)
+    json_path_prompt: Option<JsonPathPrompt>,
+    /// Sheet chosen for each workbook, shown whenever it is loaded
+    sheets: HashMap<PathBuf, String>,
+    /// Sheet picker popup (if open)
+    sheet_picker: Option<SheetPicker>,
+    /// Schema chosen for each JSON file, used instead of a sibling
+    /// `<name>.schema.json`
+    schemas: HashMap<PathBuf, PathBuf>,
+    /// Schema file input popup (if open)
+    schema_prompt: Option<String>,
+    /// Marked rows, as loaded data row indices
+    marks: BTreeSet<usize>,
+    /// Display position where a visual range selection started (if active)
+    visual_anchor: Option<usize>,
+    /// Whether marked rows may be deleted from the loaded data
+    editing: bool,
+    /// Row subset shown instead of the full table (if any)
+    row_filter: Option<RowFilter>,
+    /// Filter, sort order and column layout of the table
+    view: ViewSettings,
+    /// Unpinned columns scrolled out of view on the left
+    column_offset: usize,
+    /// Selected field when the table tab shows one row as a record
+    record_view: Option<TableState>,
+    /// Lines scrolled past at the top of the raw view
+    raw_scroll: usize,
+    /// Loaded data rows on display after the row subset, filter and sort;
+    /// `None` when every row is shown in file order
+    view_rows: Option<Vec<usize>>,
+    /// Row filter input popup (if open)
+    filter_prompt: Option<FilterPrompt>,
+    /// Column sort and visibility popup (if open)
+    column_picker: Option<ListState>,
+    /// Computed column input popup, opened from the column popup
+    computed_prompt: Option<FilterPrompt>,
+    /// Recently opened files popup (if open)
+    recent_picker: Option<ListState>,
+    /// Open files in tab bar order; `None` marks the active buffer, whose
+    /// state lives in the fields above
+    buffers: Vec<Option<Buffer>>,
+    /// Open buffers popup (if open)
+    buffer_picker: Option<ListState>,
+    /// Join wizard popup (if open)
+    join_wizard: Option<JoinWizard>,
+    /// State remembered between runs
+    session: Session,
+    /// Where the session is saved; `None` keeps it in memory only
+    session_path: Option<PathBuf>,
+    /// Prefix key waiting for the key after it, with the which-key popup
+    /// open
+    prefix: Option<char>,
+    /// Scroll offset of the key bindings overlay (if open)
+    help: Option<usize>,
+    /// Goto row or column popup (if open)
+    goto_prompt: Option<GotoPrompt>,
+    /// File list rows that fit on screen, as last drawn
+    file_page: usize,
+    /// Lines inside the data view content border, as last drawn
+    view_page: usize,
+    /// Fuzzy file finder popup (if open)
+    finder: Option<FileFinder>,
     /// Currently active tab
     active_tab: ActiveTab,
+    /// Columns and zoom of the chart tab
+    chart: ChartSettings,
+    /// Column picker popup for the chart tab (if open)
+    chart_picker: Option<ListState>,
     /// Currently active panel
     active_panel: ActivePanel,
     /// Current directory being viewed
     current_dir: PathBuf,
     /// Modal dialog (if any)
     modal: Option<ModalDialog>,
+    /// Status bar message and when it was shown, cleared after
+    /// [`STATUS_TIMEOUT`]
+    status_message: Option<(String, Instant)>,
+    /// How malformed CSV records are handled
+    csv_mode: CsvMode,
+    /// Encoding forced by the user; `None` means detect automatically
+    encoding_override: Option<&'static Encoding>,
+    /// Whether the parse error list is shown under the table
+    show_errors: bool,
     /// Whether the app should quit
     should_quit: bool,
 }
@@ -106,443 +581,5602 @@ impl App {
     /// Create a new App instance
     fn new() -> Result<Self> {
         let current_dir = std::env::current_dir().context("Failed to get current directory")?;
-        let mut app = App {
+        let mut app = App::with_dir(current_dir);
+        app.session_path = Session::default_path();
+        if let Some(path) = &app.session_path {
+            app.restore_session(Session::load(path));
+        }
+        app.refresh_file_list()?;
+        if !app.file_list.is_empty() {
+            app.file_list_state.select(Some(0));
+        }
+        Ok(app)
+    }
+
+    /// Create an App in `current_dir` without listing it or restoring the
+    /// session, as used by `--print`
+    fn with_dir(current_dir: PathBuf) -> Self {
+        App {
             file_list: Vec::new(),
+            list_options: ListOptions::default(),
+            glob_prompt: None,
+            expanded: BTreeSet::new(),
+            file_sizes: HashMap::new(),
             file_list_state: ListState::default(),
             selected_file: None,
             table_data: None,
             table_state: TableState::default(),
+            pivot: None,
+            pivot_editor: None,
+            query: None,
+            query_console: None,
+            query_history: Vec::new(),
+            json_paths: HashMap::new(),
+            json_path_prompt: None,
+            sheets: HashMap::new(),
+            sheet_picker: None,
+            schemas: HashMap::new(),
+            schema_prompt: None,
+            marks: BTreeSet::new(),
+            visual_anchor: None,
+            editing: false,
+            row_filter: None,
+            view: ViewSettings::default(),
+            column_offset: 0,
+            raw_scroll: 0,
+            record_view: None,
+            view_rows: None,
+            filter_prompt: None,
+            column_picker: None,
+            computed_prompt: None,
+            recent_picker: None,
+            buffers: Vec::new(),
+            buffer_picker: None,
+            join_wizard: None,
+            session: Session::default(),
+            session_path: None,
+            prefix: None,
+            help: None,
+            goto_prompt: None,
+            file_page: 10,
+            view_page: 10,
+            finder: None,
             active_tab: ActiveTab::Table,
+            chart: ChartSettings::default(),
+            chart_picker: None,
             active_panel: ActivePanel::FileList,
             current_dir,
             modal: None,
+            status_message: None,
+            csv_mode: CsvMode::Strict,
+            encoding_override: None,
+            show_errors: false,
             should_quit: false,
+        }
+    }
+
+    /// Pick up where the last run left off: its directory and JSON paths,
+    /// with the recent files offered straight away
+    fn restore_session(&mut self, session: Session) {
+        if let Some(dir) = session.last_dir.as_ref().filter(|dir| dir.is_dir()) {
+            self.current_dir = dir.clone();
+        }
+        for (file, state) in &session.files {
+            if let Some(json_path) = &state.json_path {
+                self.json_paths.insert(file.clone(), json_path.clone());
+            }
+            if let Some(sheet) = &state.sheet {
+                self.sheets.insert(file.clone(), sheet.clone());
+            }
+            if let Some(schema) = &state.schema {
+                self.schemas.insert(file.clone(), schema.clone());
+            }
+        }
+        if !session.recent_files.is_empty() {
+            self.recent_picker = Some(ListState::default().with_selected(Some(0)));
+        }
+        self.session = session;
+    }
+
+    /// Record how the loaded file is shown so it can be restored later
+    fn remember_file_state(&mut self) {
+        let (Some(file), Some(data)) = (&self.selected_file, &self.table_data) else {
+            return;
         };
-        app.refresh_file_list()?;
-        if !app.file_list.is_empty() {
-            app.file_list_state.select(Some(0));
+        let state = FileState {
+            selected: self.table_state.selected().unwrap_or(0),
+            tab: self.active_tab,
+            view: self.view.clone(),
+            json_path: data.json_path.clone(),
+            sheet: self.sheets.get(file).cloned(),
+            schema: self.schemas.get(file).cloned(),
+        };
+        self.session.files.insert(file.clone(), state);
+    }
+
+    /// Write the session file, if there is one
+    fn save_session(&mut self) -> Result<()> {
+        self.remember_file_state();
+        self.session.last_dir = Some(self.current_dir.clone());
+        match &self.session_path {
+            Some(path) => self.session.save(path),
+            None => Ok(()),
         }
-        Ok(app)
     }
 
     /// Refresh the file list from the current directory
+    ///
+    /// The selected entry stays selected when it is still listed.
     fn refresh_file_list(&mut self) -> Result<()> {
-        self.file_list.clear();
-
-        // Add parent directory entry if not at root
-        if let Some(parent) = self.current_dir.parent() {
-            self.file_list.push(parent.to_path_buf());
+        let selected = self.selected_entry().map(|e| e.path.clone());
+        self.file_list = browser::list_dir(&self.current_dir, &self.list_options)?;
+        let mut i = 0;
+        while i < self.file_list.len() {
+            let entry = &self.file_list[i];
+            i += 1;
+            if entry.kind == EntryKind::Database && self.expanded.contains(&entry.path) {
+                let children = browser::list_database(&entry.path).unwrap_or_default();
+                let count = children.len();
+                self.file_list.splice(i..i, children);
+                i += count;
+            }
         }
 
-        let entries = fs::read_dir(&self.current_dir)
-            .context("Failed to read directory")?;
-
-        let mut dirs = Vec::new();
-        let mut files = Vec::new();
-
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                dirs.push(path);
-            } else if let Some(ext) = path.extension() {
-                let ext_lower = ext.to_string_lossy().to_lowercase();
-                if ext_lower == "json" || ext_lower == "csv" {
-                    files.push(path);
+        for entry in &self.file_list {
+            let (_, compression) = compression::split_extension(&entry.path);
+            if entry.is_file()
+                && compression != Compression::None
+                && !self.file_sizes.contains_key(&entry.path)
+            {
+                if let Some(sizes) = compression::size_info(&entry.path) {
+                    self.file_sizes.insert(entry.path.clone(), sizes);
                 }
             }
         }
 
-        // Sort directories and files
-        dirs.sort();
-        files.sort();
+        if let Some(path) = selected {
+            self.select_path(&path);
+        }
+        Ok(())
+    }
+
+    /// Entry under the cursor in the file panel
+    fn selected_entry(&self) -> Option<&FileEntry> {
+        self.file_list_state
+            .selected()
+            .and_then(|i| self.file_list.get(i))
+    }
 
-        // Add directories first, then files
-        self.file_list.extend(dirs);
-        self.file_list.extend(files);
+    /// Select the entry for `path`, or the first entry if it isn't listed
+    fn select_path(&mut self, path: &Path) {
+        let index = self.file_list.iter().position(|e| e.path == path);
+        let fallback = (!self.file_list.is_empty()).then_some(0);
+        self.file_list_state.select(index.or(fallback));
+    }
 
-        Ok(())
+    /// Change to another directory, selecting `select` in it if given
+    fn change_dir(&mut self, dir: PathBuf, select: Option<PathBuf>) {
+        let previous = std::mem::replace(&mut self.current_dir, dir);
+        self.file_list_state.select(None);
+        if let Err(e) = self.refresh_file_list() {
+            self.current_dir = previous;
+            let _ = self.refresh_file_list();
+            self.show_error("Navigation Error", &e.to_string());
+            return;
+        }
+        match select {
+            Some(path) => self.select_path(&path),
+            None => self.file_list_state.select(Some(0)),
+        }
     }
 
     /// Navigate to the selected file or directory
     fn select_current(&mut self) {
-        if let Some(selected) = self.file_list_state.selected() {
-            if let Some(path) = self.file_list.get(selected).cloned() {
-                if path.is_dir() {
-                    self.current_dir = path;
-                    if let Err(e) = self.refresh_file_list() {
-                        self.show_error("Navigation Error", &e.to_string());
-                    } else {
-                        self.file_list_state.select(Some(0));
-                    }
-                } else {
-                    self.load_file(&path);
-                }
-            }
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+        match entry.kind {
+            EntryKind::Parent => self.go_to_parent(),
+            EntryKind::Dir => self.change_dir(entry.path, None),
+            EntryKind::File | EntryKind::Table | EntryKind::View => self.load_file(&entry.path),
+            EntryKind::Database => self.toggle_database(entry.path),
         }
     }
 
-    /// Load a file and parse its contents
-    fn load_file(&mut self, path: &Path) {
-        let ext = path
-            .extension()
-            .map(|e| e.to_string_lossy().to_lowercase())
-            .unwrap_or_default();
-
-        let result = match ext.as_str() {
-            "json" => self.load_json(path),
-            "csv" => self.load_csv(path),
-            _ => {
-                self.show_error("Unsupported File", "Only JSON and CSV files are supported");
-                return;
+    /// Show or hide the tables and views of a database in the file list
+    fn toggle_database(&mut self, db: PathBuf) {
+        if self.expanded.remove(&db) {
+            let _ = self.refresh_file_list();
+            return;
+        }
+        match browser::list_database(&db) {
+            Ok(children) if children.is_empty() => {
+                self.show_status("The database has no tables or views");
             }
-        };
+            Ok(_) => {
+                self.expanded.insert(db);
+                let _ = self.refresh_file_list();
+            }
+            Err(e) => self.show_error("Database Error", &format!("{:#}", e)),
+        }
+    }
 
-        match result {
-            Ok(data) => {
-                self.selected_file = Some(path.to_path_buf());
-                self.table_data = Some(data);
-                self.table_state.select(Some(0));
-                self.active_panel = ActivePanel::DataView;
+    /// Leave the selected table's database collapsed and selected, or go
+    /// to the parent directory
+    fn collapse_or_go_to_parent(&mut self) {
+        let db = self.selected_entry().and_then(|entry| match entry.kind {
+            EntryKind::Table | EntryKind::View => entry.path.parent().map(Path::to_path_buf),
+            _ => None,
+        });
+        match db {
+            Some(db) => {
+                self.expanded.remove(&db);
+                let _ = self.refresh_file_list();
+                self.select_path(&db);
             }
-            Err(e) => {
-                self.show_error("Load Error", &e.to_string());
+            None => self.go_to_parent(),
+        }
+    }
+
+    /// Change to the directory of a file (or of the database a table
+    /// belongs to, expanded) and select it
+    fn reveal(&mut self, file: &Path) {
+        let (dir, select) = match sqlite::split_object_path(file) {
+            Some((db, _)) => {
+                self.expanded.insert(db.to_path_buf());
+                (db.parent(), file)
             }
+            None => (file.parent(), file),
+        };
+        if let Some(dir) = dir {
+            self.change_dir(dir.to_path_buf(), Some(select.to_path_buf()));
         }
     }
 
-    /// Load and parse a JSON file
-    fn load_json(&self, path: &Path) -> Result<LoadedData> {
-        let content = fs::read_to_string(path).context("Failed to read file")?;
-        let value: Value = serde_json::from_str(&content).context("Failed to parse JSON")?;
+    /// Navigate to the parent directory, selecting the directory we left
+    fn go_to_parent(&mut self) {
+        if let Some(parent) = self.current_dir.parent() {
+            let child = self.current_dir.clone();
+            self.change_dir(parent.to_path_buf(), Some(child));
+        }
+    }
 
-        let file_name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
+    /// Apply a change to the file panel options and relist the directory
+    fn update_list_options(&mut self, change: impl FnOnce(&mut ListOptions)) {
+        change(&mut self.list_options);
+        if let Err(e) = self.refresh_file_list() {
+            self.show_error("Navigation Error", &e.to_string());
+        }
+    }
 
-        // Try to extract tabular data from JSON
-        let (headers, rows) = self.json_to_table(&value);
+    /// Open the fuzzy finder over the data files under the current directory
+    fn open_finder(&mut self) {
+        self.finder = Some(FileFinder {
+            index: FileIndex::spawn(&self.current_dir),
+            query: String::new(),
+            matches: Vec::new(),
+            state: ListState::default(),
+        });
+    }
 
-        Ok(LoadedData {
-            file_name,
-            file_type: "JSON".to_string(),
-            raw_content: serde_json::to_string_pretty(&value)
-                .unwrap_or_else(|_| content.clone()),
-            headers,
-            rows,
-        })
+    /// Re-rank the finder's matches after the query or the index changed
+    fn update_finder_matches(&mut self) {
+        /// Matches kept for display; more than fit on any screen
+        const LIMIT: usize = 200;
+        let Some(finder) = &mut self.finder else {
+            return;
+        };
+        finder.matches = finder::rank(&finder.query, &finder.index.files, LIMIT);
+        let selected = finder.state.selected().unwrap_or(0);
+        finder
+            .state
+            .select((!finder.matches.is_empty()).then(|| selected.min(finder.matches.len() - 1)));
     }
 
-    /// Convert JSON value to table format
-    fn json_to_table(&self, value: &Value) -> (Vec<String>, Vec<Vec<String>>) {
-        match value {
-            Value::Array(arr) => {
-                // Check if it's an array of objects
-                if arr.is_empty() {
-                    return (vec!["(empty)".to_string()], vec![]);
-                }
+    /// Move the finder selection by `delta` rows
+    fn move_finder_selection(&mut self, delta: isize) {
+        if let Some(finder) = &mut self.finder {
+            if let Some(selected) = finder.state.selected() {
+                let last = finder.matches.len().saturating_sub(1);
+                finder
+                    .state
+                    .select(Some(selected.saturating_add_signed(delta).min(last)));
+            }
+        }
+    }
 
-                if let Some(Value::Object(first)) = arr.first() {
-                    let headers: Vec<String> = first.keys().cloned().collect();
-                    let rows: Vec<Vec<String>> = arr
-                        .iter()
-                        .filter_map(|item| {
-                            if let Value::Object(obj) = item {
-                                Some(
-                                    headers
-                                        .iter()
-                                        .map(|h| self.value_to_string(obj.get(h)))
-                                        .collect(),
-                                )
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-                    return (headers, rows);
+    /// Handle a key while the fuzzy finder is open
+    fn handle_finder_key(&mut self, code: KeyCode) {
+        let Some(finder) = &mut self.finder else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.finder = None,
+            KeyCode::Enter => {
+                let selected = finder
+                    .state
+                    .selected()
+                    .and_then(|i| finder.matches.get(i))
+                    .map(|(index, _)| finder.index.root.join(&finder.index.files[*index]));
+                if let Some(path) = selected {
+                    self.finder = None;
+                    self.load_file(&path);
                 }
-
-                // Array of primitives
-                let headers = vec!["Index".to_string(), "Value".to_string()];
-                let rows: Vec<Vec<String>> = arr
-                    .iter()
-                    .enumerate()
-                    .map(|(i, v)| vec![i.to_string(), self.value_to_string(Some(v))])
-                    .collect();
-                (headers, rows)
             }
-            Value::Object(obj) => {
-                let headers = vec!["Key".to_string(), "Value".to_string()];
-                let rows: Vec<Vec<String>> = obj
-                    .iter()
-                    .map(|(k, v)| vec![k.clone(), self.value_to_string(Some(v))])
-                    .collect();
-                (headers, rows)
+            KeyCode::Up => self.move_finder_selection(-1),
+            KeyCode::Down => self.move_finder_selection(1),
+            KeyCode::Backspace => {
+                finder.query.pop();
+                finder.state.select(None);
+                self.update_finder_matches();
             }
-            _ => {
-                let headers = vec!["Value".to_string()];
-                let rows = vec![vec![self.value_to_string(Some(value))]];
-                (headers, rows)
+            KeyCode::Char(c) => {
+                finder.query.push(c);
+                finder.state.select(None);
+                self.update_finder_matches();
             }
+            _ => {}
         }
     }
 
-    /// Convert a JSON value to a display string
-    fn value_to_string(&self, value: Option<&Value>) -> String {
-        match value {
-            None => String::new(),
-            Some(Value::Null) => "null".to_string(),
-            Some(Value::Bool(b)) => b.to_string(),
-            Some(Value::Number(n)) => n.to_string(),
-            Some(Value::String(s)) => s.clone(),
-            Some(Value::Array(arr)) => format!("[{} items]", arr.len()),
-            Some(Value::Object(obj)) => format!("{{{} keys}}", obj.len()),
+    /// Handle a key pressed with Ctrl held
+    fn handle_ctrl_key(&mut self, code: KeyCode) {
+        if self.modal.is_some() || self.help.is_some() {
+            return;
+        }
+        // The finder keeps the keys while it is open
+        let contexts = match self.finder {
+            Some(_) => vec![KeyContext::Finder],
+            None => self.key_contexts(),
+        };
+        if let Some(action) = keymap::lookup(&contexts, code, true) {
+            self.run_action(action, code);
         }
     }
 
-    /// Load and parse a CSV file
-    fn load_csv(&self, path: &Path) -> Result<LoadedData> {
-        let content = fs::read_to_string(path).context("Failed to read file")?;
-        let mut reader = csv::Reader::from_reader(content.as_bytes());
-
-        let headers: Vec<String> = reader
-            .headers()
-            .context("Failed to read CSV headers")?
-            .iter()
-            .map(|s| s.to_string())
-            .collect();
-
-        let rows: Vec<Vec<String>> = reader
-            .records()
-            .filter_map(|r| r.ok())
-            .map(|r| r.iter().map(|s| s.to_string()).collect())
-            .collect();
-
-        let file_name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "unknown".to_string());
-
-        Ok(LoadedData {
-            file_name,
-            file_type: "CSV".to_string(),
-            raw_content: content,
-            headers,
-            rows,
-        })
+    /// Pick up work finished in the background since the last frame
+    fn tick(&mut self) {
+        let indexed = self
+            .finder
+            .as_mut()
+            .is_some_and(|finder| finder.index.poll());
+        if indexed {
+            self.update_finder_matches();
+        }
+        if self
+            .status_message
+            .as_ref()
+            .is_some_and(|(_, shown)| shown.elapsed() >= STATUS_TIMEOUT)
+        {
+            self.status_message = None;
+        }
+        self.read_ahead();
     }
 
-    /// Show an error modal
-    fn show_error(&mut self, title: &str, message: &str) {
-        self.modal = Some(ModalDialog {
-            title: title.to_string(),
-            message: message.to_string(),
-        });
+    /// Handle a key while the glob filter popup is open
+    fn handle_glob_prompt_key(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.glob_prompt else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.glob_prompt = None,
+            KeyCode::Enter => {
+                let glob = input.trim().to_string();
+                self.glob_prompt = None;
+                self.update_list_options(|options| {
+                    options.glob = (!glob.is_empty()).then_some(glob);
+                });
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
     }
 
-    /// Dismiss the modal dialog
-    fn dismiss_modal(&mut self) {
-        self.modal = None;
-    }
+    /// Load a file and parse its contents, restoring how it was last shown
+    ///
+    /// Other open files stay loaded in their buffers; opening one of them
+    /// again switches to it, while the active file is read afresh.
+    fn load_file(&mut self, path: &Path) {
+        let open = self
+            .buffers
+            .iter()
+            .position(|b| b.as_ref().is_some_and(|b| b.file == path));
+        if let Some(index) = open {
+            self.switch_buffer(index);
+            self.active_panel = ActivePanel::DataView;
+            self.session.record_open(path);
+            return;
+        }
 
-    /// Move selection up in the current panel
-    fn move_up(&mut self) {
-        match self.active_panel {
-            ActivePanel::FileList => {
-                if let Some(selected) = self.file_list_state.selected() {
-                    if selected > 0 {
-                        self.file_list_state.select(Some(selected - 1));
-                    }
+        match self.load_data(path) {
+            Ok(data) => {
+                self.remember_file_state();
+                if self.selected_file.as_deref() != Some(path) {
+                    self.park_active();
+                    self.buffers.push(None);
+                }
+                if let Some(sizes) = self.file_sizes.get_mut(path) {
+                    sizes.uncompressed = Some(data.decoded_size);
+                }
+                let state = self.session.files.get(path).cloned().unwrap_or_default();
+                self.selected_file = Some(path.to_path_buf());
+                self.table_data = Some(data);
+                let validated = self.validate_rows();
+                self.pivot = None;
+                self.query = None;
+                self.marks.clear();
+                self.visual_anchor = None;
+                self.row_filter = None;
+                self.view = state.view;
+                self.column_offset = 0;
+                self.raw_scroll = 0;
+                self.view_rows = None;
+                self.chart = ChartSettings::default();
+                self.table_state.select(Some(0));
+                self.refresh_view_rows();
+                let last = self.view_len().saturating_sub(1);
+                self.table_state.select(Some(state.selected.min(last)));
+                self.select_tab(state.tab);
+                self.active_panel = ActivePanel::DataView;
+                // A workbook opened for the first time asks which sheet to show
+                if spreadsheet::is_spreadsheet(path)
+                    && !self.sheets.contains_key(path)
+                    && spreadsheet::sheet_names(path).is_ok_and(|names| names.len() > 1)
+                {
+                    self.open_sheet_picker();
+                }
+
+                self.session.record_open(path);
+                if let Err(e) = validated {
+                    self.show_error("Schema Error", &format!("{:#}", e));
+                }
+                if let Err(e) = self.save_session() {
+                    self.show_error("Session Error", &format!("{:#}", e));
                 }
             }
-            ActivePanel::DataView => {
-                if let Some(selected) = self.table_state.selected() {
-                    if selected > 0 {
-                        self.table_state.select(Some(selected - 1));
-                    }
+            Err(e) => {
+                // Don't let a stale JSON path or sheet keep the file from opening
+                if let Some(state) = self.session.files.get_mut(path) {
+                    state.json_path = None;
+                    state.sheet = None;
                 }
+                let message = match (self.json_paths.remove(path), self.sheets.remove(path)) {
+                    (Some(json_path), _) => {
+                        format!("{:#}\n\nJSON path {} was cleared", e, json_path)
+                    }
+                    (None, Some(sheet)) => format!("{:#}\n\nSheet {} was cleared", e, sheet),
+                    (None, None) => e.to_string(),
+                };
+                self.show_error("Load Error", &message);
             }
         }
     }
 
-    /// Move selection down in the current panel
-    fn move_down(&mut self) {
-        match self.active_panel {
-            ActivePanel::FileList => {
-                if let Some(selected) = self.file_list_state.selected() {
-                    if selected < self.file_list.len().saturating_sub(1) {
-                        self.file_list_state.select(Some(selected + 1));
-                    }
-                }
+    /// Move the active file's state out of the view into its buffer slot
+    fn park_active(&mut self) {
+        let Some(slot) = self.buffers.iter().position(Option::is_none) else {
+            return;
+        };
+        let (Some(file), Some(data)) = (self.selected_file.take(), self.table_data.take()) else {
+            return;
+        };
+        self.buffers[slot] = Some(Buffer {
+            file,
+            data,
+            table_state: std::mem::take(&mut self.table_state),
+            pivot: self.pivot.take(),
+            query: self.query.take(),
+            marks: std::mem::take(&mut self.marks),
+            row_filter: self.row_filter.take(),
+            view: std::mem::take(&mut self.view),
+            column_offset: std::mem::take(&mut self.column_offset),
+            view_rows: self.view_rows.take(),
+            chart: std::mem::take(&mut self.chart),
+            active_tab: self.active_tab,
+        });
+        self.visual_anchor = None;
+    }
+
+    /// Show the buffer at `index`, parking the active one
+    fn switch_buffer(&mut self, index: usize) {
+        if !self.buffers.get(index).is_some_and(Option::is_some) {
+            return;
+        }
+        self.remember_file_state();
+        self.park_active();
+        if let Some(buffer) = self.buffers[index].take() {
+            self.activate(buffer);
+        }
+    }
+
+    /// Put a parked buffer's state into the view
+    fn activate(&mut self, buffer: Buffer) {
+        self.selected_file = Some(buffer.file);
+        self.table_data = Some(buffer.data);
+        self.table_state = buffer.table_state;
+        self.pivot = buffer.pivot;
+        self.query = buffer.query;
+        self.marks = buffer.marks;
+        self.row_filter = buffer.row_filter;
+        self.view = buffer.view;
+        self.column_offset = buffer.column_offset;
+        self.view_rows = buffer.view_rows;
+        self.chart = buffer.chart;
+        self.active_tab = buffer.active_tab;
+        self.visual_anchor = None;
+        self.record_view = None;
+        self.raw_scroll = 0;
+        self.pivot_editor = None;
+        self.chart_picker = None;
+        self.column_picker = None;
+        self.computed_prompt = None;
+        self.filter_prompt = None;
+        self.json_path_prompt = None;
+        self.sheet_picker = None;
+        self.schema_prompt = None;
+        self.join_wizard = None;
+        self.goto_prompt = None;
+        self.prefix = None;
+    }
+
+    /// Switch to the next (or previous) buffer in the tab bar
+    fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffers.len();
+        if let Some(active) = self.buffers.iter().position(Option::is_none) {
+            if count > 1 {
+                let next = if forward {
+                    (active + 1) % count
+                } else {
+                    (active + count - 1) % count
+                };
+                self.switch_buffer(next);
             }
-            ActivePanel::DataView => {
-                if let Some(data) = &self.table_data {
-                    if let Some(selected) = self.table_state.selected() {
-                        if selected < data.rows.len().saturating_sub(1) {
-                            self.table_state.select(Some(selected + 1));
+        }
+    }
+
+    /// Close the buffer at `index`; closing the active one shows a neighbour
+    fn close_buffer(&mut self, index: usize) {
+        if index >= self.buffers.len() {
+            return;
+        }
+        if self.buffers[index].is_some() {
+            self.buffers.remove(index);
+            return;
+        }
+
+        self.remember_file_state();
+        self.buffers.remove(index);
+        self.selected_file = None;
+        self.table_data = None;
+        self.table_state = TableState::default();
+        self.pivot = None;
+        self.query = None;
+        self.marks.clear();
+        self.row_filter = None;
+        self.view = ViewSettings::default();
+        self.view_rows = None;
+        self.chart = ChartSettings::default();
+        self.active_tab = ActiveTab::Table;
+        self.visual_anchor = None;
+        if !self.buffers.is_empty() {
+            let next = index.min(self.buffers.len() - 1);
+            if let Some(buffer) = self.buffers[next].take() {
+                self.activate(buffer);
+            }
+        }
+    }
+
+    /// File and data of every open buffer, in tab bar order
+    fn buffer_list(&self) -> Vec<(&Path, &LoadedData)> {
+        self.buffers
+            .iter()
+            .filter_map(|slot| match slot {
+                Some(buffer) => Some((buffer.file.as_path(), &buffer.data)),
+                None => self.selected_file.as_deref().zip(self.table_data.as_ref()),
+            })
+            .collect()
+    }
+
+    /// Open the buffer list popup
+    fn open_buffer_picker(&mut self) {
+        let active = self.buffers.iter().position(Option::is_none);
+        if active.is_some() {
+            self.buffer_picker = Some(ListState::default().with_selected(active));
+        }
+    }
+
+    /// Run a keymap action while the buffer list popup is open
+    fn buffer_picker_action(&mut self, action: Action) {
+        let Some(state) = &mut self.buffer_picker else {
+            return;
+        };
+        let count = self.buffers.len();
+        let selected = state.selected().unwrap_or(0);
+
+        match action {
+            Action::ClosePopup => self.buffer_picker = None,
+            Action::ListDown if selected + 1 < count => {
+                state.select(Some(selected + 1));
+            }
+            Action::ListUp => {
+                state.select(Some(selected.saturating_sub(1)));
+            }
+            Action::RemoveEntry => {
+                self.close_buffer(selected);
+                match self.buffers.len() {
+                    0 => self.buffer_picker = None,
+                    n => {
+                        if let Some(state) = &mut self.buffer_picker {
+                            state.select(Some(selected.min(n - 1)));
                         }
                     }
                 }
             }
+            Action::Choose => {
+                self.buffer_picker = None;
+                self.switch_buffer(selected);
+                self.active_panel = ActivePanel::DataView;
+            }
+            _ => {}
         }
     }
 
-    /// Switch between panels
-    fn switch_panel(&mut self) {
-        self.active_panel = match self.active_panel {
-            ActivePanel::FileList => ActivePanel::DataView,
-            ActivePanel::DataView => ActivePanel::FileList,
+    /// Show the loaded data with different view settings
+    ///
+    /// The selected row stays selected if it is still shown. Nothing changes
+    /// when the filter or sort column doesn't fit the data.
+    fn set_view(&mut self, view: ViewSettings) -> Result<(), ExprError> {
+        let Some(data) = &self.table_data else {
+            return Ok(());
+        };
+        let subset = self.row_filter.as_ref().map(|f| &f.rows[..]);
+        let rows = if subset.is_some() || view.changes_rows() {
+            Some(view.rows(&data.headers, &data.rows, subset)?)
+        } else {
+            None
+        };
+
+        let selected = self.table_state.selected().and_then(|i| self.base_row(i));
+        let position = match (&rows, selected) {
+            (Some(rows), Some(row)) => rows.iter().position(|&r| r == row),
+            (None, selected) => selected,
+            (Some(_), None) => None,
         };
+        self.view = view;
+        self.view_rows = rows;
+        self.table_state.select(position.or(Some(0)));
+        Ok(())
     }
 
-    /// Switch to the next tab
-    fn next_tab(&mut self) {
-        self.active_tab = self.active_tab.next();
+    /// Recompute the rows on display after the data or row subset changed,
+    /// dropping a filter or sort order that no longer applies
+    fn refresh_view_rows(&mut self) {
+        let view = self.view.clone();
+        if let Err(e) = self.set_view(view) {
+            let mut view = self.view.clone();
+            let dropped: Vec<String> = view.describe();
+            view.filter = None;
+            view.sort = None;
+            let _ = self.set_view(view);
+            self.show_error(
+                "View Error",
+                &format!("{}\n\nRemoved {}", e, dropped.join(", ")),
+            );
+        }
     }
 
-    /// Handle keyboard input
-    fn handle_key(&mut self, key: KeyCode) {
-        // Handle modal first
-        if self.modal.is_some() {
-            match key {
-                KeyCode::Enter | KeyCode::Esc | KeyCode::Char(' ') => {
-                    self.dismiss_modal();
+    /// Open the row filter popup, starting from the current filter
+    fn open_filter_prompt(&mut self) {
+        if self.table_data.is_none() || self.pivot.is_some() || self.query.is_some() {
+            return;
+        }
+        self.filter_prompt = Some(FilterPrompt {
+            input: self.view.filter.clone().unwrap_or_default(),
+            error: None,
+        });
+    }
+
+    /// Handle a key while the row filter popup is open
+    fn handle_filter_prompt_key(&mut self, code: KeyCode) {
+        let Some(prompt) = &mut self.filter_prompt else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.filter_prompt = None,
+            KeyCode::Enter => {
+                let input = prompt.input.trim();
+                let view = ViewSettings {
+                    filter: (!input.is_empty()).then(|| input.to_string()),
+                    ..self.view.clone()
+                };
+                match self.set_view(view) {
+                    Ok(()) => self.filter_prompt = None,
+                    Err(e) => {
+                        if let Some(prompt) = &mut self.filter_prompt {
+                            prompt.error = Some(e);
+                        }
+                    }
                 }
-                _ => {}
             }
-            return;
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                prompt.error = None;
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                prompt.error = None;
+            }
+            _ => {}
         }
+    }
 
-        match key {
-            KeyCode::Char('q') | KeyCode::Esc => {
-                self.should_quit = true;
+    /// Open the column sort and visibility popup
+    fn open_column_picker(&mut self) {
+        if self.table_data.is_some() && self.pivot.is_none() && self.query.is_none() {
+            self.column_picker = Some(ListState::default().with_selected(Some(0)));
+        }
+    }
+
+    /// Run a keymap action while the column popup is open
+    ///
+    /// The popup lists every column in display order; the selection follows
+    /// a column as it is pinned or moved.
+    fn column_picker_action(&mut self, action: Action) {
+        let (Some(data), Some(state)) = (&self.table_data, &mut self.column_picker) else {
+            return;
+        };
+        let headers = &data.headers;
+        let all_headers = self.view.all_headers(headers);
+        let order = self.view.ordered_columns(headers);
+        let selected = state.selected().unwrap_or(0);
+        let Some(column) = order.get(selected).map(|&i| all_headers[i].clone()) else {
+            self.column_picker = None;
+            return;
+        };
+
+        match action {
+            Action::ClosePopup => {
+                self.column_picker = None;
+                return;
             }
-            KeyCode::Tab => {
-                self.switch_panel();
+            Action::ListDown if selected + 1 < order.len() => {
+                state.select(Some(selected + 1));
+                return;
             }
-            KeyCode::Char('1') | KeyCode::Char('2') => {
-                self.next_tab();
+            Action::ListUp => {
+                state.select(Some(selected.saturating_sub(1)));
+                return;
             }
-            KeyCode::Char('j') | KeyCode::Down => {
-                self.move_down();
+            Action::SortColumn => {
+                let mut view = self.view.clone();
+                view.cycle_sort(&column);
+                if let Err(e) = self.set_view(view) {
+                    self.show_error("View Error", &e.to_string());
+                }
+                return;
             }
-            KeyCode::Char('k') | KeyCode::Up => {
-                self.move_up();
+            Action::HideColumn => self.view.toggle_hidden(&column),
+            Action::PinColumn => self.view.toggle_pinned(&column),
+            Action::MoveColumnDown => self.view.move_column(headers, &column, 1),
+            Action::MoveColumnUp => self.view.move_column(headers, &column, -1),
+            Action::ResetColumns => {
+                self.view.hidden_columns.clear();
+                self.view.column_order.clear();
+                self.view.pinned_columns.clear();
+                self.column_offset = 0;
+            }
+            Action::AddComputed => {
+                self.computed_prompt = Some(FilterPrompt::default());
+                return;
             }
-            KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right => {
-                if self.active_panel == ActivePanel::FileList {
-                    self.select_current();
+            Action::RemoveComputed
+                if self.view.computed_columns.iter().any(|c| c.name == column) =>
+            {
+                let mut view = self.view.clone();
+                if let Err(message) = view.remove_computed(headers, &column) {
+                    self.show_error("Computed Column", &message);
+                    return;
+                }
+                if let Err(e) = self.set_view(view) {
+                    self.show_error("View Error", &e.to_string());
                 }
+                if let Some(state) = &mut self.column_picker {
+                    state.select(Some(selected.min(order.len().saturating_sub(2))));
+                }
+                return;
             }
-            KeyCode::Char('h') | KeyCode::Left => {
-                if self.active_panel == ActivePanel::FileList {
-                    // Navigate to parent directory
-                    if let Some(parent) = self.current_dir.parent() {
-                        self.current_dir = parent.to_path_buf();
-                        let _ = self.refresh_file_list();
-                        self.file_list_state.select(Some(0));
+            _ => return,
+        }
+        let all_headers = self.view.all_headers(headers);
+        let position = self
+            .view
+            .ordered_columns(headers)
+            .iter()
+            .position(|&i| all_headers[i] == column);
+        state.select(position);
+    }
+
+    /// Handle a key while the computed column popup is open
+    fn handle_computed_prompt_key(&mut self, code: KeyCode) {
+        let (Some(data), Some(prompt)) = (&self.table_data, &mut self.computed_prompt) else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.computed_prompt = None,
+            KeyCode::Enter => {
+                let mut view = self.view.clone();
+                if let Err(e) = view.add_computed(&data.headers, &prompt.input) {
+                    prompt.error = Some(e);
+                    return;
+                }
+                let count = view.ordered_columns(&data.headers).len();
+                match self.set_view(view) {
+                    Ok(()) => {
+                        self.computed_prompt = None;
+                        if let Some(state) = &mut self.column_picker {
+                            state.select(Some(count - 1));
+                        }
+                    }
+                    Err(e) => {
+                        if let Some(prompt) = &mut self.computed_prompt {
+                            prompt.error = Some(e);
+                        }
                     }
                 }
             }
-            KeyCode::Char('r') => {
-                let _ = self.refresh_file_list();
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                prompt.error = None;
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                prompt.error = None;
             }
             _ => {}
         }
     }
-}
 
-/// Create a centered rect for modal dialogs
-fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
-    let popup_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(area);
+    /// Whether the table tab shows the selected row as a record
+    fn record_active(&self) -> bool {
+        self.record_view.is_some()
+            && self.active_panel == ActivePanel::DataView
+            && self.active_tab == ActiveTab::Table
+    }
 
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(popup_layout[1])[1]
-}
+    /// Whether the raw tab takes the navigation keys
+    fn raw_active(&self) -> bool {
+        self.table_data.is_some()
+            && self.active_panel == ActivePanel::DataView
+            && self.active_tab == ActiveTab::Raw
+    }
 
-/// Render the UI
-fn ui(frame: &mut Frame, app: &mut App) {
-    // Create the main layout: two panels side by side
-    let main_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-        .split(frame.area());
+    /// Switch the table tab between the table and the selected row as a
+    /// record
+    fn toggle_record_view(&mut self) {
+        self.record_view = match self.record_view {
+            Some(_) => None,
+            None if self.table_data.is_some() => {
+                let state = self.view_state_mut();
+                if state.selected().is_none() {
+                    state.select(Some(0));
+                }
+                Some(TableState::default().with_selected(Some(0)))
+            }
+            None => None,
+        };
+    }
 
-    // Render file list panel (left)
-    render_file_list(frame, app, main_chunks[0]);
+    /// Move between the fields of the record view
+    fn move_field(&mut self, delta: isize) {
+        let count = self.selected_record().map_or(0, |r| r.fields.len());
+        if let Some(state) = &mut self.record_view {
+            let field = state.selected().unwrap_or(0).saturating_add_signed(delta);
+            state.select(Some(field.min(count.saturating_sub(1))));
+        }
+    }
 
-    // Render data view panel (right)
-    render_data_view(frame, app, main_chunks[1]);
+    /// The selected row of the shown table with its fields; the loaded data
+    /// shows the columns of the view, derived tables all of theirs
+    fn selected_record(&self) -> Option<Record> {
+        let count = self.view_len();
+        let derived = match (&self.query, &self.pivot) {
+            (Some(query), _) => Some((&query.data, &query.state)),
+            (None, Some(pivot)) if pivot.drilled.is_none() => Some((&pivot.data, &pivot.state)),
+            _ => None,
+        };
+        if let Some((data, state)) = derived {
+            let position = state.selected()?;
+            let row = data.rows.get(position)?;
+            let fields = data
+                .headers
+                .iter()
+                .enumerate()
+                .map(|(col, header)| (header.clone(), row.get(col).cloned().unwrap_or_default()))
+                .collect();
+            return Some(Record {
+                file_name: data.file_name.clone(),
+                position,
+                count,
+                fields,
+            });
+        }
 
-    // Render modal dialog if present
-    if let Some(modal) = &app.modal {
-        render_modal(frame, modal);
+        let data = self.table_data.as_ref()?;
+        let position = self.table_state.selected()?;
+        let row = &data.rows[self.base_row(position)?];
+        let computed = self.view.computed(&data.headers);
+        let fields = self
+            .view
+            .visible_columns(&data.headers)
+            .into_iter()
+            .map(|col| {
+                let header = computed.header(&data.headers, col).to_string();
+                (header, computed.cell(row, col))
+            })
+            .collect();
+        Some(Record {
+            file_name: data.file_name.clone(),
+            position,
+            count,
+            fields,
+        })
     }
-}
 
-/// Render the file list panel
-fn render_file_list(frame: &mut Frame, app: &mut App, area: Rect) {
-    let is_active = app.active_panel == ActivePanel::FileList;
-    let border_style = if is_active {
-        Style::default().fg(Color::Cyan)
-    } else {
-        Style::default().fg(Color::DarkGray)
-    };
+    /// Scroll the unpinned columns of the table sideways
+    fn scroll_columns(&mut self, delta: isize) {
+        let Some(data) = &self.table_data else {
+            return;
+        };
+        // Pivot groups and query results show all their columns unscrolled
+        if self.query.is_some() || self.pivot.as_ref().is_some_and(|p| p.drilled.is_none()) {
+            return;
+        }
+        let visible = self.view.visible_columns(&data.headers).len();
+        let scrollable = visible - self.view.pinned_count(&data.headers);
+        self.column_offset = self
+            .column_offset
+            .saturating_add_signed(delta)
+            .min(scrollable.saturating_sub(1));
+    }
 
-    let title = format!(" Files: {} ", app.current_dir.display());
-    let block = Block::default()
-        .title(title)
-        .borders(Borders::ALL)
-        .border_style(border_style);
+    /// Open the recently opened files popup
+    fn open_recent_picker(&mut self) {
+        if self.session.recent_files.is_empty() {
+            self.show_status("No files have been opened yet");
+        } else {
+            self.recent_picker = Some(ListState::default().with_selected(Some(0)));
+        }
+    }
 
-    let items: Vec<ListItem> = app
-        .file_list
-        .iter()
-        .map(|path| {
-            let name = if path == &app.current_dir.parent().unwrap_or(path) {
-                "..".to_string()
-            } else {
-                path.file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_else(|| path.display().to_string())
-            };
+    /// Run a keymap action while the recent files popup is open
+    fn recent_picker_action(&mut self, action: Action) {
+        let Some(state) = &mut self.recent_picker else {
+            return;
+        };
+        let count = self.session.recent_files.len();
+        let selected = state.selected().unwrap_or(0);
 
-            let (icon, style) = if path.is_dir() {
-                ("[D]", Style::default().fg(Color::Blue).bold())
-            } else {
-                let ext = path
-                    .extension()
-                    .map(|e| e.to_string_lossy().to_lowercase())
-                    .unwrap_or_default();
-                match ext.as_str() {
-                    "json" => ("[J]", Style::default().fg(Color::Yellow)),
-                    "csv" => ("[C]", Style::default().fg(Color::Green)),
-                    _ => ("[F]", Style::default()),
+        match action {
+            Action::ClosePopup => self.recent_picker = None,
+            Action::ListDown if selected + 1 < count => {
+                state.select(Some(selected + 1));
+            }
+            Action::ListUp => {
+                state.select(Some(selected.saturating_sub(1)));
+            }
+            Action::RemoveEntry if selected < count => {
+                self.session.recent_files.remove(selected);
+                if self.session.recent_files.is_empty() {
+                    self.recent_picker = None;
+                } else {
+                    state.select(Some(selected.min(count - 2)));
                 }
-            };
+            }
+            Action::Choose => {
+                let Some(file) = self.session.recent_files.get(selected).cloned() else {
+                    return;
+                };
+                self.recent_picker = None;
+                if !file_exists(&file) {
+                    self.session.recent_files.remove(selected);
+                    self.show_error(
+                        "Recent Files",
+                        &format!("{} no longer exists", file.display()),
+                    );
+                    return;
+                }
+                self.reveal(&file);
+                self.load_file(&file);
+            }
+            _ => {}
+        }
+    }
 
-            ListItem::new(Line::from(vec![
-                Span::styled(format!("{} ", icon), style),
-                Span::raw(name),
-            ]))
-        })
-        .collect();
+    /// Parse a supported file without changing what is displayed
+    fn load_data(&self, path: &Path) -> Result<LoadedData> {
+        if let Some((db, name)) = sqlite::split_object_path(path) {
+            return load_table(db, name);
+        }
+        if columnar::is_columnar(path) {
+            return self.load_columnar(path);
+        }
+        if spreadsheet::is_spreadsheet(path) {
+            let sheet = spreadsheet::read_sheet(path, self.sheets.get(path).map(String::as_str))?;
+            return Ok(load_sheet(path, sheet));
+        }
+        let (ext, _) = compression::split_extension(path);
+        match ext.as_str() {
+            "json" | "ndjson" | "jsonl" => self.load_json(path),
+            "csv" => self.load_csv(path),
+            _ if sqlite::is_database(path) => {
+                anyhow::bail!("Expand the database in the file list and open one of its tables")
+            }
+            _ => {
+                anyhow::bail!("Only JSON, CSV, spreadsheet, Parquet and Arrow files are supported")
+            }
+        }
+    }
 
-    let list = List::new(items)
-        .block(block)
+    /// Load and parse a JSON file, or an NDJSON file as an array of its lines
+    ///
+    /// A top-level array is streamed into rows one element at a time and the
+    /// raw view pages it from the file, so the whole document is never held
+    /// in memory. Other documents, and files that need transcoding, are read
+    /// whole.
+    fn load_json(&self, path: &Path) -> Result<LoadedData> {
+        let (ext, compression) = compression::split_extension(path);
+        let json_path = self.json_paths.get(path).cloned();
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        if ext == "json" && json_path.is_none() {
+            let mut table = ArrayRows::default();
+            let streamed = stream_json_array(path, self.encoding_override, |item| {
+                table.push(&item, |value| self.value_to_string(value));
+            });
+            // Invalid documents are read again whole to report the error
+            if let Ok(Some((decoded_size, encoding))) = streamed {
+                let (headers, rows) = table.finish();
+                return Ok(LoadedData {
+                    file_name,
+                    file_type: "JSON".to_string(),
+                    compression,
+                    decoded_size,
+                    raw: RawText::File(FilePager::new(path)),
+                    headers,
+                    rows,
+                    encoding,
+                    errors: Vec::new(),
+                    row_fixes: BTreeMap::new(),
+                    json_path,
+                    sheet: None,
+                    schema: None,
+                    schema_errors: BTreeMap::new(),
+                    modified: false,
+                    window: None,
+                    groups: None,
+                });
+            }
+        }
+
+        let bytes = compression::read(path)?;
+        let (content, encoding) = encoding::to_utf8(&bytes, self.encoding_override);
+        let (value, errors) = parse_json(&content, &ext, &encoding)?;
+
+        // Try to extract tabular data from JSON, starting from the chosen path
+        let (headers, rows) = match &json_path {
+            Some(json_path) => {
+                let selected = jsonpath::parse(json_path)
+                    .and_then(|p| p.select(&value))
+                    .with_context(|| format!("Failed to apply JSON path {}", json_path))?;
+                self.json_to_table(&selected)
+            }
+            None => self.json_to_table(&value),
+        };
+
+        Ok(LoadedData {
+            file_name,
+            file_type: "JSON".to_string(),
+            compression,
+            decoded_size: bytes.len() as u64,
+            raw: raw_text(path, &content, &encoding),
+            headers,
+            rows,
+            encoding,
+            errors,
+            row_fixes: BTreeMap::new(),
+            json_path,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: None,
+        })
+    }
+
+    /// Load a Parquet or Arrow file with its schema as the text of the raw
+    /// tab: its first row group, or every row when a JSON path picks them
+    fn load_columnar(&self, path: &Path) -> Result<LoadedData> {
+        let columnar::Opened {
+            headers,
+            schema,
+            mut groups,
+        } = columnar::open(path)?;
+        let json_path = self.json_paths.get(path).cloned();
+        let (headers, rows) = match &json_path {
+            Some(json_path) => {
+                let document = columnar::document(path)?;
+                let selected = jsonpath::parse(json_path)
+                    .and_then(|p| p.select(&document))
+                    .with_context(|| format!("Failed to apply JSON path {}", json_path))?;
+                groups.loaded = groups.sizes.len();
+                self.json_to_table(&selected)
+            }
+            None if groups.remaining() => (headers, groups.read_next()?),
+            None => (headers, Vec::new()),
+        };
+
+        Ok(LoadedData {
+            file_name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            file_type: groups.format.label().to_string(),
+            compression: Compression::None,
+            decoded_size: std::fs::metadata(path).map_or(0, |m| m.len()),
+            raw: RawText::Text(schema),
+            headers,
+            rows,
+            encoding: EncodingInfo::utf8(),
+            errors: Vec::new(),
+            row_fixes: BTreeMap::new(),
+            json_path,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: Some(groups),
+        })
+    }
+
+    /// Convert JSON value to table format
+    fn json_to_table(&self, value: &Value) -> (Vec<String>, Vec<Vec<String>>) {
+        match value {
+            Value::Array(arr) => {
+                let mut table = ArrayRows::default();
+                for item in arr {
+                    table.push(item, |value| self.value_to_string(value));
+                }
+                table.finish()
+            }
+            Value::Object(obj) => {
+                let headers = vec!["Key".to_string(), "Value".to_string()];
+                let rows: Vec<Vec<String>> = obj
+                    .iter()
+                    .map(|(k, v)| vec![k.clone(), self.value_to_string(Some(v))])
+                    .collect();
+                (headers, rows)
+            }
+            _ => {
+                let headers = vec!["Value".to_string()];
+                let rows = vec![vec![self.value_to_string(Some(value))]];
+                (headers, rows)
+            }
+        }
+    }
+
+    /// Convert a JSON value to a display string
+    fn value_to_string(&self, value: Option<&Value>) -> String {
+        match value {
+            None => String::new(),
+            Some(Value::Null) => "null".to_string(),
+            Some(Value::Bool(b)) => b.to_string(),
+            Some(Value::Number(n)) => n.to_string(),
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(arr)) => format!("[{} items]", arr.len()),
+            Some(Value::Object(obj)) => format!("{{{} keys}}", obj.len()),
+        }
+    }
+
+    /// Check the loaded JSON rows against the file's schema: the one chosen
+    /// with `v`, or else a sibling `<name>.schema.json`
+    fn validate_rows(&mut self) -> Result<()> {
+        let (Some(file), Some(data)) = (&self.selected_file, &mut self.table_data) else {
+            return Ok(());
+        };
+        data.schema = None;
+        data.schema_errors.clear();
+        if data.file_type != "JSON" {
+            return Ok(());
+        }
+        let Some(schema_path) = self
+            .schemas
+            .get(file)
+            .cloned()
+            .or_else(|| schema::sibling(file))
+        else {
+            return Ok(());
+        };
+        data.schema_errors = validate(data, file, &schema_path)
+            .with_context(|| format!("Failed to validate against {}", schema_path.display()))?;
+        data.schema = Some(schema_path);
+        Ok(())
+    }
+
+    /// Load and parse a CSV file
+    ///
+    /// UTF-8 files are parsed as they are decompressed, and the raw view
+    /// pages them from the file. Files that need transcoding are read whole.
+    fn load_csv(&self, path: &Path) -> Result<LoadedData> {
+        let mut reader = BufReader::with_capacity(1 << 16, compression::open(path)?);
+        let sample = reader.fill_buf().context("Failed to read file")?;
+        let streamed = encoding::utf8_stream(sample, self.encoding_override).and_then(|encoding| {
+            let mut counted = Counted {
+                inner: reader,
+                bytes: 0,
+            };
+            let parsed = parse_csv(&mut counted, self.csv_mode);
+            // Invalid UTF-8 past the sample means the encoding was guessed
+            // wrong, so the file is detected and decoded again whole
+            let misread = !encoding.forced
+                && parsed
+                    .as_ref()
+                    .is_ok_and(|(_, _, errors, _)| errors.iter().any(|e| e.kind == "Utf8"));
+            let raw = RawText::File(FilePager::new(path));
+            (!misread).then_some((parsed, counted.bytes, encoding, raw))
+        });
+        let (parsed, decoded_size, encoding, raw) = match streamed {
+            Some(streamed) => streamed,
+            None => {
+                let bytes = compression::read(path)?;
+                let (content, encoding) = encoding::to_utf8(&bytes, self.encoding_override);
+                let parsed = parse_csv(&content[..], self.csv_mode);
+                let raw = raw_text(path, &content, &encoding);
+                (parsed, bytes.len() as u64, encoding, raw)
+            }
+        };
+        let (headers, rows, errors, row_fixes) = parsed
+            .with_context(|| format!("Failed to parse CSV (decoded as {})", encoding.label()))?;
+
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        Ok(LoadedData {
+            file_name,
+            file_type: "CSV".to_string(),
+            compression: compression::split_extension(path).1,
+            decoded_size,
+            raw,
+            headers,
+            rows,
+            encoding,
+            errors,
+            row_fixes,
+            json_path: None,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: None,
+        })
+    }
+
+    /// Reload the open file, keeping the selected row where possible
+    fn reload_current(&mut self) {
+        if let (Some(_), Some(path)) = (&self.table_data, self.selected_file.clone()) {
+            let selected = self.table_state.selected();
+            self.load_file(&path);
+            if let Some(data) = &self.table_data {
+                let last = data.rows.len().saturating_sub(1);
+                self.table_state.select(selected.map(|i| i.min(last)));
+            }
+        }
+    }
+
+    /// Switch between strict and lenient CSV parsing and reload the open file
+    fn toggle_csv_mode(&mut self) {
+        self.csv_mode = self.csv_mode.toggle();
+        let is_csv = self
+            .table_data
+            .as_ref()
+            .is_some_and(|data| data.file_type == "CSV");
+        if is_csv {
+            self.reload_current();
+        }
+    }
+
+    /// Cycle the forced encoding (ending back at auto-detect) and reload the open file
+    fn cycle_encoding(&mut self) {
+        self.encoding_override = encoding::next_override(self.encoding_override);
+        self.reload_current();
+    }
+
+    /// Show an error modal
+    fn show_error(&mut self, title: &str, message: &str) {
+        self.modal = Some(ModalDialog {
+            title: title.to_string(),
+            message: message.to_string(),
+            error: true,
+        });
+    }
+
+    /// Show a message in the status bar that goes away by itself
+    fn show_status(&mut self, message: &str) {
+        self.status_message = Some((message.to_string(), Instant::now()));
+    }
+
+    /// Dismiss the modal dialog
+    fn dismiss_modal(&mut self) {
+        self.modal = None;
+    }
+
+    /// Move selection up in the current panel
+    fn move_up(&mut self) {
+        match self.active_panel {
+            ActivePanel::FileList => {
+                if let Some(selected) = self.file_list_state.selected() {
+                    if selected > 0 {
+                        self.file_list_state.select(Some(selected - 1));
+                    }
+                }
+            }
+            ActivePanel::DataView => {
+                let state = self.view_state_mut();
+                if let Some(selected) = state.selected() {
+                    if selected > 0 {
+                        state.select(Some(selected - 1));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Move selection down in the current panel
+    fn move_down(&mut self) {
+        match self.active_panel {
+            ActivePanel::FileList => {
+                if let Some(selected) = self.file_list_state.selected() {
+                    if selected < self.file_list.len().saturating_sub(1) {
+                        self.file_list_state.select(Some(selected + 1));
+                    }
+                }
+            }
+            ActivePanel::DataView => {
+                let len = self.view_len();
+                let state = self.view_state_mut();
+                if let Some(selected) = state.selected() {
+                    if selected < len.saturating_sub(1) {
+                        state.select(Some(selected + 1));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rows (or raw lines) of the current panel that fit on screen
+    fn page_rows(&self) -> usize {
+        let rows = match self.active_panel {
+            ActivePanel::FileList => self.file_page,
+            ActivePanel::DataView if self.raw_active() => self.view_page,
+            // Less the table header and the line under it
+            ActivePanel::DataView => self.view_page.saturating_sub(2),
+        };
+        rows.max(1)
+    }
+
+    /// Move the selection of the current panel by `delta`, stopping at
+    /// either end
+    fn move_by(&mut self, delta: isize) {
+        self.move_to(|position| position.saturating_add_signed(delta));
+    }
+
+    /// Move the selection of the current panel to `target(selected)`,
+    /// stopping at the end: the field in the record view, the first line in
+    /// the raw view, otherwise the selected file or row
+    fn move_to(&mut self, target: impl FnOnce(usize) -> usize) {
+        if self.raw_active() {
+            // Clamped to the end of the text once drawn
+            self.raw_scroll = target(self.raw_scroll);
+            return;
+        }
+        if self.record_active() {
+            let count = self
+                .selected_record()
+                .map_or(0, |record| record.fields.len());
+            if let Some(state) = &mut self.record_view {
+                let selected = target(state.selected().unwrap_or(0));
+                state.select(Some(selected.min(count.saturating_sub(1))));
+            }
+            return;
+        }
+        match self.active_panel {
+            ActivePanel::FileList => {
+                let last = self.file_list.len().saturating_sub(1);
+                if let Some(selected) = self.file_list_state.selected() {
+                    self.file_list_state
+                        .select(Some(target(selected).min(last)));
+                }
+            }
+            ActivePanel::DataView => {
+                let last = self.view_len().saturating_sub(1);
+                let state = self.view_state_mut();
+                if let Some(selected) = state.selected() {
+                    state.select(Some(target(selected).min(last)));
+                }
+            }
+        }
+    }
+
+    /// Load the next (or previous) page of rows of a database table
+    fn turn_db_page(&mut self, forward: bool) {
+        if self.pivot.is_some() || self.query.is_some() {
+            return;
+        }
+        let Some(window) = self.table_data.as_ref().and_then(|d| d.window.as_ref()) else {
+            self.show_status("Only database tables are read a page at a time");
+            return;
+        };
+        let offset = if forward {
+            window.offset + PAGE_ROWS
+        } else {
+            window.offset.saturating_sub(PAGE_ROWS)
+        };
+        if offset >= window.total || offset == window.offset {
+            self.show_status(if forward {
+                "Already on the last page"
+            } else {
+                "Already on the first page"
+            });
+            return;
+        }
+        self.load_db_page(offset);
+        self.table_state.select(Some(0));
+    }
+
+    /// Replace the loaded rows of a database table with those from
+    /// `offset` on; marks and row subsets refer to the old rows and go
+    fn load_db_page(&mut self, offset: usize) {
+        let Some(window) = self.table_data.as_ref().and_then(|d| d.window.clone()) else {
+            return;
+        };
+        let page = match sqlite::read_page(&window.db, &window.name, offset, PAGE_ROWS) {
+            Ok(page) => page,
+            Err(e) => {
+                self.show_error("Load Error", &format!("{:#}", e));
+                return;
+            }
+        };
+        if let Some(data) = &mut self.table_data {
+            data.rows = page.rows;
+            data.modified = false;
+            data.window = Some(TableWindow { offset, ..window });
+        }
+        self.marks.clear();
+        self.visual_anchor = None;
+        self.row_filter = None;
+        self.refresh_view_rows();
+    }
+
+    /// Position on the loaded page of row `index` of a database table,
+    /// loading its page first; `None` unless the table shows its rows in
+    /// table order
+    fn goto_db_row(&mut self, index: usize) -> Option<usize> {
+        if self.pivot.is_some() || self.query.is_some() || self.view_rows.is_some() {
+            return None;
+        }
+        let window = self.table_data.as_ref()?.window.as_ref()?;
+        let index = index.min(window.total.saturating_sub(1));
+        let offset = index - index % PAGE_ROWS;
+        if offset != window.offset {
+            self.load_db_page(offset);
+        }
+        Some(index - offset)
+    }
+
+    /// Load the whole of a paged table before it is pivoted, queried or
+    /// joined, keeping marks and the selection on their rows; `false` once
+    /// the error is shown when it can't be read
+    fn read_all_rows(&mut self) -> bool {
+        let Some(data) = &mut self.table_data else {
+            return true;
+        };
+        if !data.is_partial() {
+            return true;
+        }
+        let offset = data.window.as_ref().map_or(0, |window| window.offset);
+        if let Err(e) = data.read_all() {
+            self.show_error("Load Error", &format!("{:#}", e));
+            return false;
+        }
+        let selected = self
+            .table_state
+            .selected()
+            .and_then(|i| match &self.view_rows {
+                Some(rows) => rows.get(i).copied(),
+                None => Some(i),
+            });
+        self.marks = self.marks.iter().map(|&row| row + offset).collect();
+        if let Some(filter) = &mut self.row_filter {
+            filter.rows.iter_mut().for_each(|row| *row += offset);
+        }
+        self.refresh_view_rows();
+        let position = selected
+            .map(|row| row + offset)
+            .and_then(|row| match &self.view_rows {
+                Some(rows) => rows.iter().position(|&r| r == row),
+                None => Some(row),
+            });
+        self.table_state.select(position.or(Some(0)));
+        true
+    }
+
+    /// Read the next row group of a Parquet or Arrow file once the
+    /// selection comes near the last loaded row
+    fn read_ahead(&mut self) {
+        if self.pivot.is_some() || self.query.is_some() {
+            return;
+        }
+        let remaining = self
+            .table_data
+            .as_ref()
+            .and_then(|d| d.groups.as_ref())
+            .is_some_and(RowGroups::remaining);
+        let selected = self.table_state.selected().unwrap_or(0);
+        if remaining && selected + READ_AHEAD_ROWS >= self.view_len() {
+            self.read_next_group();
+        }
+    }
+
+    /// Read row groups of a Parquet or Arrow file until row `index` is loaded
+    fn read_groups_through(&mut self, index: usize) {
+        while self.table_data.as_ref().is_some_and(|d| {
+            d.rows.len() <= index && d.groups.as_ref().is_some_and(RowGroups::remaining)
+        }) {
+            self.read_next_group();
+        }
+    }
+
+    /// Append the next row group of a Parquet or Arrow file to the loaded
+    /// rows, keeping the selection
+    fn read_next_group(&mut self) {
+        let Some(data) = &mut self.table_data else {
+            return;
+        };
+        let Some(groups) = &mut data.groups else {
+            return;
+        };
+        match groups.read_next() {
+            Ok(rows) => {
+                data.rows.extend(rows);
+                self.refresh_view_rows();
+            }
+            Err(e) => {
+                // Stop reading instead of failing again on every tick
+                groups.loaded = groups.sizes.len();
+                self.show_error("Load Error", &format!("{:#}", e));
+            }
+        }
+    }
+
+    /// Open the goto popup, for a column (or file) name or for a number
+    fn open_goto_prompt(&mut self, by_name: bool) {
+        let target = match self.active_panel {
+            ActivePanel::FileList => "file",
+            ActivePanel::DataView if self.table_data.is_none() => return,
+            ActivePanel::DataView if by_name && self.record_active() => "field",
+            ActivePanel::DataView if by_name => "column",
+            ActivePanel::DataView if self.raw_active() => "line",
+            ActivePanel::DataView => "row",
+        };
+        let mut prompt = GotoPrompt {
+            target,
+            names: by_name.then(|| self.goto_names()),
+            input: String::new(),
+            matches: Vec::new(),
+            completed: None,
+            error: None,
+        };
+        prompt.update_matches();
+        self.goto_prompt = Some(prompt);
+    }
+
+    /// Names the goto popup completes: the files of the file list, or the
+    /// columns of the table in display order
+    fn goto_names(&self) -> Vec<String> {
+        if self.active_panel == ActivePanel::FileList {
+            return self.file_list.iter().map(|e| e.name.clone()).collect();
+        }
+        let Some((data, _)) = self.displayed_table() else {
+            return Vec::new();
+        };
+        if self.query.is_some() || self.pivot.as_ref().is_some_and(|p| p.drilled.is_none()) {
+            return data.headers.clone();
+        }
+        let computed = self.view.computed(&data.headers);
+        self.view
+            .visible_columns(&data.headers)
+            .into_iter()
+            .map(|col| computed.header(&data.headers, col).to_string())
+            .collect()
+    }
+
+    /// Handle keys while the goto popup is open
+    fn handle_goto_prompt_key(&mut self, key: KeyCode) {
+        let Some(prompt) = &mut self.goto_prompt else {
+            return;
+        };
+        match key {
+            KeyCode::Esc => self.goto_prompt = None,
+            KeyCode::Tab if !prompt.matches.is_empty() => {
+                let next = prompt
+                    .completed
+                    .map_or(0, |i| (i + 1) % prompt.matches.len());
+                prompt.input = prompt.matches[next].clone();
+                prompt.completed = Some(next);
+            }
+            KeyCode::Enter => {
+                let input = prompt.input.trim().to_string();
+                let Some(names) = &prompt.names else {
+                    match input.parse::<usize>() {
+                        Ok(number) if number > 0 => {
+                            self.goto_prompt = None;
+                            self.goto_number(number);
+                        }
+                        _ => prompt.error = Some(format!("Type a {} number", prompt.target)),
+                    }
+                    return;
+                };
+                let exact = names.iter().position(|n| n.eq_ignore_ascii_case(&input));
+                let first = prompt
+                    .matches
+                    .first()
+                    .and_then(|m| names.iter().position(|n| n == m));
+                match exact.or(first) {
+                    Some(index) => {
+                        self.goto_prompt = None;
+                        self.goto_index(index);
+                    }
+                    None => prompt.error = Some(format!("No {} matches", prompt.target)),
+                }
+            }
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                prompt.update_matches();
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                prompt.update_matches();
+            }
+            _ => {}
+        }
+    }
+
+    /// Select the file, row or raw line numbered from 1, stopping at the end
+    fn goto_number(&mut self, number: usize) {
+        let index = number - 1;
+        if self.raw_active() {
+            self.raw_scroll = index;
+            return;
+        }
+        match self.active_panel {
+            ActivePanel::FileList => {
+                let last = self.file_list.len().saturating_sub(1);
+                self.file_list_state.select(Some(index.min(last)));
+            }
+            ActivePanel::DataView => {
+                self.read_groups_through(index);
+                let index = self.goto_db_row(index).unwrap_or(index);
+                let last = self.view_len().saturating_sub(1);
+                self.view_state_mut().select(Some(index.min(last)));
+            }
+        }
+    }
+
+    /// Select the file or record field at `index` of the goto names, or
+    /// scroll the table so that column comes first after the pinned ones
+    fn goto_index(&mut self, index: usize) {
+        if self.active_panel == ActivePanel::FileList {
+            self.file_list_state.select(Some(index));
+        } else if let Some(state) = &mut self.record_view {
+            state.select(Some(index));
+        } else if let Some(data) = &self.table_data {
+            // Pivot and query results show every column unscrolled
+            if self.query.is_none() && self.pivot.as_ref().is_none_or(|p| p.drilled.is_some()) {
+                let pinned = self.view.pinned_count(&data.headers);
+                self.column_offset = index.saturating_sub(pinned);
+            }
+        }
+    }
+
+    /// Number of rows in the table currently shown in the data view
+    fn view_len(&self) -> usize {
+        if let Some(query) = &self.query {
+            return query.data.rows.len();
+        }
+        match (&self.pivot, &self.table_data) {
+            (Some(pivot), _) => match pivot.drilled {
+                Some(group) => pivot.groups[group].len(),
+                None => pivot.data.rows.len(),
+            },
+            (None, Some(data)) => match &self.view_rows {
+                Some(rows) => rows.len(),
+                None => data.rows.len(),
+            },
+            (None, None) => 0,
+        }
+    }
+
+    /// Table state of the table currently shown in the data view
+    fn view_state_mut(&mut self) -> &mut TableState {
+        if let Some(query) = &mut self.query {
+            return &mut query.state;
+        }
+        match &mut self.pivot {
+            Some(pivot) if pivot.drilled.is_none() => &mut pivot.state,
+            _ => &mut self.table_state,
+        }
+    }
+
+    /// Open the pivot setup popup, starting from the current pivot if any
+    fn open_pivot_editor(&mut self) {
+        if self.table_data.is_none() {
+            return;
+        }
+        let spec = self
+            .pivot
+            .as_ref()
+            .map(|p| p.spec.clone())
+            .unwrap_or_default();
+        let mut state = ListState::default();
+        state.select(Some(0));
+        self.pivot_editor = Some(PivotEditor { spec, state });
+    }
+
+    /// Run a keymap action while the pivot setup popup is open
+    fn pivot_editor_action(&mut self, action: Action, key: KeyCode) {
+        let Some(editor) = &mut self.pivot_editor else {
+            return;
+        };
+        let col_count = self.table_data.as_ref().map_or(0, |d| d.headers.len());
+        let selected = editor.state.selected().unwrap_or(0);
+
+        match action {
+            Action::ClosePopup => {
+                self.pivot_editor = None;
+            }
+            Action::Choose => {
+                if let Some(editor) = self.pivot_editor.take() {
+                    self.apply_pivot(editor.spec);
+                }
+            }
+            Action::ListDown if selected + 1 < col_count => {
+                editor.state.select(Some(selected + 1));
+            }
+            Action::ListUp => {
+                editor.state.select(Some(selected.saturating_sub(1)));
+            }
+            Action::PivotGroupBy => {
+                let group_by = &mut editor.spec.group_by;
+                match group_by.iter().position(|&c| c == selected) {
+                    Some(i) => {
+                        group_by.remove(i);
+                    }
+                    None => group_by.push(selected),
+                }
+            }
+            Action::PivotAggregate => {
+                let aggregate = Aggregate::ALL
+                    .iter()
+                    .find(|a| KeyCode::Char(a.key()) == key);
+                if let Some(agg) = aggregate {
+                    let aggregates = &mut editor.spec.aggregates;
+                    match aggregates.iter().position(|&a| a == (*agg, selected)) {
+                        Some(i) => {
+                            aggregates.remove(i);
+                        }
+                        None => {
+                            aggregates.push((*agg, selected));
+                            aggregates.sort();
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Build the pivot table for `spec` and show it in the data view
+    fn apply_pivot(&mut self, spec: PivotSpec) {
+        if !self.read_all_rows() {
+            return;
+        }
+        let Some(data) = &self.table_data else {
+            return;
+        };
+        if spec.group_by.is_empty() {
+            self.show_error("Pivot", "Select at least one group-by column");
+            return;
+        }
+
+        let table = pivot::pivot(&data.headers, &data.rows, &spec);
+        let group_names: Vec<&str> = spec
+            .group_by
+            .iter()
+            .map(|&c| data.headers[c].as_str())
+            .collect();
+        let pivot_data = LoadedData {
+            file_name: format!("{} by {}", data.file_name, group_names.join(", ")),
+            file_type: "Pivot".to_string(),
+            compression: Compression::None,
+            decoded_size: 0,
+            raw: RawText::Text(String::new()),
+            headers: table.headers,
+            rows: table.rows,
+            encoding: data.encoding,
+            errors: Vec::new(),
+            row_fixes: BTreeMap::new(),
+            json_path: None,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: None,
+        };
+
+        let base_selected = match &self.pivot {
+            Some(pivot) => pivot.base_selected,
+            None => self.table_state.selected(),
+        };
+        let mut state = TableState::default();
+        state.select(Some(0));
+        self.chart = ChartSettings::default();
+        self.query = None;
+        self.pivot = Some(PivotView {
+            spec,
+            data: pivot_data,
+            groups: table.groups,
+            state,
+            drilled: None,
+            base_selected,
+        });
+        self.active_panel = ActivePanel::DataView;
+    }
+
+    /// Show the loaded data rows behind the selected pivot row
+    fn drill_down(&mut self) {
+        if self.query.is_some() {
+            return;
+        }
+        if let Some(pivot) = &mut self.pivot {
+            if pivot.drilled.is_none() {
+                if let Some(group) = pivot.state.selected() {
+                    if group < pivot.groups.len() {
+                        pivot.drilled = Some(group);
+                        self.table_state.select(Some(0));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Step back out of a drilled group, or close the pivot view
+    fn pivot_back(&mut self) {
+        if let Some(pivot) = &mut self.pivot {
+            if pivot.drilled.take().is_none() {
+                self.table_state.select(pivot.base_selected);
+                self.pivot = None;
+            }
+            self.chart = ChartSettings::default();
+        }
+    }
+
+    /// Handle keys while the SQL input pane is open
+    fn handle_query_console_key(&mut self, key: KeyCode) {
+        let Some(console) = &mut self.query_console else {
+            return;
+        };
+
+        match key {
+            KeyCode::Esc => {
+                self.query_console = None;
+            }
+            KeyCode::Enter => {
+                let sql = console.input.trim().to_string();
+                if sql.is_empty() {
+                    return;
+                }
+                if self.query_history.last() != Some(&sql) {
+                    self.query_history.push(sql.clone());
+                }
+                let result = self.run_query(&sql);
+                if let Some(console) = &mut self.query_console {
+                    console.history_index = None;
+                    console.error = result.err();
+                }
+            }
+            KeyCode::Up if !self.query_history.is_empty() => {
+                let index = match console.history_index {
+                    Some(i) => i.saturating_sub(1),
+                    None => self.query_history.len() - 1,
+                };
+                console.history_index = Some(index);
+                console.input = self.query_history[index].clone();
+                console.error = None;
+            }
+            KeyCode::Down => {
+                if let Some(i) = console.history_index {
+                    if i + 1 < self.query_history.len() {
+                        console.history_index = Some(i + 1);
+                        console.input = self.query_history[i + 1].clone();
+                    } else {
+                        console.history_index = None;
+                        console.input.clear();
+                    }
+                    console.error = None;
+                }
+            }
+            KeyCode::Backspace => {
+                console.input.pop();
+                console.error = None;
+            }
+            KeyCode::Char(c) => {
+                console.input.push(c);
+                console.error = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Run a SELECT statement and show its result table
+    ///
+    /// `FROM current` reads the loaded file; any other name is looked up
+    /// among the open buffers, as they are shown, and then among the data
+    /// files in the current directory, by file name or by the part before
+    /// the first dot (`events` for `events.csv.gz`). Database tables, Parquet
+    /// and Arrow files are read whole first.
+    fn run_query(&mut self, sql: &str) -> Result<(), ExprError> {
+        let query = sql::parse(sql)?;
+        let current = query.from.eq_ignore_ascii_case("current");
+        if current && !self.read_all_rows() {
+            return Err(ExprError::new(
+                query.from_pos,
+                "Failed to read the whole table",
+            ));
+        }
+        let named =
+            |name: &str| name == query.from || name.split('.').next() == Some(query.from.as_str());
+        let read_whole = |data: Result<LoadedData>| {
+            data.and_then(|mut data| data.read_all().map(|()| data))
+                .map_err(|e| ExprError::new(query.from_pos, e.to_string()))
+        };
+        let buffer = self.buffer_list().into_iter().find(|(file, _)| {
+            file.file_name()
+                .is_some_and(|name| named(&name.to_string_lossy()))
+        });
+        let other;
+        let source = if current {
+            self.table_data
+                .as_ref()
+                .ok_or_else(|| ExprError::new(query.from_pos, "No file is loaded"))?
+        } else if let Some((_, data)) = buffer {
+            if data.is_partial() {
+                other = read_whole(Ok(data.clone()))?;
+                &other
+            } else {
+                data
+            }
+        } else {
+            let entry = self
+                .file_list
+                .iter()
+                .filter(|e| e.is_file())
+                .find(|e| named(&e.name))
+                .ok_or_else(|| {
+                    ExprError::new(query.from_pos, format!("No file named '{}'", query.from))
+                })?;
+            other = read_whole(self.load_data(&entry.path))?;
+            &other
+        };
+
+        let result = query.execute(&source.headers, &source.rows)?;
+        let data = LoadedData {
+            file_name: sql.to_string(),
+            file_type: "Query".to_string(),
+            compression: Compression::None,
+            decoded_size: 0,
+            raw: RawText::Text(String::new()),
+            headers: result.headers,
+            rows: result.rows,
+            encoding: source.encoding,
+            errors: Vec::new(),
+            row_fixes: BTreeMap::new(),
+            json_path: None,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: None,
+        };
+        let mut state = TableState::default();
+        state.select(Some(0));
+        self.query = Some(QueryView { data, state });
+        self.chart = ChartSettings::default();
+        self.active_panel = ActivePanel::DataView;
+        Ok(())
+    }
+
+    /// Open the join wizard for the loaded file
+    fn open_join_wizard(&mut self) {
+        let Some(current) = self.selected_file.clone() else {
+            return;
+        };
+        let mut files: Vec<PathBuf> = self
+            .buffer_list()
+            .into_iter()
+            .map(|(file, _)| file.to_path_buf())
+            .filter(|file| *file != current)
+            .collect();
+        for entry in &self.file_list {
+            if entry.is_file()
+                && browser::is_supported(&entry.path)
+                && entry.path != current
+                && !files.contains(&entry.path)
+            {
+                files.push(entry.path.clone());
+            }
+        }
+        if files.is_empty() {
+            self.show_status("There are no other files to join with");
+            return;
+        }
+        self.join_wizard = Some(JoinWizard {
+            step: JoinStep::File,
+            files,
+            other: None,
+            left_key: 0,
+            right_key: 0,
+            state: ListState::default().with_selected(Some(0)),
+        });
+    }
+
+    /// Run a keymap action while the join wizard is open
+    ///
+    /// Enter moves on to the next step and Esc back to the previous one.
+    fn join_wizard_action(&mut self, action: Action) {
+        let Some(wizard) = &mut self.join_wizard else {
+            return;
+        };
+        let left_headers = self.table_data.as_ref().map_or(&[][..], |d| &d.headers);
+        let right_headers = wizard.other.as_ref().map_or(&[][..], |(_, d)| &d.headers);
+        let count = match wizard.step {
+            JoinStep::File => wizard.files.len(),
+            JoinStep::LeftKey => left_headers.len(),
+            JoinStep::RightKey => right_headers.len(),
+            JoinStep::Kind => JoinKind::ALL.len(),
+        };
+        let selected = wizard.state.selected().unwrap_or(0);
+
+        match action {
+            Action::ListDown if selected + 1 < count => {
+                wizard.state.select(Some(selected + 1));
+            }
+            Action::ListUp => {
+                wizard.state.select(Some(selected.saturating_sub(1)));
+            }
+            Action::StepBack => {
+                let (step, selected) = match wizard.step {
+                    JoinStep::File => {
+                        self.join_wizard = None;
+                        return;
+                    }
+                    JoinStep::LeftKey => {
+                        let file = wizard.other.take().map(|(file, _)| file);
+                        let index = wizard.files.iter().position(|f| Some(f) == file.as_ref());
+                        (JoinStep::File, index.unwrap_or(0))
+                    }
+                    JoinStep::RightKey => (JoinStep::LeftKey, wizard.left_key),
+                    JoinStep::Kind => (JoinStep::RightKey, wizard.right_key),
+                };
+                wizard.step = step;
+                wizard.state.select(Some(selected));
+            }
+            Action::Choose if count > 0 => match wizard.step {
+                JoinStep::File => {
+                    let file = wizard.files[selected].clone();
+                    let parked = self
+                        .buffers
+                        .iter()
+                        .flatten()
+                        .find(|b| b.file == file)
+                        .map(|b| b.data.clone());
+                    let data = match parked {
+                        Some(data) => Ok(data),
+                        None => self.load_data(&file),
+                    };
+                    let data = match data.and_then(|mut data| data.read_all().map(|()| data)) {
+                        Ok(data) => data,
+                        Err(e) => {
+                            self.show_error("Join", &format!("{:#}", e));
+                            return;
+                        }
+                    };
+                    // Start from a column both files share, the likely key
+                    let left_key = left_headers
+                        .iter()
+                        .position(|h| data.headers.contains(h))
+                        .unwrap_or(0);
+                    if let Some(wizard) = &mut self.join_wizard {
+                        wizard.other = Some((file, data));
+                        wizard.step = JoinStep::LeftKey;
+                        wizard.state.select(Some(left_key));
+                    }
+                }
+                JoinStep::LeftKey => {
+                    let right_key = right_headers
+                        .iter()
+                        .position(|h| *h == left_headers[selected])
+                        .unwrap_or(0);
+                    wizard.left_key = selected;
+                    wizard.step = JoinStep::RightKey;
+                    wizard.state.select(Some(right_key));
+                }
+                JoinStep::RightKey => {
+                    wizard.right_key = selected;
+                    wizard.step = JoinStep::Kind;
+                    wizard.state.select(Some(0));
+                }
+                JoinStep::Kind => {
+                    if let Some(wizard) = self.join_wizard.take() {
+                        self.apply_join(wizard, JoinKind::ALL[selected]);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Join the loaded file with the wizard's second file, showing the result
+    /// like a query result and the unmatched keys in a report
+    fn apply_join(&mut self, wizard: JoinWizard, kind: JoinKind) {
+        if !self.read_all_rows() {
+            return;
+        }
+        let (Some(data), Some((_, other))) = (&self.table_data, &wizard.other) else {
+            return;
+        };
+        let joined = join::join(
+            data.join_table(wizard.left_key),
+            other.join_table(wizard.right_key),
+            kind,
+        );
+
+        let mut report = format!("{} rows in the joined table", joined.rows.len());
+        for (unmatched, name) in [
+            (&joined.unmatched_left, &data.file_name),
+            (&joined.unmatched_right, &other.file_name),
+        ] {
+            if unmatched.is_empty() {
+                report.push_str(&format!("\nEvery key in {} matched", name));
+                continue;
+            }
+            const SHOWN: usize = 8;
+            let mut keys: Vec<String> = unmatched
+                .iter()
+                .take(SHOWN)
+                .map(|u| match (u.key.as_str(), u.rows) {
+                    ("", n) => format!("(empty) ×{}", n),
+                    (key, 1) => key.to_string(),
+                    (key, n) => format!("{} ×{}", key, n),
+                })
+                .collect();
+            if unmatched.len() > SHOWN {
+                keys.push(format!("… {} more", unmatched.len() - SHOWN));
+            }
+            report.push_str(&format!("\nUnmatched in {}: {}", name, keys.join(", ")));
+        }
+
+        let result = LoadedData {
+            file_name: format!(
+                "{} {} join {} on {} = {}",
+                data.file_name,
+                kind.label(),
+                other.file_name,
+                data.headers[wizard.left_key],
+                other.headers[wizard.right_key]
+            ),
+            file_type: "Join".to_string(),
+            compression: Compression::None,
+            decoded_size: 0,
+            raw: RawText::Text(String::new()),
+            headers: joined.headers,
+            rows: joined.rows,
+            encoding: data.encoding,
+            errors: Vec::new(),
+            row_fixes: BTreeMap::new(),
+            json_path: None,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: None,
+        };
+        let mut state = TableState::default();
+        state.select(Some(0));
+        self.query = Some(QueryView {
+            data: result,
+            state,
+        });
+        self.chart = ChartSettings::default();
+        self.active_panel = ActivePanel::DataView;
+        self.modal = Some(ModalDialog {
+            title: "Join Report".to_string(),
+            message: report,
+            error: false,
+        });
+    }
+
+    /// Open the sheet picker for the loaded workbook
+    fn open_sheet_picker(&mut self) {
+        let (Some(data), Some(file)) = (&self.table_data, &self.selected_file) else {
+            return;
+        };
+        match spreadsheet::sheet_names(file) {
+            Ok(names) if names.len() > 1 => {
+                let selected = names.iter().position(|n| Some(n) == data.sheet.as_ref());
+                self.sheet_picker = Some(SheetPicker {
+                    current: data.sheet.clone(),
+                    state: ListState::default().with_selected(selected.or(Some(0))),
+                    names,
+                });
+            }
+            Ok(_) => self.show_status("The workbook has a single sheet"),
+            Err(e) => self.show_error("Load Error", &format!("{:#}", e)),
+        }
+    }
+
+    /// Run a keymap action while the sheet picker is open
+    fn sheet_picker_action(&mut self, action: Action) {
+        let Some(picker) = &mut self.sheet_picker else {
+            return;
+        };
+        let selected = picker.state.selected().unwrap_or(0);
+
+        match action {
+            Action::ClosePopup => self.sheet_picker = None,
+            Action::ListDown if selected + 1 < picker.names.len() => {
+                picker.state.select(Some(selected + 1));
+            }
+            Action::ListUp => {
+                picker.state.select(Some(selected.saturating_sub(1)));
+            }
+            Action::Choose => {
+                let name = picker.names[selected].clone();
+                let unchanged = picker.current.as_ref() == Some(&name);
+                self.sheet_picker = None;
+                if !unchanged {
+                    self.apply_sheet(name);
+                } else if let Some(file) = &self.selected_file {
+                    self.sheets.insert(file.clone(), name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Show another sheet of the loaded workbook, and remember it for when
+    /// the file is reloaded
+    ///
+    /// The view settings belong to the old sheet's columns, so they go.
+    fn apply_sheet(&mut self, name: String) {
+        let Some(file) = self.selected_file.clone() else {
+            return;
+        };
+        let sheet = match spreadsheet::read_sheet(&file, Some(&name)) {
+            Ok(sheet) => sheet,
+            Err(e) => {
+                self.show_error("Load Error", &format!("{:#}", e));
+                return;
+            }
+        };
+        self.sheets.insert(file, name);
+        if let Some(data) = &mut self.table_data {
+            data.headers = sheet.headers;
+            data.rows = sheet.rows;
+            data.raw = RawText::Text(sheet.raw);
+            data.sheet = Some(sheet.name);
+            data.modified = false;
+        }
+        self.marks.clear();
+        self.visual_anchor = None;
+        self.row_filter = None;
+        self.pivot = None;
+        self.query = None;
+        self.view = ViewSettings::default();
+        self.column_offset = 0;
+        self.raw_scroll = 0;
+        self.chart = ChartSettings::default();
+        self.table_state.select(Some(0));
+        self.refresh_view_rows();
+        self.active_panel = ActivePanel::DataView;
+    }
+
+    /// Open the JSON path popup for the loaded JSON file
+    fn open_json_path_prompt(&mut self) {
+        let (Some(data), Some(file)) = (&self.table_data, &self.selected_file) else {
+            return;
+        };
+        if data.file_type != "JSON" && data.groups.is_none() {
+            self.show_error(
+                "JSON Path",
+                "Paths can only be applied to JSON, Parquet and Arrow files",
+            );
+            return;
+        }
+        let Ok(document) = json_document(data, file) else {
+            return;
+        };
+        self.json_path_prompt = Some(JsonPathPrompt {
+            input: data.json_path.clone().unwrap_or_default(),
+            suggestion: jsonpath::suggest(&document),
+            document,
+            error: None,
+        });
+    }
+
+    /// Handle keys while the JSON path popup is open
+    fn handle_json_path_key(&mut self, key: KeyCode) {
+        let Some(prompt) = &mut self.json_path_prompt else {
+            return;
+        };
+
+        match key {
+            KeyCode::Esc => {
+                self.json_path_prompt = None;
+            }
+            KeyCode::Tab => {
+                if let Some((path, _)) = &prompt.suggestion {
+                    prompt.input = path.clone();
+                    prompt.error = None;
+                }
+            }
+            KeyCode::Enter => {
+                let input = prompt.input.trim().to_string();
+                match jsonpath::parse(&input).and_then(|p| p.select(&prompt.document)) {
+                    Ok(selected) => {
+                        let (headers, rows) = self.json_to_table(&selected);
+                        self.apply_json_path(input, headers, rows);
+                    }
+                    Err(e) => prompt.error = Some(e),
+                }
+            }
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                prompt.error = None;
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                prompt.error = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Replace the loaded JSON table with rows selected by a path, and
+    /// remember the path for when the file is reloaded
+    fn apply_json_path(&mut self, path: String, headers: Vec<String>, rows: Vec<Vec<String>>) {
+        let (Some(data), Some(file)) = (&mut self.table_data, &self.selected_file) else {
+            return;
+        };
+        let path = match path.as_str() {
+            "" | "." | "$" => None,
+            _ => Some(path),
+        };
+        match &path {
+            Some(path) => self.json_paths.insert(file.clone(), path.clone()),
+            None => self.json_paths.remove(file),
+        };
+
+        data.headers = headers;
+        data.rows = rows;
+        data.json_path = path;
+        // The path was applied to every row of a Parquet or Arrow file
+        if let Some(groups) = &mut data.groups {
+            groups.loaded = groups.sizes.len();
+        }
+        self.json_path_prompt = None;
+        if let Err(e) = self.validate_rows() {
+            self.show_error("Schema Error", &format!("{:#}", e));
+        }
+        self.marks.clear();
+        self.row_filter = None;
+        self.pivot = None;
+        self.query = None;
+        self.chart = ChartSettings::default();
+        self.table_state.select(Some(0));
+        self.refresh_view_rows();
+        self.active_panel = ActivePanel::DataView;
+    }
+
+    /// Loaded data row index behind a display position, or `None` when the
+    /// data view shows a derived table (pivot groups or a query result)
+    fn base_row(&self, display: usize) -> Option<usize> {
+        if self.query.is_some() {
+            return None;
+        }
+        match (&self.pivot, &self.view_rows) {
+            (Some(pivot), _) => pivot
+                .drilled
+                .and_then(|group| pivot.groups[group].get(display).copied()),
+            (None, Some(rows)) => rows.get(display).copied(),
+            (None, None) => self
+                .table_data
+                .as_ref()
+                .filter(|data| display < data.rows.len())
+                .map(|_| display),
+        }
+    }
+
+    /// Toggle the mark on the selected row and move to the next one
+    fn toggle_mark(&mut self) {
+        let Some(selected) = self.view_state_mut().selected() else {
+            return;
+        };
+        if let Some(row) = self.base_row(selected) {
+            if !self.marks.remove(&row) {
+                self.marks.insert(row);
+            }
+            self.move_down();
+        }
+    }
+
+    /// Start a visual range selection, or mark every row in the current one
+    fn toggle_visual(&mut self) {
+        let Some(selected) = self.view_state_mut().selected() else {
+            return;
+        };
+        match self.visual_anchor.take() {
+            Some(anchor) => {
+                let (start, end) = (anchor.min(selected), anchor.max(selected));
+                let rows: Vec<usize> = (start..=end).filter_map(|i| self.base_row(i)).collect();
+                self.marks.extend(rows);
+            }
+            None if self.base_row(selected).is_some() => {
+                self.visual_anchor = Some(selected);
+            }
+            None => {}
+        }
+    }
+
+    /// Move the selection to the next (or previous) marked row on display
+    fn jump_to_mark(&mut self, forward: bool) {
+        let Some(selected) = self.view_state_mut().selected() else {
+            return;
+        };
+        let is_marked = |i: &usize| self.base_row(*i).is_some_and(|r| self.marks.contains(&r));
+        let target = if forward {
+            (selected + 1..self.view_len()).find(is_marked)
+        } else {
+            (0..selected).rev().find(is_marked)
+        };
+        if let Some(target) = target {
+            self.view_state_mut().select(Some(target));
+        }
+    }
+
+    /// Header and marked rows to export or copy: every loaded column in file
+    /// order, or only the shown columns (computed ones included) in display
+    /// order
+    fn marked_table(&self, data: &LoadedData, as_shown: bool) -> Vec<Vec<String>> {
+        let computed = self.view.computed(&data.headers);
+        let columns = if as_shown {
+            self.view.visible_columns(&data.headers)
+        } else {
+            (0..data.headers.len()).collect()
+        };
+        let header = columns
+            .iter()
+            .map(|&col| computed.header(&data.headers, col).to_string())
+            .collect();
+        let rows = self.marks.iter().map(|&row| {
+            columns
+                .iter()
+                .map(|&col| computed.cell(&data.rows[row], col))
+                .collect()
+        });
+        std::iter::once(header).chain(rows).collect()
+    }
+
+    /// Marked rows with headers as tab-separated text, for the clipboard
+    fn marked_rows_text(&self, as_shown: bool) -> String {
+        let Some(data) = &self.table_data else {
+            return String::new();
+        };
+        self.marked_table(data, as_shown)
+            .iter()
+            .map(|row| row.join("\t"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Write the marked rows to a new CSV file next to the open one
+    fn export_marked(&self, as_shown: bool) -> Result<PathBuf> {
+        let data = self.table_data.as_ref().context("No file loaded")?;
+        let stem = data
+            .file_name
+            .split('.')
+            .next()
+            .filter(|s| !s.is_empty())
+            .unwrap_or("export");
+        // Database tables are exported next to their database
+        let dir = match &data.window {
+            Some(window) => window.db.parent(),
+            None => self.selected_file.as_ref().and_then(|p| p.parent()),
+        }
+        .unwrap_or(&self.current_dir);
+        let path = (0..)
+            .map(|n| match n {
+                0 => dir.join(format!("{}-marked.csv", stem)),
+                n => dir.join(format!("{}-marked-{}.csv", stem, n)),
+            })
+            .find(|p| !p.exists())
+            .context("No free file name")?;
+
+        let mut writer = csv::Writer::from_path(&path).context("Failed to create file")?;
+        for row in self.marked_table(data, as_shown) {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+
+    /// Remove the marked rows from the loaded data (the file is not changed)
+    ///
+    /// Only while editing, and not from a database table, whose rows are
+    /// read again from the database when its page changes.
+    fn delete_marked(&mut self) {
+        if !self.editing {
+            self.show_status("Press i to edit the table before deleting rows");
+            return;
+        }
+        let Some(data) = &mut self.table_data else {
+            return;
+        };
+        if data.window.is_some() {
+            self.show_status("Rows of a database table can't be deleted");
+            return;
+        }
+        let marks = std::mem::take(&mut self.marks);
+        let rows = std::mem::take(&mut data.rows);
+        data.rows = rows
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !marks.contains(i))
+            .map(|(_, row)| row)
+            .collect();
+        // Shift repairs up past the deleted rows
+        let fixes = std::mem::take(&mut data.row_fixes);
+        data.row_fixes = fixes
+            .into_iter()
+            .filter(|(i, _)| !marks.contains(i))
+            .map(|(i, fix)| (i - marks.range(..i).count(), fix))
+            .collect();
+        let schema_errors = std::mem::take(&mut data.schema_errors);
+        data.schema_errors = schema_errors
+            .into_iter()
+            .filter(|(i, _)| !marks.contains(i))
+            .map(|(i, errors)| (i - marks.range(..i).count(), errors))
+            .collect();
+        data.modified = true;
+
+        self.pivot = None;
+        self.query = None;
+        self.row_filter = None;
+        self.view_rows = None;
+        self.chart = ChartSettings::default();
+        self.refresh_view_rows();
+        let last = self.view_len().saturating_sub(1);
+        let selected = self.table_state.selected().map(|i| i.min(last));
+        self.table_state.select(selected);
+    }
+
+    /// Show only the marked rows until Backspace is pressed
+    fn filter_to_marked(&mut self) {
+        self.pivot = None;
+        self.query = None;
+        self.row_filter = Some(RowFilter {
+            label: "marked rows".to_string(),
+            rows: self.marks.iter().copied().collect(),
+        });
+        self.chart = ChartSettings::default();
+        self.refresh_view_rows();
+        self.active_panel = ActivePanel::DataView;
+    }
+
+    /// Open the schema file popup for the loaded JSON file
+    fn open_schema_prompt(&mut self) {
+        let Some(data) = &self.table_data else {
+            return;
+        };
+        if data.file_type != "JSON" {
+            self.show_error(
+                "Schema",
+                "Only JSON files can be validated against a schema",
+            );
+            return;
+        }
+        let current = data.schema.as_ref().map(|p| p.display().to_string());
+        self.schema_prompt = Some(current.unwrap_or_default());
+    }
+
+    /// Handle keys while the schema file popup is open
+    ///
+    /// Relative paths are taken from the data file's directory; an empty
+    /// input goes back to the sibling schema, if there is one.
+    fn handle_schema_prompt_key(&mut self, code: KeyCode) {
+        let (Some(input), Some(file)) = (&mut self.schema_prompt, &self.selected_file) else {
+            return;
+        };
+        match code {
+            KeyCode::Esc => self.schema_prompt = None,
+            KeyCode::Enter => {
+                let file = file.clone();
+                let input = input.trim();
+                let previous = if input.is_empty() {
+                    self.schemas.remove(&file)
+                } else {
+                    let dir = file.parent().unwrap_or(Path::new("."));
+                    let schema = dir.join(input);
+                    if !schema.is_file() {
+                        self.show_error("Schema", &format!("No file at {}", schema.display()));
+                        return;
+                    }
+                    self.schemas.insert(file.clone(), schema)
+                };
+                match self.validate_rows() {
+                    Ok(()) => {
+                        self.schema_prompt = None;
+                        self.refresh_view_rows();
+                    }
+                    Err(e) => {
+                        match previous {
+                            Some(previous) => self.schemas.insert(file, previous),
+                            None => self.schemas.remove(&file),
+                        };
+                        let _ = self.validate_rows();
+                        self.show_error("Schema Error", &format!("{:#}", e));
+                    }
+                }
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+    }
+
+    /// Show only the rows that fail schema validation until Backspace is
+    /// pressed
+    fn filter_to_invalid(&mut self) {
+        let Some(data) = &self.table_data else {
+            return;
+        };
+        if data.schema.is_none() {
+            self.show_status("No schema is set; press v to choose one");
+            return;
+        }
+        if data.schema_errors.is_empty() {
+            self.show_status("Every row is valid");
+            return;
+        }
+        let selected = self.table_state.selected().and_then(|i| self.base_row(i));
+        self.pivot = None;
+        self.query = None;
+        self.row_filter = Some(RowFilter {
+            label: "invalid rows".to_string(),
+            rows: data.schema_errors.keys().copied().collect(),
+        });
+        self.chart = ChartSettings::default();
+        self.refresh_view_rows();
+        let rows = self.view_rows.as_deref().unwrap_or_default();
+        let position = selected.and_then(|base| rows.iter().position(|&row| row >= base));
+        self.table_state.select(Some(position.unwrap_or(0)));
+        self.active_panel = ActivePanel::DataView;
+    }
+
+    /// Switch between panels
+    fn switch_panel(&mut self) {
+        self.active_panel = match self.active_panel {
+            ActivePanel::FileList => ActivePanel::DataView,
+            ActivePanel::DataView => ActivePanel::FileList,
+        };
+    }
+
+    /// Switch to the next tab
+    fn next_tab(&mut self) {
+        self.select_tab(self.active_tab.next());
+    }
+
+    /// Switch to a tab, picking a default chart series when opening the chart
+    fn select_tab(&mut self, tab: ActiveTab) {
+        self.active_tab = tab;
+        if tab == ActiveTab::Chart && self.chart.series.is_empty() {
+            let rows = self.displayed_rows();
+            let numeric = self.displayed_table().and_then(|(data, _)| {
+                (0..data.headers.len()).find(|&col| {
+                    rows.iter()
+                        .any(|r| r.get(col).is_some_and(|v| chart::parse_number(v).is_some()))
+                })
+            });
+            self.chart.series.extend(numeric);
+        }
+    }
+
+    /// The table currently shown in the data view, with the subset of its
+    /// rows on display when drilled into a pivot group
+    fn displayed_table(&self) -> Option<(&LoadedData, Option<&[usize]>)> {
+        if let Some(query) = &self.query {
+            return Some((&query.data, None));
+        }
+        match (&self.pivot, &self.table_data) {
+            (Some(pivot), Some(data)) => match pivot.drilled {
+                Some(group) => Some((data, Some(&pivot.groups[group]))),
+                None => Some((&pivot.data, None)),
+            },
+            (None, Some(data)) => Some((data, self.view_rows.as_deref())),
+            (_, None) => None,
+        }
+    }
+
+    /// Selected position in the table currently shown in the data view
+    fn view_selected(&self) -> Option<usize> {
+        if let Some(query) = &self.query {
+            return query.state.selected();
+        }
+        match &self.pivot {
+            Some(pivot) if pivot.drilled.is_none() => pivot.state.selected(),
+            _ => self.table_state.selected(),
+        }
+    }
+
+    /// Name and type of the current column: the selected field in the
+    /// record view, otherwise the first unpinned column on screen
+    fn current_column(&self) -> Option<(String, &'static str)> {
+        let (data, _) = self.displayed_table()?;
+        let derived =
+            self.query.is_some() || self.pivot.as_ref().is_some_and(|p| p.drilled.is_none());
+        let (columns, computed) = if derived {
+            ((0..data.headers.len()).collect(), None)
+        } else {
+            let columns = self.view.visible_columns(&data.headers);
+            (columns, Some(self.view.computed(&data.headers)))
+        };
+        let index = match &self.record_view {
+            Some(state) => state.selected().unwrap_or(0),
+            None if derived => 0,
+            None => self.view.pinned_count(&data.headers) + self.column_offset,
+        };
+        let column = *columns.get(index).or(columns.last())?;
+        let rows = self.displayed_rows();
+        let values = rows.iter().map(|row| match &computed {
+            Some(computed) => computed.cell(row, column),
+            None => row.get(column).cloned().unwrap_or_default(),
+        });
+        let header = match &computed {
+            Some(computed) => computed.header(&data.headers, column),
+            None => &data.headers[column],
+        };
+        Some((header.to_string(), column_type(values)))
+    }
+
+    /// Parts of the status bar: position, current column, file format,
+    /// filter and sort, and whether rows were deleted
+    fn status_parts(&self) -> Vec<Span<'static>> {
+        let Some(data) = &self.table_data else {
+            let count = self.file_list.len();
+            return match self.file_list_state.selected() {
+                Some(i) if count > 0 => vec![Span::raw(format!("File {} of {}", i + 1, count))],
+                _ => vec![Span::raw("No file loaded")],
+            };
+        };
+
+        let mut parts = Vec::new();
+        if self.active_tab == ActiveTab::Raw {
+            let line = self.raw_scroll + 1;
+            parts.push(Span::raw(match data.raw.line_count() {
+                Some(count) => format!("Line {} of {}", line, count),
+                None => format!("Line {}", line),
+            }));
+        } else {
+            let count = self.view_len();
+            let total = match (&self.query, &self.pivot) {
+                (None, Some(pivot)) if pivot.drilled.is_some() => Some(data.rows.len()),
+                (None, None) if self.view_rows.is_some() => Some(data.rows.len()),
+                _ => None,
+            };
+            let mut position = match self.view_selected() {
+                Some(i) if count > 0 => format!("Row {} of {}", i + 1, count),
+                _ => format!("{} rows", count),
+            };
+            if let Some(total) = total {
+                position.push_str(&format!(" ({} total)", total));
+            }
+            parts.push(Span::raw(position));
+            if let (Some(window), None, None) = (&data.window, &self.query, &self.pivot) {
+                parts.push(Span::raw(format!(
+                    "rows {}-{} of {} in the table",
+                    window.offset + 1,
+                    window.offset + data.rows.len(),
+                    window.total
+                )));
+            }
+            if let (Some(groups), None, None) = (&data.groups, &self.query, &self.pivot) {
+                if groups.remaining() {
+                    parts.push(Span::raw(format!(
+                        "{} of {} {} read",
+                        groups.loaded,
+                        groups.sizes.len(),
+                        groups.format.group_label()
+                    )));
+                }
+            }
+            if let Some((name, kind)) = self.current_column() {
+                parts.push(Span::raw(format!("{}: {}", name, kind)));
+            }
+        }
+
+        let mut format = vec![data.file_type.clone(), data.encoding.label()];
+        if data.compression != Compression::None {
+            format.push(data.compression.label().to_string());
+        }
+        format.push(compression::format_size(data.decoded_size));
+        parts.push(Span::raw(format.join(", ")));
+
+        let view = match (&self.query, &self.pivot) {
+            (Some(_), _) => vec!["query result".to_string()],
+            (None, Some(pivot)) if pivot.drilled.is_none() => vec!["pivot".to_string()],
+            _ => {
+                let mut labels: Vec<String> =
+                    self.row_filter.iter().map(|f| f.label.clone()).collect();
+                labels.extend(self.view.describe());
+                labels
+            }
+        };
+        if !view.is_empty() {
+            parts.push(Span::raw(view.join(", ")));
+        }
+        if self.editing {
+            parts.push(Span::styled(
+                "editing",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
+        if data.modified {
+            parts.push(Span::styled(
+                "modified",
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ));
+        }
+        parts
+    }
+
+    /// Rows of the table currently shown in the data view, in display order
+    fn displayed_rows(&self) -> Vec<&Vec<String>> {
+        match self.displayed_table() {
+            Some((data, Some(indices))) => indices.iter().map(|&i| &data.rows[i]).collect(),
+            Some((data, None)) => data.rows.iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether chart tab keys apply
+    fn chart_active(&self) -> bool {
+        self.active_panel == ActivePanel::DataView && self.active_tab == ActiveTab::Chart
+    }
+
+    /// Full x range covered by the chart series
+    fn chart_x_range(&self) -> Option<(f64, f64)> {
+        let rows = self.displayed_rows();
+        let scale = chart::x_scale(&rows, self.chart.x);
+        let xs: Vec<f64> = self
+            .chart
+            .series
+            .iter()
+            .flat_map(|&y| chart::series_points(&rows, self.chart.x, scale, y))
+            .map(|(x, _)| x)
+            .collect();
+        chart::bounds(xs.into_iter())
+    }
+
+    /// Zoom the chart's x range in (factor < 1) or out (factor > 1)
+    fn zoom_chart(&mut self, factor: f64) {
+        if let Some(full) = self.chart_x_range() {
+            self.chart.zoom(full, factor);
+        }
+    }
+
+    /// Pan the chart's x range by a fraction of the visible width
+    fn pan_chart(&mut self, fraction: f64) {
+        if let Some(full) = self.chart_x_range() {
+            self.chart.pan(full, fraction);
+        }
+    }
+
+    /// Open the chart column picker popup
+    fn open_chart_picker(&mut self) {
+        if self.displayed_table().is_some() {
+            let mut state = ListState::default();
+            state.select(Some(0));
+            self.chart_picker = Some(state);
+        }
+    }
+
+    /// Run a keymap action while the chart column picker is open
+    fn chart_picker_action(&mut self, action: Action) {
+        let col_count = self.displayed_table().map_or(0, |(d, _)| d.headers.len());
+        let Some(state) = &mut self.chart_picker else {
+            return;
+        };
+        let selected = state.selected().unwrap_or(0);
+
+        match action {
+            Action::ClosePopup => {
+                self.chart_picker = None;
+            }
+            Action::ListDown if selected + 1 < col_count => {
+                state.select(Some(selected + 1));
+            }
+            Action::ListUp => {
+                state.select(Some(selected.saturating_sub(1)));
+            }
+            Action::ChartSeries => {
+                match self.chart.series.iter().position(|&c| c == selected) {
+                    Some(i) => {
+                        self.chart.series.remove(i);
+                    }
+                    None => self.chart.series.push(selected),
+                }
+                self.chart.window = None;
+            }
+            Action::ChartX => {
+                self.chart.x = if self.chart.x == Some(selected) {
+                    None
+                } else {
+                    Some(selected)
+                };
+                self.chart.window = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle keyboard input
+    fn handle_key(&mut self, key: KeyCode) {
+        // Handle modal first
+        if self.modal.is_some() || self.help.is_some() {
+            let context = match self.modal {
+                Some(_) => KeyContext::Modal,
+                None => KeyContext::Help,
+            };
+            if let Some(action) = keymap::lookup(&[context], key, false) {
+                self.run_action(action, key);
+            }
+            return;
+        }
+
+        if self.finder.is_some() {
+            self.handle_finder_key(key);
+            return;
+        }
+
+        if self.pivot_editor.is_some() {
+            self.handle_popup_key(KeyContext::PivotEditor, key);
+            return;
+        }
+
+        if self.chart_picker.is_some() {
+            self.handle_popup_key(KeyContext::ChartPicker, key);
+            return;
+        }
+
+        if self.computed_prompt.is_some() {
+            self.handle_computed_prompt_key(key);
+            return;
+        }
+
+        if self.column_picker.is_some() {
+            self.handle_popup_key(KeyContext::ColumnPicker, key);
+            return;
+        }
+
+        if self.recent_picker.is_some() {
+            self.handle_popup_key(KeyContext::RecentPicker, key);
+            return;
+        }
+
+        if self.buffer_picker.is_some() {
+            self.handle_popup_key(KeyContext::BufferPicker, key);
+            return;
+        }
+
+        if self.join_wizard.is_some() {
+            self.handle_popup_key(KeyContext::JoinWizard, key);
+            return;
+        }
+
+        if self.filter_prompt.is_some() {
+            self.handle_filter_prompt_key(key);
+            return;
+        }
+
+        if self.query_console.is_some() {
+            self.handle_query_console_key(key);
+            return;
+        }
+
+        if self.json_path_prompt.is_some() {
+            self.handle_json_path_key(key);
+            return;
+        }
+
+        if self.sheet_picker.is_some() {
+            self.handle_popup_key(KeyContext::SheetPicker, key);
+            return;
+        }
+
+        if self.glob_prompt.is_some() {
+            self.handle_glob_prompt_key(key);
+            return;
+        }
+
+        if self.schema_prompt.is_some() {
+            self.handle_schema_prompt_key(key);
+            return;
+        }
+
+        if self.goto_prompt.is_some() {
+            self.handle_goto_prompt_key(key);
+            return;
+        }
+
+        // Any key other than the ones listed closes the which-key popup
+        if let Some(prefix) = self.prefix.take() {
+            if let Some(action) = keymap::lookup(&[KeyContext::Prefix(prefix)], key, false) {
+                self.run_action(action, key);
+            }
+            return;
+        }
+
+        if let Some(action) = keymap::lookup(&self.key_contexts(), key, false) {
+            self.run_action(action, key);
+        }
+    }
+
+    /// Run the action a key is bound to in a list popup's context
+    fn handle_popup_key(&mut self, context: KeyContext, key: KeyCode) {
+        let Some(action) = keymap::lookup(&[context], key, false) else {
+            return;
+        };
+        match context {
+            KeyContext::PivotEditor => self.pivot_editor_action(action, key),
+            KeyContext::ChartPicker => self.chart_picker_action(action),
+            KeyContext::ColumnPicker => self.column_picker_action(action),
+            KeyContext::RecentPicker => self.recent_picker_action(action),
+            KeyContext::BufferPicker => self.buffer_picker_action(action),
+            KeyContext::SheetPicker => self.sheet_picker_action(action),
+            KeyContext::JoinWizard => self.join_wizard_action(action),
+            _ => {}
+        }
+    }
+
+    /// Contexts whose key bindings apply, besides popups
+    fn key_contexts(&self) -> Vec<KeyContext> {
+        let contexts = [
+            (KeyContext::Global, true),
+            (KeyContext::Visual, self.visual_anchor.is_some()),
+            (
+                KeyContext::FileList,
+                self.active_panel == ActivePanel::FileList,
+            ),
+            (
+                KeyContext::DataView,
+                self.active_panel == ActivePanel::DataView,
+            ),
+            (KeyContext::Record, self.record_active()),
+            (KeyContext::Raw, self.raw_active()),
+            (KeyContext::Chart, self.chart_active()),
+            (KeyContext::Marks, !self.marks.is_empty()),
+            (
+                KeyContext::Workbook,
+                self.table_data.as_ref().is_some_and(|d| d.sheet.is_some()),
+            ),
+        ];
+        contexts
+            .into_iter()
+            .filter_map(|(context, active)| active.then_some(context))
+            .collect()
+    }
+
+    /// Do what a key is bound to in the keymap
+    fn run_action(&mut self, action: Action, key: KeyCode) {
+        match action {
+            Action::Quit => {
+                self.should_quit = true;
+            }
+            Action::SwitchPanel => {
+                self.switch_panel();
+            }
+            Action::NextTab => {
+                self.next_tab();
+            }
+            Action::SelectTab => {
+                if let KeyCode::Char(c @ '1'..='3') = key {
+                    if let Some(tab) = ActiveTab::from_index(c as usize - '1' as usize) {
+                        self.select_tab(tab);
+                    }
+                }
+            }
+            Action::ShowHelp => {
+                self.help = Some(0);
+            }
+            Action::CloseHelp => {
+                self.help = None;
+            }
+            Action::HelpDown => {
+                self.help = self.help.map(|offset| offset + 1);
+            }
+            Action::HelpUp => {
+                self.help = self.help.map(|offset| offset.saturating_sub(1));
+            }
+            Action::DismissModal => {
+                self.dismiss_modal();
+            }
+            Action::OpenFinder => self.open_finder(),
+            Action::FinderUp => self.move_finder_selection(-1),
+            Action::FinderDown => self.move_finder_selection(1),
+            Action::ChartColumns => {
+                self.open_chart_picker();
+            }
+            Action::ChartKind => {
+                self.chart.kind = self.chart.kind.next();
+            }
+            Action::ZoomIn => {
+                self.zoom_chart(0.5);
+            }
+            Action::ZoomOut => {
+                self.zoom_chart(2.0);
+            }
+            Action::ResetZoom => {
+                self.chart.window = None;
+            }
+            Action::PanLeft => {
+                self.pan_chart(-0.25);
+            }
+            Action::PanRight => {
+                self.pan_chart(0.25);
+            }
+            Action::NextField => {
+                self.move_field(1);
+            }
+            Action::PrevField => {
+                self.move_field(-1);
+            }
+            Action::ToggleRecord => {
+                self.toggle_record_view();
+            }
+            Action::CloseRecord => {
+                self.record_view = None;
+            }
+            Action::RawDown => {
+                self.raw_scroll += 1;
+            }
+            Action::RawUp => {
+                self.raw_scroll = self.raw_scroll.saturating_sub(1);
+            }
+            Action::MoveDown | Action::NextRecord => {
+                self.move_down();
+            }
+            Action::MoveUp | Action::PrevRecord => {
+                self.move_up();
+            }
+            Action::PageDown => {
+                self.move_by(self.page_rows() as isize);
+            }
+            Action::PageUp => {
+                self.move_by(-(self.page_rows() as isize));
+            }
+            Action::HalfPageDown => {
+                self.move_by((self.page_rows() / 2).max(1) as isize);
+            }
+            Action::HalfPageUp => {
+                self.move_by(-((self.page_rows() / 2).max(1) as isize));
+            }
+            Action::Top => {
+                self.move_to(|_| 0);
+            }
+            Action::Bottom => {
+                self.move_to(|_| usize::MAX);
+            }
+            Action::GotoNumber => {
+                self.open_goto_prompt(false);
+            }
+            Action::GotoName => {
+                self.open_goto_prompt(true);
+            }
+            Action::OpenSelected => {
+                self.select_current();
+            }
+            Action::DrillDown => {
+                self.drill_down();
+            }
+            Action::Back => {
+                if self.query.take().is_some() {
+                    self.chart = ChartSettings::default();
+                } else if self.pivot.is_some() {
+                    self.pivot_back();
+                } else if self.row_filter.is_some() {
+                    // Peel off the marked row subset, then the filter, then the sort
+                    let selected = self.table_state.selected().and_then(|i| self.base_row(i));
+                    self.row_filter = None;
+                    self.view_rows = None;
+                    self.table_state.select(selected);
+                    self.chart = ChartSettings::default();
+                    self.refresh_view_rows();
+                } else if self.view.filter.is_some() {
+                    let view = ViewSettings {
+                        filter: None,
+                        ..self.view.clone()
+                    };
+                    let _ = self.set_view(view);
+                } else if self.view.sort.is_some() {
+                    let view = ViewSettings {
+                        sort: None,
+                        ..self.view.clone()
+                    };
+                    let _ = self.set_view(view);
+                }
+            }
+            Action::ParentDir => {
+                self.collapse_or_go_to_parent();
+            }
+            Action::ScrollLeft => {
+                self.scroll_columns(-1);
+            }
+            Action::ScrollRight => {
+                self.scroll_columns(1);
+            }
+            Action::CycleFileSort => {
+                self.update_list_options(|options| options.sort = options.sort.next());
+            }
+            Action::ReverseFileSort => {
+                self.update_list_options(|options| options.descending = !options.descending);
+            }
+            Action::ToggleHidden => {
+                self.update_list_options(|options| options.show_hidden = !options.show_hidden);
+            }
+            Action::ToggleAllFiles => {
+                self.update_list_options(|options| options.show_all = !options.show_all);
+            }
+            Action::GlobFilter => {
+                self.glob_prompt = Some(self.list_options.glob.clone().unwrap_or_default());
+            }
+            Action::Refresh => {
+                let _ = self.refresh_file_list();
+            }
+            Action::ToggleErrors => {
+                self.show_errors = !self.show_errors;
+            }
+            Action::ToggleCsvMode => {
+                self.toggle_csv_mode();
+            }
+            Action::CycleEncoding => {
+                self.cycle_encoding();
+            }
+            Action::Pivot => {
+                self.open_pivot_editor();
+            }
+            Action::Query => {
+                self.query_console = Some(QueryConsole::default());
+            }
+            Action::JsonPath => {
+                self.open_json_path_prompt();
+            }
+            Action::SheetPicker => {
+                self.open_sheet_picker();
+            }
+            Action::Schema => {
+                self.open_schema_prompt();
+            }
+            Action::InvalidRows => {
+                self.filter_to_invalid();
+            }
+            Action::ToggleMark => {
+                self.toggle_mark();
+            }
+            Action::ToggleVisual => {
+                self.toggle_visual();
+            }
+            Action::CancelVisual => {
+                self.visual_anchor = None;
+            }
+            Action::NextMark => {
+                self.jump_to_mark(true);
+            }
+            Action::PrevMark => {
+                self.jump_to_mark(false);
+            }
+            Action::NextDbPage => {
+                self.turn_db_page(true);
+            }
+            Action::PrevDbPage => {
+                self.turn_db_page(false);
+            }
+            Action::Filter => {
+                self.open_filter_prompt();
+            }
+            Action::Columns => {
+                self.open_column_picker();
+            }
+            Action::Recent => {
+                self.open_recent_picker();
+            }
+            Action::Buffers => {
+                self.open_buffer_picker();
+            }
+            Action::Join => {
+                self.open_join_wizard();
+            }
+            Action::NextBuffer => {
+                self.cycle_buffer(true);
+            }
+            Action::PrevBuffer => {
+                self.cycle_buffer(false);
+            }
+            Action::Prefix(prefix) => {
+                self.prefix = Some(prefix);
+            }
+            Action::ExportMarked | Action::ExportMarkedShown => {
+                match self.export_marked(action == Action::ExportMarkedShown) {
+                    Ok(path) => {
+                        let mut message =
+                            format!("Wrote {} rows to {}", self.marks.len(), path.display());
+                        if self.table_data.as_ref().is_some_and(LoadedData::is_partial) {
+                            message.push_str(" (marks only cover the loaded rows)");
+                        }
+                        self.show_status(&message);
+                    }
+                    Err(e) => self.show_error("Export Error", &format!("{:#}", e)),
+                }
+            }
+            Action::CopyMarked | Action::CopyMarkedShown => {
+                let text = self.marked_rows_text(action == Action::CopyMarkedShown);
+                match copy_to_clipboard(&text) {
+                    Ok(()) => {
+                        let message =
+                            format!("Copied {} rows as tab-separated text", self.marks.len());
+                        self.show_status(&message);
+                    }
+                    Err(e) => self.show_error("Copy Error", &e.to_string()),
+                }
+            }
+            Action::DeleteMarked => self.delete_marked(),
+            Action::ToggleEditing => {
+                self.editing = !self.editing;
+                self.show_status(if self.editing {
+                    "Editing: m d deletes the marked rows"
+                } else {
+                    "Stopped editing"
+                });
+            }
+            Action::FilterMarked => self.filter_to_marked(),
+            Action::ClearMarks => self.marks.clear(),
+            // Bound in list popups only, whose handlers run them
+            Action::ListDown
+            | Action::ListUp
+            | Action::Choose
+            | Action::ClosePopup
+            | Action::StepBack
+            | Action::RemoveEntry
+            | Action::PivotGroupBy
+            | Action::PivotAggregate
+            | Action::ChartSeries
+            | Action::ChartX
+            | Action::SortColumn
+            | Action::HideColumn
+            | Action::PinColumn
+            | Action::MoveColumnDown
+            | Action::MoveColumnUp
+            | Action::ResetColumns
+            | Action::AddComputed
+            | Action::RemoveComputed => {}
+        }
+    }
+}
+
+/// Parsed CSV contents: headers, rows, malformed records and ragged row repairs
+type ParsedCsv = (
+    Vec<String>,
+    Vec<Vec<String>>,
+    Vec<RecordError>,
+    BTreeMap<usize, RowFix>,
+);
+
+/// Parse CSV as it is read, collecting malformed records instead of
+/// dropping them
+fn parse_csv(content: impl Read, mode: CsvMode) -> Result<ParsedCsv> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content);
+
+    let headers: Vec<String> = reader
+        .byte_headers()
+        .context("Failed to read CSV headers")?
+        .iter()
+        .map(|h| String::from_utf8_lossy(h).into_owned())
+        .collect();
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut row_fixes = BTreeMap::new();
+
+    for result in reader.byte_records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let (line, byte) = e
+                    .position()
+                    .map(|p| (p.line(), p.byte()))
+                    .unwrap_or_default();
+                let fatal = matches!(e.kind(), csv::ErrorKind::Io(_));
+                errors.push(RecordError {
+                    line,
+                    byte,
+                    kind: csv_error_kind(e.kind()).to_string(),
+                    message: format!("{} (skipped)", e),
+                });
+                if fatal {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        let (line, byte) = record
+            .position()
+            .map(|p| (p.line(), p.byte()))
+            .unwrap_or_default();
+
+        let mut fields = Vec::with_capacity(record.len());
+        let mut bad_utf8 = false;
+        for field in record.iter() {
+            match std::str::from_utf8(field) {
+                Ok(s) => fields.push(s.to_string()),
+                Err(_) => {
+                    bad_utf8 = true;
+                    fields.push(String::from_utf8_lossy(field).into_owned());
+                }
+            }
+        }
+
+        if bad_utf8 {
+            let action = match mode {
+                CsvMode::Strict => "skipped",
+                CsvMode::Lenient => "invalid bytes replaced",
+            };
+            errors.push(RecordError {
+                line,
+                byte,
+                kind: "Utf8".to_string(),
+                message: format!("invalid UTF-8 in record ({})", action),
+            });
+            if mode == CsvMode::Strict {
+                continue;
+            }
+        }
+
+        if fields.len() != headers.len() {
+            let action = match mode {
+                CsvMode::Strict => "skipped",
+                CsvMode::Lenient if fields.len() < headers.len() => "padded",
+                CsvMode::Lenient => "truncated",
+            };
+            errors.push(RecordError {
+                line,
+                byte,
+                kind: "UnequalLengths".to_string(),
+                message: format!(
+                    "expected {} fields, found {} ({})",
+                    headers.len(),
+                    fields.len(),
+                    action
+                ),
+            });
+            if mode == CsvMode::Strict {
+                continue;
+            }
+            if fields.len() < headers.len() {
+                row_fixes.insert(rows.len(), RowFix::Padded(headers.len() - fields.len()));
+                fields.resize(headers.len(), String::new());
+            } else {
+                let extra = fields.split_off(headers.len());
+                row_fixes.insert(rows.len(), RowFix::Truncated(extra));
+            }
+        }
+
+        rows.push(fields);
+    }
+
+    Ok((headers, rows, errors, row_fixes))
+}
+
+/// Parse NDJSON, one value per line, into an array; lines that fail to parse
+/// are skipped and reported
+fn parse_ndjson(content: &[u8]) -> (Value, Vec<RecordError>) {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    let mut byte = 0;
+    for (i, line) in content.split(|&b| b == b'\n').enumerate() {
+        let start = byte;
+        byte += line.len() as u64 + 1;
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        match serde_json::from_slice(line) {
+            Ok(value) => items.push(value),
+            Err(e) => errors.push(RecordError {
+                line: i as u64 + 1,
+                byte: start,
+                kind: "invalid JSON".to_string(),
+                message: format!("{} (line skipped)", e),
+            }),
+        }
+    }
+    (Value::Array(items), errors)
+}
+
+/// Parse a whole JSON document, or NDJSON lines as an array
+fn parse_json(
+    content: &[u8],
+    ext: &str,
+    encoding: &EncodingInfo,
+) -> Result<(Value, Vec<RecordError>)> {
+    if ext == "json" {
+        let value = serde_json::from_slice(content)
+            .with_context(|| format!("Failed to parse JSON (decoded as {})", encoding.label()))?;
+        Ok((value, Vec::new()))
+    } else {
+        Ok(parse_ndjson(content))
+    }
+}
+
+/// Stream the elements of a top-level JSON array straight from the file
+///
+/// Returns the decompressed size and the encoding, or `None` when the file
+/// has to be read whole instead: it is not UTF-8 or not an array.
+fn stream_json_array(
+    path: &Path,
+    forced: Option<&'static Encoding>,
+    f: impl FnMut(Value),
+) -> Result<Option<(u64, EncodingInfo)>> {
+    let mut reader = BufReader::with_capacity(1 << 16, compression::open(path)?);
+    let Some(encoding) = encoding::utf8_stream(reader.fill_buf()?, forced) else {
+        return Ok(None);
+    };
+    Ok(jsonstream::for_each_element(reader, f)?.map(|size| (size, encoding)))
+}
+
+/// Raw view text for a decoded file: paged from disk when the file is
+/// UTF-8 already, otherwise the decoded copy
+fn raw_text(path: &Path, content: &[u8], encoding: &EncodingInfo) -> RawText {
+    if encoding.encoding == UTF_8 {
+        RawText::File(FilePager::new(path))
+    } else {
+        RawText::Text(String::from_utf8_lossy(content).into_owned())
+    }
+}
+
+/// The whole JSON document behind the loaded data, read back from the file
+/// when the raw view pages it from there
+fn json_document(data: &LoadedData, path: &Path) -> Result<Value> {
+    if data.groups.is_some() {
+        return columnar::document(path);
+    }
+    let (ext, _) = compression::split_extension(path);
+    match &data.raw {
+        RawText::Text(text) => Ok(parse_json(text.as_bytes(), &ext, &data.encoding)?.0),
+        RawText::File(_) => {
+            let bytes = compression::read(path)?;
+            let (content, _) = encoding::to_utf8(&bytes, Some(UTF_8));
+            Ok(parse_json(&content, &ext, &data.encoding)?.0)
+        }
+    }
+}
+
+/// Which array elements become rows: all of them, or only the objects when
+/// the first element is an object
+#[derive(Debug, Default)]
+struct ArrayLayout {
+    objects: Option<bool>,
+}
+
+impl ArrayLayout {
+    fn accepts(&mut self, item: &Value) -> bool {
+        !*self.objects.get_or_insert(item.is_object()) || item.is_object()
+    }
+}
+
+/// Table built from array elements as they arrive, headed by the first
+/// object's keys or Index/Value for other elements
+#[derive(Debug, Default)]
+struct ArrayRows {
+    layout: ArrayLayout,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl ArrayRows {
+    fn push(&mut self, item: &Value, cell: impl Fn(Option<&Value>) -> String) {
+        if !self.layout.accepts(item) {
+            return;
+        }
+        match item {
+            Value::Object(obj) if self.layout.objects == Some(true) => {
+                if self.rows.is_empty() {
+                    self.headers = obj.keys().cloned().collect();
+                }
+                let row = self.headers.iter().map(|h| cell(obj.get(h))).collect();
+                self.rows.push(row);
+            }
+            _ => self
+                .rows
+                .push(vec![self.rows.len().to_string(), cell(Some(item))]),
+        }
+    }
+
+    fn finish(self) -> (Vec<String>, Vec<Vec<String>>) {
+        match self.layout.objects {
+            None => (vec!["(empty)".to_string()], Vec::new()),
+            Some(true) => (self.headers, self.rows),
+            Some(false) => (vec!["Index".to_string(), "Value".to_string()], self.rows),
+        }
+    }
+}
+
+/// Schema failures of array elements checked one at a time, numbered as
+/// table rows
+struct RowCheck<'a> {
+    schema: &'a Schema,
+    layout: ArrayLayout,
+    rows: usize,
+    errors: BTreeMap<usize, Vec<SchemaError>>,
+}
+
+impl<'a> RowCheck<'a> {
+    fn new(schema: &'a Schema) -> Self {
+        RowCheck {
+            schema,
+            layout: ArrayLayout::default(),
+            rows: 0,
+            errors: BTreeMap::new(),
+        }
+    }
+
+    fn check(&mut self, item: &Value) {
+        if self.layout.accepts(item) {
+            let errors = self.schema.validate(item);
+            if !errors.is_empty() {
+                self.errors.insert(self.rows, errors);
+            }
+            self.rows += 1;
+        }
+    }
+}
+
+/// Validate each row of a JSON file against a schema, keeping the failures
+///
+/// A streamed array is validated straight from the file again.
+fn validate(
+    data: &LoadedData,
+    path: &Path,
+    schema_path: &Path,
+) -> Result<BTreeMap<usize, Vec<SchemaError>>> {
+    let schema = Schema::load(schema_path)?;
+    let mut check = RowCheck::new(&schema);
+    let streamed = data.json_path.is_none()
+        && matches!(data.raw, RawText::File(_))
+        && compression::split_extension(path).0 == "json"
+        && stream_json_array(path, Some(UTF_8), |item| check.check(&item))
+            .is_ok_and(|size| size.is_some());
+
+    if !streamed {
+        check = RowCheck::new(&schema);
+        let document = json_document(data, path)?;
+        let selected = match &data.json_path {
+            Some(json_path) => jsonpath::parse(json_path).and_then(|p| p.select(&document))?,
+            None => document,
+        };
+        let Value::Array(items) = &selected else {
+            anyhow::bail!("Rows must come from a JSON array");
+        };
+        items.iter().for_each(|item| check.check(item));
+    }
+    if check.rows != data.rows.len() {
+        anyhow::bail!("Rows were deleted since the file was read; reload it first");
+    }
+    Ok(check.errors)
+}
+
+/// Short name for a csv error kind, as shown in the error list
+fn csv_error_kind(kind: &csv::ErrorKind) -> &'static str {
+    match kind {
+        csv::ErrorKind::Io(_) => "Io",
+        csv::ErrorKind::Utf8 { .. } => "Utf8",
+        csv::ErrorKind::UnequalLengths { .. } => "UnequalLengths",
+        csv::ErrorKind::Seek => "Seek",
+        csv::ErrorKind::Serialize(_) => "Serialize",
+        csv::ErrorKind::Deserialize { .. } => "Deserialize",
+        _ => "Other",
+    }
+}
+
+/// Create a centered rect for modal dialogs
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let popup_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(popup_layout[1])[1]
+}
+
+/// Copy text to the system clipboard with an OSC 52 escape sequence, which
+/// most terminal emulators (including over SSH and in tmux) understand
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
+/// Render the UI
+fn ui(frame: &mut Frame, app: &mut App) {
+    // Keep the bottom line for the status bar
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(frame.area());
+
+    // Create the main layout: two panels side by side
+    let main_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(rows[0]);
+
+    // Render file list panel (left)
+    render_file_list(frame, app, main_chunks[0]);
+
+    // Render data view panel (right)
+    render_data_view(frame, app, main_chunks[1]);
+
+    render_status_bar(frame, app, rows[1]);
+
+    // Render pivot setup popup if open
+    if let (Some(editor), Some(data)) = (&mut app.pivot_editor, &app.table_data) {
+        render_pivot_editor(frame, editor, data);
+    }
+
+    // Render chart column picker if open
+    if app.chart_picker.is_some() {
+        render_chart_picker(frame, app);
+    }
+
+    // Render JSON path popup if open
+    if let Some(prompt) = &app.json_path_prompt {
+        render_json_path_prompt(frame, prompt);
+    }
+
+    if let Some(picker) = &mut app.sheet_picker {
+        render_sheet_picker(frame, picker);
+    }
+
+    if let Some(input) = &app.glob_prompt {
+        render_glob_prompt(frame, input);
+    }
+
+    if let Some(input) = &app.schema_prompt {
+        render_schema_prompt(frame, input);
+    }
+
+    if let Some(prompt) = &app.goto_prompt {
+        render_goto_prompt(frame, prompt);
+    }
+
+    // Render the keys that can follow a prefix key
+    if let Some(prefix) = app.prefix {
+        render_which_key(frame, prefix, rows[0]);
+    }
+
+    if let Some(prompt) = &app.filter_prompt {
+        render_filter_prompt(frame, prompt);
+    }
+
+    if app.column_picker.is_some() {
+        render_column_picker(frame, app);
+    }
+
+    if let Some(prompt) = &app.computed_prompt {
+        render_computed_prompt(frame, prompt);
+    }
+
+    if app.recent_picker.is_some() {
+        render_recent_picker(frame, app);
+    }
+
+    if app.buffer_picker.is_some() {
+        render_buffer_picker(frame, app);
+    }
+
+    if app.join_wizard.is_some() {
+        render_join_wizard(frame, app);
+    }
+
+    if let Some(finder) = &mut app.finder {
+        render_finder(frame, finder);
+    }
+
+    if let Some(offset) = &mut app.help {
+        render_help(frame, offset);
+    }
+
+    // Render modal dialog if present
+    if let Some(modal) = &app.modal {
+        render_modal(frame, modal);
+    }
+}
+
+/// Render the file list panel
+fn render_file_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let is_active = app.active_panel == ActivePanel::FileList;
+    let border_style = if is_active {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+
+    let title = format!(" Files: {} ", app.current_dir.display());
+    let block = Block::default()
+        .title(title)
+        .title_bottom(format!(" {} ", app.list_options.summary()))
+        .borders(Borders::ALL)
+        .border_style(border_style);
+
+    // Metadata columns are added while the name keeps at least 10 characters:
+    // size first, then the decompressed size (only when the directory has
+    // compressed files), row count and modification time
+    let has_compressed = app
+        .file_list
+        .iter()
+        .any(|e| app.file_sizes.contains_key(&e.path));
+    let mut wanted = vec![(FileColumn::Size, 8)];
+    if has_compressed {
+        wanted.push((FileColumn::Uncompressed, 9));
+    }
+    wanted.extend([(FileColumn::Rows, 7), (FileColumn::Modified, 13)]);
+
+    let inner_width = area.width.saturating_sub(4) as usize;
+    let mut meta_width = 0;
+    let mut columns = Vec::new();
+    for (column, width) in wanted {
+        if inner_width.saturating_sub(4 + meta_width + width) < 10 {
+            break;
+        }
+        meta_width += width;
+        columns.push((column, width));
+    }
+    let name_width = inner_width.saturating_sub(4 + meta_width);
+    let this_year = Local::now().year();
+    let dim = Style::default().fg(Color::DarkGray);
+
+    let items: Vec<ListItem> = app
+        .file_list
+        .iter()
+        .map(|entry| {
+            let (icon, style) = match entry.kind {
+                EntryKind::Parent | EntryKind::Dir => {
+                    ("[D]", Style::default().fg(Color::Blue).bold())
+                }
+                EntryKind::Database => ("[S]", Style::default().fg(Color::Magenta).bold()),
+                EntryKind::Table => ("  [T]", Style::default().fg(Color::Magenta)),
+                EntryKind::View => ("  [V]", Style::default().fg(Color::Magenta)),
+                EntryKind::File => {
+                    let (ext, _) = compression::split_extension(&entry.path);
+                    match ext.as_str() {
+                        "json" | "ndjson" | "jsonl" => ("[J]", Style::default().fg(Color::Yellow)),
+                        "csv" => ("[C]", Style::default().fg(Color::Green)),
+                        "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => {
+                            ("[X]", Style::default().fg(Color::Green).bold())
+                        }
+                        _ if columnar::is_columnar(&entry.path) => {
+                            ("[P]", Style::default().fg(Color::Cyan))
+                        }
+                        _ => ("[F]", Style::default()),
+                    }
+                }
+            };
+
+            // Tables are indented under their database
+            let name_width = name_width.saturating_sub(icon.len() - 3);
+            let mut name = entry.name.clone();
+            if name.chars().count() > name_width {
+                name = name.chars().take(name_width.saturating_sub(1)).collect();
+                name.push('…');
+            }
+            let mut spans = vec![
+                Span::styled(format!("{} ", icon), style),
+                Span::raw(format!("{:<width$}", name, width = name_width)),
+            ];
+
+            let sizes = app.file_sizes.get(&entry.path);
+            for (column, width) in &columns {
+                let text = match column {
+                    FileColumn::Size => entry.size.map(compression::format_size),
+                    FileColumn::Uncompressed => sizes.map(|sizes| {
+                        let size = sizes.uncompressed.map(compression::format_size);
+                        format!("→ {}", size.as_deref().unwrap_or("?"))
+                    }),
+                    FileColumn::Rows => entry.rows.map(|n| n.to_string()),
+                    FileColumn::Modified => entry.modified.map(|time| {
+                        let time = DateTime::<Local>::from(time);
+                        if time.year() == this_year {
+                            time.format("%b %e %H:%M").to_string()
+                        } else {
+                            time.format("%b %e  %Y").to_string()
+                        }
+                    }),
+                };
+                spans.push(Span::styled(
+                    format!("{:>width$}", text.unwrap_or_default(), width = width),
+                    dim,
+                ));
+            }
+
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    app.file_page = area.height.saturating_sub(2) as usize;
+    frame.render_stateful_widget(list, area, &mut app.file_list_state);
+}
+
+/// Render the fuzzy file finder popup
+fn render_finder(frame: &mut Frame, finder: &mut FileFinder) {
+    let area = centered_rect(70, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let count = finder.index.files.len();
+    let status = if finder.index.done {
+        format!(" {} of {} files ", finder.matches.len(), count)
+    } else {
+        format!(" Indexing... {} files ", count)
+    };
+    let block = Block::default()
+        .title(format!(" Find file in {} ", finder.index.root.display()))
+        .title_bottom(status)
+        .title_bottom(Line::from(" Enter open, ↑/↓ or Ctrl-P/N select, Esc close ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(inner);
+
+    let prompt = input_lines(&finder.query, None, "").remove(0);
+    frame.render_widget(Paragraph::new(prompt), chunks[0]);
+
+    let highlight = Style::default().fg(Color::Yellow).bold();
+    let items: Vec<ListItem> = finder
+        .matches
+        .iter()
+        .map(|(index, m)| {
+            let spans: Vec<Span> = finder.index.files[*index]
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    if m.positions.contains(&i) {
+                        Span::styled(c.to_string(), highlight)
+                    } else {
+                        Span::raw(c.to_string())
+                    }
+                })
+                .collect();
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, chunks[1], &mut finder.state);
+}
+
+/// Render the glob filter popup for the file panel
+fn render_glob_prompt(frame: &mut Frame, input: &str) {
+    let area = centered_rect(50, 100, frame.area());
+    let height = area.height.min(4);
+    let area = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Filter files (e.g. *.csv or log-[0-9]*) ")
+        .title_bottom(" Enter apply, empty to clear, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let lines = input_lines(input, None, "Matches file names; directories stay listed");
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the goto popup, with the matching names while completing
+fn render_goto_prompt(frame: &mut Frame, prompt: &GotoPrompt) {
+    let area = centered_rect(50, 100, frame.area());
+    let height = area.height.min(4);
+    let area = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+
+    let hints = match prompt.names {
+        Some(_) => " Tab complete, Enter go, Esc cancel ",
+        None => " Enter go, Esc cancel ",
+    };
+    let block = Block::default()
+        .title(format!(" Go to {} ", prompt.target))
+        .title_bottom(hints)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let mut lines = vec![Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Magenta)),
+        Span::raw(prompt.input.as_str()),
+        Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+    ])];
+    if let Some(error) = &prompt.error {
+        lines.push(Line::styled(
+            error.as_str(),
+            Style::default().fg(Color::Red),
+        ));
+    } else if prompt.names.is_some() {
+        let spans: Vec<Span> = prompt
+            .matches
+            .iter()
+            .enumerate()
+            .flat_map(|(i, name)| {
+                let style = if prompt.completed == Some(i) {
+                    Style::default().fg(Color::Yellow).bold()
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                [Span::styled(name.as_str(), style), Span::raw("  ")]
+            })
+            .collect();
+        lines.push(Line::from(spans));
+    } else {
+        lines.push(Line::styled(
+            format!("A {} number, from 1", prompt.target),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the schema file popup
+fn render_schema_prompt(frame: &mut Frame, input: &str) {
+    let area = centered_rect(60, 100, frame.area());
+    let height = area.height.min(4);
+    let area = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Validate rows against a JSON Schema ")
+        .title_bottom(" Enter apply, empty for <name>.schema.json, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let lines = input_lines(
+        input,
+        None,
+        "Schema file, relative to the data file's directory",
+    );
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the data view panel with tabs
+fn render_data_view(frame: &mut Frame, app: &mut App, area: Rect) {
+    let is_active = app.active_panel == ActivePanel::DataView;
+    let border_style = if is_active {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+
+    // Show open buffers in a bar above the data view
+    let area = if app.buffers.is_empty() {
+        area
+    } else {
+        let split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(area);
+        render_buffer_bar(frame, app, split[0]);
+        split[1]
+    };
+
+    // Split area for tabs and content
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    // Render tabs
+    // Database tables and Parquet or Arrow files show their schema instead
+    // of the file text
+    let is_table = app
+        .table_data
+        .as_ref()
+        .is_some_and(|d| d.window.is_some() || d.groups.is_some());
+    let titles = vec!["Table", if is_table { "Schema" } else { "Raw" }, "Chart"];
+    let tabs = Tabs::new(titles)
+        .block(
+            Block::default()
+                .title(" Data View ")
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        )
+        .select(app.active_tab.index())
+        .style(Style::default().fg(Color::White))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    frame.render_widget(tabs, chunks[0]);
+
+    // Leave room for the SQL pane under the content when it is open
+    let content_area = match &app.query_console {
+        Some(console) => {
+            let split = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(5)])
+                .split(chunks[1]);
+            render_query_console(frame, console, split[1]);
+            split[0]
+        }
+        None => chunks[1],
+    };
+
+    app.view_page = content_area.height.saturating_sub(2) as usize;
+
+    // Render content based on active tab
+    let content_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(border_style);
+
+    match app.active_tab {
+        ActiveTab::Table => {
+            render_table_view(frame, app, content_area, content_block);
+        }
+        ActiveTab::Raw => {
+            render_raw_view(frame, app, content_area, content_block);
+        }
+        ActiveTab::Chart => {
+            render_chart_view(frame, app, content_area, content_block);
+        }
+    }
+}
+
+/// Render the tab bar of open buffers with their memory usage
+fn render_buffer_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let titles: Vec<Line> = app
+        .buffer_list()
+        .iter()
+        .enumerate()
+        .map(|(i, (_, data))| {
+            Line::from(vec![
+                Span::raw(format!("{}:{} ", i + 1, data.file_name)),
+                Span::styled(
+                    compression::format_size(data.memory_usage() as u64),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+        })
+        .collect();
+    let active = app.buffers.iter().position(Option::is_none).unwrap_or(0);
+    let tabs = Tabs::new(titles)
+        .select(active)
+        .style(Style::default().fg(Color::White))
+        .highlight_style(
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        );
+    frame.render_widget(tabs, area);
+}
+
+/// Render the status bar, with any transient message on the right
+fn render_status_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let style = Style::default().fg(Color::White).bg(Color::DarkGray);
+    let message = app
+        .status_message
+        .as_ref()
+        .map(|(message, _)| message.as_str());
+    let width = message.map_or(0, |m| m.chars().count() as u16 + 2);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(width)])
+        .split(area);
+
+    let mut spans = vec![Span::raw(" ")];
+    for (i, part) in app.status_parts().into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" │ "));
+        }
+        spans.push(part);
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)).style(style), chunks[0]);
+
+    if let Some(message) = message {
+        let message = Paragraph::new(format!(" {} ", message))
+            .style(style.fg(Color::Yellow).add_modifier(Modifier::BOLD));
+        frame.render_widget(message, chunks[1]);
+    }
+}
+
+/// Render the SQL input pane, with a caret under the position of any error
+fn render_query_console(frame: &mut Frame, console: &QueryConsole, area: Rect) {
+    let block = Block::default()
+        .title(" SQL (Enter run, Up/Down history, Esc close) ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+
+    let lines = input_lines(
+        &console.input,
+        console.error.as_ref(),
+        "SELECT ... FROM current | <buffer or file> [WHERE] [GROUP BY] [HAVING] [ORDER BY] [LIMIT]",
+    );
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Lines for a one-line text input: the input, then either a caret under the
+/// error position and the error message, or a hint
+fn input_lines<'a>(input: &'a str, error: Option<&ExprError>, hint: &'a str) -> Vec<Line<'a>> {
+    let mut lines = vec![Line::from(vec![
+        Span::styled("> ", Style::default().fg(Color::Magenta)),
+        Span::raw(input),
+        Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+    ])];
+    match error {
+        Some(error) => {
+            lines.push(Line::styled(
+                format!("  {}^", " ".repeat(error.pos)),
+                Style::default().fg(Color::Red).bold(),
+            ));
+            lines.push(Line::styled(
+                error.to_string(),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        None => lines.push(Line::styled(hint, Style::default().fg(Color::DarkGray))),
+    }
+    lines
+}
+
+/// Render the keys that can follow a prefix key in the bottom right corner
+/// of `area`
+fn render_which_key(frame: &mut Frame, prefix: char, area: Rect) {
+    let description = keymap::KEYMAP
+        .iter()
+        .find(|b| b.action == Action::Prefix(prefix))
+        .map_or("", |b| b.description);
+    let lines: Vec<Line> = keymap::bindings(KeyContext::Prefix(prefix))
+        .map(|binding| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {} ", keymap::keys_label(binding)),
+                    Style::default().fg(Color::Yellow).bold(),
+                ),
+                Span::raw(binding.description),
+            ])
+        })
+        .collect();
+    let width = area.width.min(48);
+    let height = area.height.min(lines.len() as u16 + 2);
+    let area = Rect {
+        x: area.right() - width,
+        y: area.bottom() - height,
+        width,
+        height,
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" {} - {} ", prefix, description))
+        .title_bottom(" any other key cancels ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta))
+        .style(Style::default().bg(Color::Black));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the key bindings of every context, scrolled by `offset` lines
+fn render_help(frame: &mut Frame, offset: &mut usize) {
+    let area = centered_rect(70, 80, frame.area());
+    frame.render_widget(Clear, area);
+
+    let mut lines = Vec::new();
+    for &context in keymap::HELP_CONTEXTS {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(
+            context.title(),
+            Style::default().fg(Color::Cyan).bold(),
+        )));
+        for binding in keymap::bindings(context) {
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {:<16}", keymap::keys_label(binding)),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::raw(binding.description),
+            ]));
+        }
+    }
+    let visible = area.height.saturating_sub(2) as usize;
+    *offset = (*offset).min(lines.len().saturating_sub(visible));
+
+    let hints: Vec<String> = keymap::bindings(KeyContext::Help)
+        .map(|b| format!("{} {}", keymap::keys_label(b), b.description))
+        .collect();
+    let block = Block::default()
+        .title(" Key bindings ")
+        .title_bottom(format!(" {} ", hints.join(", ")))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .scroll((*offset as u16, 0));
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the JSON path popup
+fn render_json_path_prompt(frame: &mut Frame, prompt: &JsonPathPrompt) {
+    let area = centered_rect(60, 100, frame.area());
+    let height = area.height.min(5);
+    let area = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" JSON path (e.g. .data.items[] or $.data.items[*]) ")
+        .title_bottom(" Enter apply, Tab use suggestion, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let hint = match &prompt.suggestion {
+        Some((path, len)) => format!("Suggested: {} ({} rows)", path, len),
+        None => "No array of objects found in this document".to_string(),
+    };
+    let lines = input_lines(&prompt.input, prompt.error.as_ref(), &hint);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the table view
+fn render_table_view(frame: &mut Frame, app: &mut App, area: Rect, block: Block) {
+    if app.record_view.is_some() && app.table_data.is_some() {
+        render_record_view(frame, app, area, block);
+        return;
+    }
+    if let Some(query) = &mut app.query {
+        let title = format!(
+            " {} ({} rows) ",
+            query.data.file_name,
+            query.data.rows.len()
+        );
+        render_table(
+            frame,
+            &query.data,
+            Slice::default(),
+            None,
+            &mut query.state,
+            area,
+            block.title(title),
+        );
+        return;
+    }
+
+    if let Some(data) = &app.table_data {
+        let area = if app.show_errors {
+            let schema_errors: usize = data.schema_errors.values().map(Vec::len).sum();
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(0),
+                    Constraint::Length(
+                        ((data.errors.len() + schema_errors) as u16).clamp(1, 8) + 2,
+                    ),
+                ])
+                .split(area);
+            let selected = app.table_state.selected().and_then(|i| app.base_row(i));
+            render_error_list(frame, data, selected, chunks[1], block.clone());
+            chunks[0]
+        } else {
+            area
+        };
+
+        // Pinned columns stay put; the rest start after the scrolled ones
+        let computed = app.view.computed(&data.headers);
+        let mut columns = app.view.visible_columns(&data.headers);
+        let pinned = app.view.pinned_count(&data.headers);
+        let scrolled = app
+            .column_offset
+            .min(columns.len().saturating_sub(pinned + 1));
+        columns.drain(pinned..pinned + scrolled);
+        let gutter = Gutter {
+            marks: &app.marks,
+            pending: app.visual_anchor.map(|anchor| {
+                let selected = app.table_state.selected().unwrap_or(anchor);
+                (anchor.min(selected), anchor.max(selected))
+            }),
+        };
+
+        match &mut app.pivot {
+            Some(pivot) => match pivot.drilled {
+                None => {
+                    let title = format!(
+                        " {} ({} groups) ",
+                        pivot.data.file_name,
+                        pivot.data.rows.len()
+                    );
+                    render_table(
+                        frame,
+                        &pivot.data,
+                        Slice::default(),
+                        None,
+                        &mut pivot.state,
+                        area,
+                        block.title(title),
+                    );
+                }
+                Some(group) => {
+                    let members = &pivot.groups[group];
+                    let filter: Vec<String> = pivot
+                        .spec
+                        .group_by
+                        .iter()
+                        .zip(&pivot.data.rows[group])
+                        .map(|(&col, value)| format!("{}={}", data.headers[col], value))
+                        .collect();
+                    let title = format!(
+                        " {} where {} ({} of {} rows) ",
+                        data.file_name,
+                        filter.join(", "),
+                        members.len(),
+                        data.rows.len()
+                    );
+                    let slice = Slice {
+                        rows: Some(members),
+                        columns: Some(&columns),
+                        pinned,
+                        computed: Some(&computed),
+                    };
+                    render_table(
+                        frame,
+                        data,
+                        slice,
+                        Some(gutter),
+                        &mut app.table_state,
+                        area,
+                        block.title(title),
+                    );
+                }
+            },
+            None => {
+                let mut labels: Vec<String> =
+                    app.row_filter.iter().map(|f| f.label.clone()).collect();
+                labels.extend(app.view.describe());
+                let hidden = computed.base + computed.names.len() - columns.len() - scrolled;
+                match hidden {
+                    0 => {}
+                    1 => labels.push("1 column hidden".to_string()),
+                    n => labels.push(format!("{} columns hidden", n)),
+                }
+                match scrolled {
+                    0 => {}
+                    1 => labels.push("1 column to the left".to_string()),
+                    n => labels.push(format!("{} columns to the left", n)),
+                }
+                let rows = app.view_rows.as_deref();
+                let title = if labels.is_empty() {
+                    table_title(data, app.csv_mode)
+                } else {
+                    format!(
+                        " {} - {} ({} of {} rows) ",
+                        data.file_name,
+                        labels.join(", "),
+                        rows.map_or(data.rows.len(), |r| r.len()),
+                        data.rows.len()
+                    )
+                };
+                let slice = Slice {
+                    rows,
+                    columns: Some(&columns),
+                    pinned,
+                    computed: Some(&computed),
+                };
+                let block = if app.marks.is_empty() {
+                    block
+                } else {
+                    block.title_bottom(format!(" {} marked: m actions, ]/[ jump ", app.marks.len()))
+                };
+                render_table(
+                    frame,
+                    data,
+                    slice,
+                    Some(gutter),
+                    &mut app.table_state,
+                    area,
+                    block.title(title),
+                );
+            }
+        }
+    } else {
+        let mut lines = vec![
+            Line::from("Select a data file or a database table to view its contents"),
+            Line::from(""),
+        ];
+        lines.extend(keymap::bindings(KeyContext::FileList).map(|binding| {
+            Line::from(format!(
+                "{:<12}{}",
+                keymap::keys_label(binding),
+                binding.description
+            ))
+        }));
+        lines.push(Line::from(""));
+        lines.push(Line::from("Press ? for every key binding"));
+        let paragraph = Paragraph::new(lines)
+            .block(block.title(" No Data "))
+            .style(Style::default().fg(Color::DarkGray))
+            .wrap(Wrap { trim: true });
+        frame.render_widget(paragraph, area);
+    }
+}
+
+/// Render the selected row as field, type and value lines, with the whole
+/// value of the selected field below
+fn render_record_view(frame: &mut Frame, app: &mut App, area: Rect, block: Block) {
+    let block = block.title_bottom(" j/k fields, J/K records, T or Esc table ");
+    let Some(record) = app.selected_record() else {
+        let paragraph = Paragraph::new("No row selected")
+            .block(block)
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(paragraph, area);
+        return;
+    };
+    let Some(state) = &mut app.record_view else {
+        return;
+    };
+    let field = state
+        .selected()
+        .unwrap_or(0)
+        .min(record.fields.len().saturating_sub(1));
+    state.select(Some(field));
+
+    let title = format!(
+        " {} - record {} of {} ",
+        record.file_name,
+        record.position + 1,
+        record.count
+    );
+    let block = block.title(title);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(5)])
+        .split(inner);
+
+    let name_width = record
+        .fields
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(8, 30) as u16;
+    let rows: Vec<Row> = record
+        .fields
+        .iter()
+        .map(|(name, value)| {
+            Row::new(vec![
+                Cell::from(name.clone()).style(Style::default().fg(Color::Yellow).bold()),
+                Cell::from(value_type(value)).style(Style::default().fg(Color::DarkGray)),
+                Cell::from(value.replace('\n', " ")),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(name_width),
+            Constraint::Length(9),
+            Constraint::Min(0),
+        ],
+    )
+    .highlight_style(
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD),
+    )
+    .highlight_symbol("> ");
+    frame.render_stateful_widget(table, chunks[0], state);
+
+    if let Some((name, value)) = record.fields.get(field) {
+        let detail = Paragraph::new(value.as_str())
+            .block(
+                Block::default()
+                    .title(format!(" {} ", name))
+                    .borders(Borders::TOP)
+                    .border_style(Style::default().fg(Color::DarkGray)),
+            )
+            .wrap(Wrap { trim: false });
+        frame.render_widget(detail, chunks[1]);
+    }
+}
+
+/// Kind of value a cell holds, shown next to it in the record view
+fn value_type(value: &str) -> &'static str {
+    let value = value.trim();
+    if value.is_empty() {
+        "empty"
+    } else if value == "true" || value == "false" {
+        "boolean"
+    } else if chart::parse_number(value).is_some() {
+        "number"
+    } else if chart::parse_timestamp(value).is_some() {
+        "timestamp"
+    } else {
+        match serde_json::from_str::<Value>(value) {
+            Ok(Value::Object(_)) => "object",
+            Ok(Value::Array(_)) => "array",
+            _ => "string",
+        }
+    }
+}
+
+/// Type shared by the non-empty values of a column, judged from the first
+/// [`TYPE_SAMPLE`] rows; "mixed" when they differ
+fn column_type(values: impl Iterator<Item = String>) -> &'static str {
+    let mut kind = "empty";
+    for value in values.take(TYPE_SAMPLE) {
+        match (kind, value_type(&value)) {
+            (_, "empty") => {}
+            ("empty", found) => kind = found,
+            (kind, found) if kind != found => return "mixed",
+            _ => {}
+        }
+    }
+    kind
+}
+
+/// Row marks to draw in the gutter of a table of loaded data
+struct Gutter<'a> {
+    /// Marked loaded data row indices
+    marks: &'a BTreeSet<usize>,
+    /// Display positions covered by an active visual selection
+    pending: Option<(usize, usize)>,
+}
+
+/// Rows and columns of a table to show, in display order; `None` shows all
+#[derive(Debug, Clone, Copy, Default)]
+struct Slice<'a> {
+    rows: Option<&'a [usize]>,
+    columns: Option<&'a [usize]>,
+    /// Number of leading columns that are pinned
+    pinned: usize,
+    /// Computed columns, numbered after the loaded ones in `columns`
+    computed: Option<&'a Computed>,
+}
+
+/// Render a table of loaded or derived data, optionally limited to some rows
+/// and columns
+fn render_table(
+    frame: &mut Frame,
+    data: &LoadedData,
+    slice: Slice,
+    gutter: Option<Gutter>,
+    state: &mut TableState,
+    area: Rect,
+    block: Block,
+) {
+    if data.headers.is_empty() {
+        let paragraph = Paragraph::new("No tabular data available")
+            .block(block)
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let all_columns: Vec<usize>;
+    let columns = match slice.columns {
+        Some(columns) => columns,
+        None => {
+            all_columns = (0..data.headers.len()).collect();
+            &all_columns
+        }
+    };
+    if columns.is_empty() {
+        let paragraph = Paragraph::new("All columns are hidden; press C to show some")
+            .block(block)
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    // Calculate column widths
+    let col_count = columns.len();
+    // Account for borders
+    let available_width = area.width.saturating_sub(2) as usize;
+    // Leave room for the "> " highlight, column spacing and the mark gutter
+    let reserved = 2 + col_count + if gutter.is_some() { 2 } else { 0 };
+    let col_width = (available_width.saturating_sub(reserved) / col_count).max(10);
+
+    let mut header_cells: Vec<Cell> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, &col)| {
+            let color = if i < slice.pinned {
+                Color::Cyan
+            } else {
+                Color::Yellow
+            };
+            let style = Style::default().fg(color).add_modifier(Modifier::BOLD);
+            match slice.computed.filter(|c| col >= c.base) {
+                Some(computed) => Cell::from(computed.header(&data.headers, col).to_string())
+                    .style(style.add_modifier(Modifier::ITALIC)),
+                None => Cell::from(data.headers[col].clone()).style(style),
+            }
+        })
+        .collect();
+    if gutter.is_some() {
+        header_cells.insert(0, Cell::from(""));
+    }
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let indices: Box<dyn Iterator<Item = usize>> = match slice.rows {
+        Some(indices) => Box::new(indices.iter().copied()),
+        None => Box::new(0..data.rows.len()),
+    };
+
+    let rows: Vec<Row> = indices
+        .enumerate()
+        .map(|(i, index)| {
+            let row = &data.rows[index];
+            let fix = data.row_fixes.get(&index);
+            let schema_errors = data.schema_errors.get(&index);
+            let mut cells: Vec<Cell> = columns
+                .iter()
+                .map(|&col| {
+                    if let Some(computed) = slice.computed.filter(|c| col >= c.base) {
+                        return Cell::from(computed.cell(row, col));
+                    }
+                    let value = row.get(col).cloned().unwrap_or_default();
+                    let offending = schema_errors.is_some_and(|errors| {
+                        errors
+                            .iter()
+                            .any(|e| e.column.as_ref() == Some(&data.headers[col]))
+                    });
+                    if offending {
+                        return Cell::from(value)
+                            .style(Style::default().fg(Color::White).bg(Color::Red));
+                    }
+                    match fix {
+                        Some(RowFix::Padded(missing)) if col + missing >= row.len() => {
+                            Cell::from("<missing>").style(
+                                Style::default()
+                                    .fg(Color::DarkGray)
+                                    .add_modifier(Modifier::ITALIC),
+                            )
+                        }
+                        Some(RowFix::Truncated(extra)) if col + 1 == row.len() => {
+                            Cell::from(Line::from(vec![
+                                Span::raw(value),
+                                Span::styled(
+                                    format!(" +{} dropped", extra.len()),
+                                    Style::default().fg(Color::Red),
+                                ),
+                            ]))
+                        }
+                        _ => Cell::from(value),
+                    }
+                })
+                .collect();
+            let mut style = if i % 2 == 0 {
+                Style::default()
+            } else {
+                Style::default().bg(Color::Rgb(30, 30, 30))
+            };
+            if schema_errors.is_some() {
+                style = style.fg(Color::Red);
+            }
+            if let Some(gutter) = &gutter {
+                let mark = if gutter.marks.contains(&index) {
+                    Cell::from("*").style(Style::default().fg(Color::Magenta).bold())
+                } else if schema_errors.is_some() {
+                    Cell::from("!").style(Style::default().fg(Color::Red).bold())
+                } else {
+                    Cell::from(" ")
+                };
+                cells.insert(0, mark);
+                if gutter
+                    .pending
+                    .is_some_and(|(start, end)| (start..=end).contains(&i))
+                {
+                    style = style.bg(Color::Rgb(40, 40, 90));
+                }
+            }
+            Row::new(cells).style(style)
+        })
+        .collect();
+
+    let mut widths: Vec<Constraint> = (0..col_count)
+        .map(|_| Constraint::Min(col_width as u16))
+        .collect();
+    if gutter.is_some() {
+        widths.insert(0, Constraint::Length(1));
+    }
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(table, area, state);
+}
+
+/// Build the table title with row and parse error totals
+fn table_title(data: &LoadedData, csv_mode: CsvMode) -> String {
+    let mut title = match &data.window {
+        Some(window) => format!(
+            " {} (rows {}-{} of {}",
+            data.file_name,
+            window.offset + 1,
+            window.offset + data.rows.len(),
+            window.total
+        ),
+        None => match &data.groups {
+            Some(groups) if groups.remaining() => format!(
+                " {} ({} of {} rows",
+                data.file_name,
+                data.rows.len(),
+                groups.total()
+            ),
+            _ => format!(" {} ({} rows", data.file_name, data.rows.len()),
+        },
+    };
+    if !data.errors.is_empty() {
+        title.push_str(&format!(", {} errors", data.errors.len()));
+    }
+    if let Some(path) = &data.json_path {
+        title.push_str(&format!(", path {}", path));
+    }
+    if let Some(sheet) = &data.sheet {
+        title.push_str(&format!(", sheet {}", sheet));
+    }
+    if data.schema.is_some() {
+        match data.schema_errors.len() {
+            0 => title.push_str(", all valid"),
+            n => title.push_str(&format!(", {} invalid", n)),
+        }
+    }
+    if data.modified {
+        title.push_str(", modified");
+    }
+    if data.compression != Compression::None {
+        title.push_str(&format!(", {}", data.compression.label()));
+    }
+    title.push_str(&format!(", {}", data.encoding.label()));
+    if data.file_type == "CSV" {
+        title.push_str(&format!(", {}", csv_mode.label()));
+    }
+    title.push_str(") ");
+    title
+}
+
+/// Render the list of malformed records found while parsing, followed by
+/// the schema violations from the selected row on
+fn render_error_list(
+    frame: &mut Frame,
+    data: &LoadedData,
+    selected: Option<usize>,
+    area: Rect,
+    block: Block,
+) {
+    let mut title = format!(" Parse Errors ({}) ", data.errors.len());
+    if data.schema.is_some() {
+        let count: usize = data.schema_errors.values().map(Vec::len).sum();
+        title = format!(
+            "{}- Schema Errors ({} in {} rows) ",
+            title,
+            count,
+            data.schema_errors.len()
+        );
+    }
+    let block = block.title(title);
+
+    if data.errors.is_empty() && data.schema_errors.is_empty() {
+        let message = if data.schema.is_some() {
+            "No malformed records, and every row matches the schema"
+        } else {
+            "No malformed records"
+        };
+        let paragraph = Paragraph::new(message)
+            .block(block)
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let mut items: Vec<ListItem> = data
+        .errors
+        .iter()
+        .map(|e| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("line {:<6} ", e.line),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(
+                    format!("byte {:<8} ", e.byte),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(format!("{:<15} ", e.kind), Style::default().fg(Color::Red)),
+                Span::raw(e.message.clone()),
+            ]))
+        })
+        .collect();
+
+    // Start at the selected row so its errors are in view, wrapping around
+    let start = selected.unwrap_or(0);
+    let schema_errors = data
+        .schema_errors
+        .range(start..)
+        .chain(data.schema_errors.range(..start));
+    for (row, errors) in schema_errors {
+        for e in errors {
+            let path = if e.instance_path.is_empty() {
+                "/"
+            } else {
+                e.instance_path.as_str()
+            };
+            items.push(ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("row {:<7} ", row + 1),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(
+                    format!("{:<18} ", path),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!("{:<15} ", e.keyword),
+                    Style::default().fg(Color::Red),
+                ),
+                Span::raw(e.message.clone()),
+            ])));
+        }
+    }
+
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// Render the raw view, reading only the lines on screen
+fn render_raw_view(frame: &mut Frame, app: &mut App, area: Rect, block: Block) {
+    let Some(data) = &mut app.table_data else {
+        let paragraph = Paragraph::new("No file loaded").block(block.title(" Raw Content "));
+        frame.render_widget(paragraph, area);
+        return;
+    };
+
+    let label = match (&data.window, &data.groups) {
+        (None, None) => "Raw Content",
+        _ => "Schema",
+    };
+    let height = area.height.saturating_sub(2) as usize;
+    let mut lines = data.raw.lines(app.raw_scroll, height);
+    // Scrolling past the last page is only noticed once the end is read
+    if let Some(count) = data.raw.line_count() {
+        let last_page = count.saturating_sub(height);
+        if app.raw_scroll > last_page {
+            app.raw_scroll = last_page;
+            lines = data.raw.lines(app.raw_scroll, height);
+        }
+    }
+
+    let (title, content) = match lines {
+        Ok(lines) if lines.is_empty() => (format!(" {} ", label), String::new()),
+        Ok(lines) => {
+            let range = format!("{}-{}", app.raw_scroll + 1, app.raw_scroll + lines.len());
+            let title = match data.raw.line_count() {
+                Some(count) => format!(" {} - lines {} of {} ", label, range, count),
+                None => format!(" {} - lines {} ", label, range),
+            };
+            (title, lines.join("\n"))
+        }
+        Err(e) => (
+            format!(" {} ", label),
+            format!("Failed to read {}: {}", data.file_name, e),
+        ),
+    };
+
+    let paragraph = Paragraph::new(content)
+        .block(block.title(title))
+        .style(Style::default())
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(paragraph, area);
+}
+
+/// Render the pivot setup popup listing columns with their pivot roles
+fn render_pivot_editor(frame: &mut Frame, editor: &mut PivotEditor, data: &LoadedData) {
+    let area = centered_rect(60, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Pivot: g group by, c/s/a/n/x/d count/sum/avg/min/max/distinct ")
+        .title_bottom(" Enter apply, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let items: Vec<ListItem> = data
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(col, header)| {
+            let group = match editor.spec.group_by.iter().position(|&c| c == col) {
+                Some(i) => Span::styled(
+                    format!("[G{}] ", i + 1),
+                    Style::default().fg(Color::Blue).bold(),
+                ),
+                None => Span::raw("     "),
+            };
+            let aggregates: Vec<&str> = editor
+                .spec
+                .aggregates
+                .iter()
+                .filter(|(_, c)| *c == col)
+                .map(|(agg, _)| agg.name())
+                .collect();
+            ListItem::new(Line::from(vec![
+                group,
+                Span::raw(format!("{:<24} ", header)),
+                Span::styled(aggregates.join(" "), Style::default().fg(Color::Green)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, area, &mut editor.state);
+}
+
+/// How long a status bar message stays up
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How close the selection gets to the last loaded row of a Parquet or
+/// Arrow file before its next row group is read
+const READ_AHEAD_ROWS: usize = 200;
+
+/// Rows looked at to tell the type of the current column
+const TYPE_SAMPLE: usize = 1000;
+
+/// Colors used for successive chart series
+const SERIES_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Magenta,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+];
+
+/// Render the chart tab: line/scatter plots of numeric columns or a bar chart
+/// of value counts
+fn render_chart_view(frame: &mut Frame, app: &App, area: Rect, block: Block) {
+    let Some((data, _)) = app.displayed_table() else {
+        let paragraph = Paragraph::new("No file loaded")
+            .block(block.title(" Chart "))
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(paragraph, area);
+        return;
+    };
+
+    let series: Vec<usize> = app
+        .chart
+        .series
+        .iter()
+        .copied()
+        .filter(|&c| c < data.headers.len())
+        .collect();
+    if series.is_empty() {
+        let paragraph = Paragraph::new(
+            "No columns selected\n\nPress c to pick series (y) and the x column (x)\nPress t to switch line/scatter/bar",
+        )
+        .block(block.title(" Chart "))
+        .style(Style::default().fg(Color::DarkGray))
+        .wrap(Wrap { trim: true });
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let rows = app.displayed_rows();
+    let what = match app.chart.kind {
+        ChartKind::Bar => format!("count by {}", data.headers[series[0]]),
+        kind => kind.label().to_string(),
+    };
+    let title = format!(
+        " Chart: {} [{}] (c columns, t type, +/- zoom, h/l pan, 0 reset) ",
+        data.file_name, what
+    );
+
+    if app.chart.kind == ChartKind::Bar {
+        let col = series[0];
+        let max_bars = (area.height.saturating_sub(2) as usize).max(1);
+        let counts = chart::value_counts(&rows, col, max_bars);
+        let bars: Vec<(&str, u64)> = counts.iter().map(|(v, n)| (v.as_str(), *n)).collect();
+        let bar_chart = BarChart::default()
+            .block(block.title(title))
+            .direction(Direction::Horizontal)
+            .bar_width(1)
+            .bar_gap(0)
+            .bar_style(Style::default().fg(Color::Cyan))
+            .value_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+            .data(bars.as_slice());
+        frame.render_widget(bar_chart, area);
+        return;
+    }
+
+    let x_col = app.chart.x.filter(|&c| c < data.headers.len());
+    let scale = chart::x_scale(&rows, x_col);
+    let window = app.chart.window;
+    let points: Vec<Vec<(f64, f64)>> = series
+        .iter()
+        .map(|&y| {
+            chart::series_points(&rows, x_col, scale, y)
+                .into_iter()
+                .filter(|(x, _)| window.is_none_or(|(lo, hi)| *x >= lo && *x <= hi))
+                .collect()
+        })
+        .collect();
+
+    let all = || points.iter().flatten();
+    let (Some(x_bounds), Some(y_bounds)) = (
+        window.or_else(|| chart::bounds(all().map(|p| p.0))),
+        chart::bounds(all().map(|p| p.1)),
+    ) else {
+        let paragraph = Paragraph::new("Selected columns have no numeric values in range")
+            .block(block.title(title))
+            .style(Style::default().fg(Color::DarkGray));
+        frame.render_widget(paragraph, area);
+        return;
+    };
+
+    let graph_type = match app.chart.kind {
+        ChartKind::Scatter => GraphType::Scatter,
+        _ => GraphType::Line,
+    };
+    let datasets: Vec<Dataset> = series
+        .iter()
+        .zip(&points)
+        .enumerate()
+        .map(|(i, (&col, points))| {
+            Dataset::default()
+                .name(data.headers[col].clone())
+                .marker(symbols::Marker::Braille)
+                .graph_type(graph_type)
+                .style(Style::default().fg(SERIES_COLORS[i % SERIES_COLORS.len()]))
+                .data(points)
+        })
+        .collect();
+
+    let x_title = match x_col {
+        Some(col) => data.headers[col].clone(),
+        None => "row".to_string(),
+    };
+    let chart = Chart::new(datasets)
+        .block(block.title(title))
+        .x_axis(
+            Axis::default()
+                .title(x_title)
+                .style(Style::default().fg(Color::Gray))
+                .bounds([x_bounds.0, x_bounds.1])
+                .labels(chart::axis_labels(x_bounds, scale)),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([y_bounds.0, y_bounds.1])
+                .labels(chart::axis_labels(y_bounds, XScale::Number)),
+        );
+    frame.render_widget(chart, area);
+}
+
+/// Render the chart column picker popup
+fn render_chart_picker(frame: &mut Frame, app: &mut App) {
+    let Some((data, _)) = app.displayed_table() else {
+        return;
+    };
+    let area = centered_rect(50, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Chart columns: y toggle series, x set x axis ")
+        .title_bottom(" Enter close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let items: Vec<ListItem> = data
+        .headers
+        .iter()
+        .enumerate()
+        .map(|(col, header)| {
+            let role = if app.chart.x == Some(col) {
+                Span::styled("[x] ", Style::default().fg(Color::Blue).bold())
+            } else if let Some(i) = app.chart.series.iter().position(|&c| c == col) {
+                Span::styled(
+                    "[y] ",
+                    Style::default().fg(SERIES_COLORS[i % SERIES_COLORS.len()]),
+                )
+            } else {
+                Span::raw("    ")
+            };
+            ListItem::new(Line::from(vec![role, Span::raw(header.clone())]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    if let Some(state) = &mut app.chart_picker {
+        frame.render_stateful_widget(list, area, state);
+    }
+}
+
+/// Render the row filter popup
+fn render_filter_prompt(frame: &mut Frame, prompt: &FilterPrompt) {
+    let area = centered_rect(60, 100, frame.area());
+    let height = area.height.min(5);
+    let area = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Filter rows (e.g. status >= 500 and host like 'web%') ")
+        .title_bottom(" Enter apply, empty to clear, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let lines = input_lines(
+        &prompt.input,
+        prompt.error.as_ref(),
+        "Columns by name (`odd name` in backticks), strings in quotes",
+    );
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the computed column popup
+fn render_computed_prompt(frame: &mut Frame, prompt: &FilterPrompt) {
+    let area = centered_rect(60, 100, frame.area());
+    let height = area.height.min(5);
+    let area = Rect {
+        y: area.y + (area.height - height) / 2,
+        height,
+        ..area
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Add column (e.g. bytes / 1024 as kb) ")
+        .title_bottom(" Enter add, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let lines = input_lines(
+        &prompt.input,
+        prompt.error.as_ref(),
+        "concat(first, ' ', last), parse_ts(created_at).hour, ...",
+    );
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Render the column sort and visibility popup
+fn render_column_picker(frame: &mut Frame, app: &mut App) {
+    let Some(data) = &app.table_data else {
+        return;
+    };
+    let area = centered_rect(50, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Columns: Space show/hide, s sort, p pin, J/K move ")
+        .title_bottom(" a add computed, x delete it, r reset, Enter close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let all_headers = app.view.all_headers(&data.headers);
+    let items: Vec<ListItem> = app
+        .view
+        .ordered_columns(&data.headers)
+        .into_iter()
+        .map(|col| {
+            let header = &all_headers[col];
+            let hidden = app.view.hidden_columns.contains(header);
+            let visibility = if hidden { "[ ] " } else { "[x] " };
+            let mut spans = vec![Span::raw(visibility), Span::raw(header.clone())];
+            if let Some(sort) = app.view.sort.as_ref().filter(|s| s.column == *header) {
+                let arrow = if sort.descending { " ↓" } else { " ↑" };
+                spans.push(Span::styled(
+                    arrow,
+                    Style::default().fg(Color::Yellow).bold(),
+                ));
+            }
+            if app.view.pinned_columns.contains(header) {
+                spans.push(Span::styled(" pinned", Style::default().fg(Color::Cyan)));
+            }
+            if let Some(computed) = col
+                .checked_sub(data.headers.len())
+                .map(|i| &app.view.computed_columns[i])
+                .filter(|c| c.expr != c.name)
+            {
+                spans.push(Span::styled(
+                    format!(" = {}", computed.expr),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            let style = if hidden {
+                Style::default().fg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(spans)).style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    if let Some(state) = &mut app.column_picker {
+        frame.render_stateful_widget(list, area, state);
+    }
+}
+
+/// Render the sheet picker popup, marking the sheet on display
+fn render_sheet_picker(frame: &mut Frame, picker: &mut SheetPicker) {
+    let area = centered_rect(40, 50, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Sheets ")
+        .title_bottom(" Enter show, Esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let items: Vec<ListItem> = picker
+        .names
+        .iter()
+        .map(|name| {
+            if picker.current.as_ref() == Some(name) {
+                ListItem::new(format!("{} (shown)", name)).style(Style::default().fg(Color::Yellow))
+            } else {
+                ListItem::new(name.as_str())
+            }
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, area, &mut picker.state);
+}
+
+/// Render the recently opened files popup
+fn render_recent_picker(frame: &mut Frame, app: &mut App) {
+    let area = centered_rect(60, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Recent files ")
+        .title_bottom(" Enter open, d forget, Esc close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let items: Vec<ListItem> = app
+        .session
+        .recent_files
+        .iter()
+        .map(|file| {
+            let style = if file_exists(file) {
+                Style::default()
+            } else {
+                Style::default()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::CROSSED_OUT)
+            };
+            ListItem::new(file.display().to_string()).style(style)
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    if let Some(state) = &mut app.recent_picker {
+        frame.render_stateful_widget(list, area, state);
+    }
+}
+
+/// Render the open buffers popup
+fn render_buffer_picker(frame: &mut Frame, app: &mut App) {
+    let area = centered_rect(60, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let buffers = app.buffer_list();
+    let total: usize = buffers.iter().map(|(_, data)| data.memory_usage()).sum();
+    let block = Block::default()
+        .title(format!(
+            " Buffers ({} in memory) ",
+            compression::format_size(total as u64)
+        ))
+        .title_bottom(" Enter switch, d close, Esc cancel ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let dim = Style::default().fg(Color::DarkGray);
+    let items: Vec<ListItem> = buffers
+        .iter()
+        .enumerate()
+        .map(|(i, (file, data))| {
+            let mut spans = vec![
+                Span::raw(format!("{}:{}", i + 1, data.file_name)),
+                Span::styled(
+                    format!(
+                        "  {} rows, {}",
+                        data.rows.len(),
+                        compression::format_size(data.memory_usage() as u64)
+                    ),
+                    dim,
+                ),
+            ];
+            if data.modified {
+                spans.push(Span::styled(
+                    ", modified",
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            spans.push(Span::styled(format!("  {}", file.display()), dim));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
         .highlight_style(
             Style::default()
                 .bg(Color::DarkGray)
@@ -550,306 +6184,1469 @@ fn render_file_list(frame: &mut Frame, app: &mut App, area: Rect) {
         )
         .highlight_symbol("> ");
 
-    frame.render_stateful_widget(list, area, &mut app.file_list_state);
+    if let Some(state) = &mut app.buffer_picker {
+        frame.render_stateful_widget(list, area, state);
+    }
+}
+
+/// Render the join wizard: the second file, both key columns, then the kind
+fn render_join_wizard(frame: &mut Frame, app: &mut App) {
+    let (Some(wizard), Some(data)) = (&mut app.join_wizard, &app.table_data) else {
+        return;
+    };
+    let area = centered_rect(60, 60, frame.area());
+    frame.render_widget(Clear, area);
+
+    let dim = Style::default().fg(Color::DarkGray);
+    // Key columns are listed with their first value as a hint
+    let key_items = |data: &LoadedData, highlight: Option<&String>| -> Vec<ListItem> {
+        data.headers
+            .iter()
+            .enumerate()
+            .map(|(col, header)| {
+                let sample = data.rows.first().and_then(|r| r.get(col));
+                let name_style = if Some(header) == highlight {
+                    Style::default().fg(Color::Green)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:<24} ", header), name_style),
+                    Span::styled(sample.cloned().unwrap_or_default(), dim),
+                ]))
+            })
+            .collect()
+    };
+
+    let other = wizard.other.as_ref().map(|(_, data)| data);
+    let (title, items) = match (wizard.step, other) {
+        (JoinStep::LeftKey, Some(_)) => (
+            format!(" Join: key column of {} ", data.file_name),
+            key_items(data, None),
+        ),
+        (JoinStep::RightKey, Some(other)) => (
+            format!(" Join: key column of {} ", other.file_name),
+            key_items(other, data.headers.get(wizard.left_key)),
+        ),
+        (JoinStep::Kind, Some(other)) => (
+            format!(
+                " Join on {} = {} ",
+                data.headers[wizard.left_key], other.headers[wizard.right_key]
+            ),
+            JoinKind::ALL
+                .iter()
+                .map(|kind| {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{:<8}", kind.label())),
+                        Span::styled(kind.description(), dim),
+                    ]))
+                })
+                .collect(),
+        ),
+        _ => {
+            let open: Vec<&Path> = app
+                .buffers
+                .iter()
+                .flatten()
+                .map(|b| b.file.as_path())
+                .collect();
+            (
+                format!(" Join {} with ", data.file_name),
+                wizard
+                    .files
+                    .iter()
+                    .map(|file| {
+                        let name = file.file_name().unwrap_or_default().to_string_lossy();
+                        let mut spans = vec![Span::raw(name.to_string())];
+                        if open.contains(&file.as_path()) {
+                            spans.push(Span::styled("  open", dim));
+                        }
+                        ListItem::new(Line::from(spans))
+                    })
+                    .collect(),
+            )
+        }
+    };
+    let hint = match wizard.step {
+        JoinStep::File => " Enter next, Esc cancel ",
+        JoinStep::Kind => " Enter join, Esc back ",
+        _ => " Enter next, Esc back ",
+    };
+
+    let block = Block::default()
+        .title(title)
+        .title_bottom(hint)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, area, &mut wizard.state);
+}
+
+/// Render a modal dialog
+fn render_modal(frame: &mut Frame, modal: &ModalDialog) {
+    let area = centered_rect(50, 30, frame.area());
+
+    // Clear the area behind the modal
+    frame.render_widget(Clear, area);
+
+    let color = if modal.error { Color::Red } else { Color::Cyan };
+    let block = Block::default()
+        .title(format!(" {} ", modal.title))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color))
+        .style(Style::default().bg(Color::Black));
+
+    let text = format!("{}\n\nPress Enter or Esc to close", modal.message);
+    let paragraph = Paragraph::new(text)
+        .block(block)
+        .style(Style::default().fg(Color::White))
+        .wrap(Wrap { trim: true });
+
+    frame.render_widget(paragraph, area);
+}
+
+/// Setup the terminal
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode().context("Failed to enable raw mode")?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
+        .context("Failed to enter alternate screen")?;
+    let backend = CrosstermBackend::new(stdout);
+    let terminal = Terminal::new(backend).context("Failed to create terminal")?;
+    Ok(terminal)
+}
+
+/// Restore the terminal to its original state
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    disable_raw_mode().context("Failed to disable raw mode")?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )
+    .context("Failed to leave alternate screen")?;
+    terminal.show_cursor().context("Failed to show cursor")?;
+    Ok(())
 }
 
-/// Render the data view panel with tabs
-fn render_data_view(frame: &mut Frame, app: &mut App, area: Rect) {
-    let is_active = app.active_panel == ActivePanel::DataView;
-    let border_style = if is_active {
-        Style::default().fg(Color::Cyan)
-    } else {
-        Style::default().fg(Color::DarkGray)
+/// Run the application main loop
+fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
+    loop {
+        terminal.draw(|f| ui(f, app))?;
+
+        // Wake up periodically so background work (file indexing) shows up
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    if key.modifiers.contains(KeyModifiers::CONTROL) {
+                        app.handle_ctrl_key(key.code);
+                    } else {
+                        app.handle_key(key.code);
+                    }
+                }
+            }
+        }
+        app.tick();
+
+        if app.should_quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Load the first page of a table or view of a SQLite database, with its
+/// schema as the text of the raw tab
+fn load_table(db: &Path, name: String) -> Result<LoadedData> {
+    let kind = sqlite::object_kind(db, &name)?
+        .with_context(|| format!("{} has no table or view named {}", db.display(), name))?;
+    let total = sqlite::count(db, &name)?;
+    let page = sqlite::read_page(db, &name, 0, PAGE_ROWS)?;
+    let schema = sqlite::describe(db, &name)?;
+    Ok(LoadedData {
+        file_name: name.clone(),
+        file_type: kind.label().to_string(),
+        compression: Compression::None,
+        decoded_size: std::fs::metadata(db).map_or(0, |m| m.len()),
+        raw: RawText::Text(schema),
+        headers: page.headers,
+        rows: page.rows,
+        encoding: EncodingInfo::utf8(),
+        errors: Vec::new(),
+        row_fixes: BTreeMap::new(),
+        json_path: None,
+        sheet: None,
+        schema: None,
+        schema_errors: BTreeMap::new(),
+        modified: false,
+        window: Some(TableWindow {
+            db: db.to_path_buf(),
+            name,
+            kind,
+            offset: 0,
+            total,
+        }),
+        groups: None,
+    })
+}
+
+/// Table of a workbook sheet, with its cells as the text of the raw tab
+fn load_sheet(path: &Path, sheet: Sheet) -> LoadedData {
+    LoadedData {
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+        file_type: spreadsheet::format_label(path).to_string(),
+        compression: Compression::None,
+        decoded_size: std::fs::metadata(path).map_or(0, |m| m.len()),
+        raw: RawText::Text(sheet.raw),
+        headers: sheet.headers,
+        rows: sheet.rows,
+        encoding: EncodingInfo::utf8(),
+        errors: Vec::new(),
+        row_fixes: BTreeMap::new(),
+        json_path: None,
+        sheet: Some(sheet.name),
+        schema: None,
+        schema_errors: BTreeMap::new(),
+        modified: false,
+        window: None,
+        groups: None,
+    }
+}
+
+/// Whether a recent file (or database table) can still be opened
+fn file_exists(path: &Path) -> bool {
+    path.is_file() || sqlite::split_object_path(path).is_some()
+}
+
+/// Rows drawn per off-screen buffer when printing a table
+const PRINT_CHUNK_ROWS: usize = 1000;
+
+/// Load a file and render it as `--print` asks, without a terminal
+fn print_output(options: &PrintOptions) -> Result<Vec<u8>> {
+    let dir = options
+        .file
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let mut data = App::with_dir(dir).load_data(&options.file)?;
+    // Database tables, Parquet and Arrow files are printed whole, not just
+    // their first page or row group
+    data.read_all()?;
+
+    let columns: Vec<usize> = match &options.columns {
+        Some(names) => names
+            .iter()
+            .map(|name| {
+                data.headers
+                    .iter()
+                    .position(|h| h == name)
+                    .with_context(|| {
+                        format!(
+                            "Unknown column '{}' (columns: {})",
+                            name,
+                            data.headers.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<_>>()?,
+        None => (0..data.headers.len()).collect(),
     };
+    let view = ViewSettings {
+        filter: options.filter.clone(),
+        ..ViewSettings::default()
+    };
+    let mut rows = view
+        .rows(&data.headers, &data.rows, None)
+        .map_err(|e| anyhow::anyhow!("Invalid filter: {}", e))?;
+    rows.truncate(options.limit.unwrap_or(usize::MAX));
 
-    // Split area for tabs and content
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Min(0)])
-        .split(area);
+    let mut out = Vec::new();
+    let format = options.format;
+    if matches!(format, Format::Table | Format::Plain) {
+        let width = options
+            .width
+            .or_else(|| crossterm::terminal::size().ok().map(|(w, _)| w))
+            .unwrap_or(100);
+        // Lines drawn above and below the rows: the border and title, the
+        // header and its margin
+        let (block, above, below) = match format {
+            Format::Table => {
+                let title = format!(
+                    " {} ({} of {} rows) ",
+                    data.file_name,
+                    rows.len(),
+                    data.rows.len()
+                );
+                (Block::default().borders(Borders::ALL).title(title), 3, 1)
+            }
+            _ => (Block::default(), 2, 0),
+        };
+        // Column widths only depend on the width, so the rows are drawn a
+        // chunk at a time and the repeated header and borders are dropped
+        let mut start = 0;
+        loop {
+            let end = (start + PRINT_CHUNK_ROWS).min(rows.len());
+            let height = (above + end - start + below) as u16;
+            let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+            let slice = Slice {
+                rows: Some(&rows[start..end]),
+                columns: Some(&columns),
+                ..Slice::default()
+            };
+            terminal.draw(|frame| {
+                let area = frame.area();
+                render_table(
+                    frame,
+                    &data,
+                    slice,
+                    None,
+                    &mut TableState::default(),
+                    area,
+                    block.clone(),
+                );
+            })?;
+            let lines = print::buffer_lines(terminal.backend().buffer());
+            let first = if start == 0 { 0 } else { above };
+            let last = if end == rows.len() {
+                lines.len()
+            } else {
+                lines.len() - below
+            };
+            for line in &lines[first..last] {
+                writeln!(out, "{}", line)?;
+            }
+            if end == rows.len() {
+                break;
+            }
+            start = end;
+        }
+        return Ok(out);
+    }
 
-    // Render tabs
-    let titles = vec!["Table", "Raw"];
-    let tabs = Tabs::new(titles)
-        .block(
-            Block::default()
-                .title(" Data View ")
-                .borders(Borders::ALL)
-                .border_style(border_style),
-        )
-        .select(app.active_tab.index())
-        .style(Style::default().fg(Color::White))
-        .highlight_style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
+    let headers: Vec<String> = columns.iter().map(|&c| data.headers[c].clone()).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|&r| {
+            let row = &data.rows[r];
+            columns
+                .iter()
+                .map(|&c| row.get(c).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    match format {
+        Format::Csv => print::write_csv(&mut out, &headers, &rows)?,
+        Format::Json => print::write_json(&mut out, &headers, &rows)?,
+        _ => print::write_markdown(&mut out, &headers, &rows)?,
+    }
+    Ok(out)
+}
+
+fn main() -> Result<()> {
+    match print::parse_args(std::env::args().skip(1))? {
+        Command::Help => {
+            print!("{}", print::USAGE);
+            return Ok(());
+        }
+        Command::Print(options) => {
+            let output = print_output(&options)?;
+            return io::stdout()
+                .write_all(&output)
+                .context("Failed to write output");
+        }
+        Command::Interactive => {}
+    }
+
+    // Setup terminal
+    let mut terminal = setup_terminal()?;
+
+    // Create app state
+    let mut app = App::new()?;
+
+    // Run the app
+    let result = run_app(&mut terminal, &mut app);
+    let saved = app.save_session();
+
+    // Restore terminal
+    restore_terminal(&mut terminal)?;
+
+    // Handle any errors from run_app, then from saving the session
+    result?;
+    saved.context("Failed to save session")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn test_app() -> App {
+        App::with_dir(PathBuf::from("/tmp"))
+    }
+
+    fn test_data(headers: &[&str], rows: &[&[&str]]) -> LoadedData {
+        LoadedData {
+            file_name: "test.csv".to_string(),
+            file_type: "CSV".to_string(),
+            compression: Compression::None,
+            decoded_size: 0,
+            raw: RawText::Text(String::new()),
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: rows
+                .iter()
+                .map(|r| r.iter().map(|c| c.to_string()).collect())
+                .collect(),
+            encoding: encoding::to_utf8(b"", None).1,
+            errors: Vec::new(),
+            row_fixes: BTreeMap::new(),
+            json_path: None,
+            sheet: None,
+            schema: None,
+            schema_errors: BTreeMap::new(),
+            modified: false,
+            window: None,
+            groups: None,
+        }
+    }
+
+    #[test]
+    fn test_active_tab_index() {
+        assert_eq!(ActiveTab::Table.index(), 0);
+        assert_eq!(ActiveTab::Raw.index(), 1);
+        assert_eq!(ActiveTab::Chart.index(), 2);
+    }
+
+    #[test]
+    fn test_active_tab_next() {
+        assert_eq!(ActiveTab::Table.next(), ActiveTab::Raw);
+        assert_eq!(ActiveTab::Raw.next(), ActiveTab::Chart);
+        assert_eq!(ActiveTab::Chart.next(), ActiveTab::Table);
+    }
+
+    #[test]
+    fn test_json_to_table_array_of_objects() {
+        let app = test_app();
+
+        let json = serde_json::json!([
+            {"name": "Alice", "age": 30},
+            {"name": "Bob", "age": 25}
+        ]);
+
+        let (headers, rows) = app.json_to_table(&json);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn test_json_to_table_object() {
+        let app = test_app();
+
+        let json = serde_json::json!({"key1": "value1", "key2": "value2"});
+
+        let (headers, rows) = app.json_to_table(&json);
+        assert_eq!(headers, vec!["Key", "Value"]);
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[test]
+    fn test_value_to_string() {
+        let app = test_app();
+
+        assert_eq!(app.value_to_string(None), "");
+        assert_eq!(app.value_to_string(Some(&Value::Null)), "null");
+        assert_eq!(app.value_to_string(Some(&Value::Bool(true))), "true");
+        assert_eq!(app.value_to_string(Some(&serde_json::json!(42))), "42");
+        assert_eq!(app.value_to_string(Some(&Value::String("hello".to_string()))), "hello");
+    }
+
+    #[test]
+    fn test_parse_csv_strict_skips_ragged_rows() {
+        let csv = b"a,b,c\n1,2,3\n4,5\n6,7,8,9\n";
+        let (headers, rows, errors, fixes) = parse_csv(&csv[..], CsvMode::Strict).unwrap();
+
+        assert_eq!(headers, vec!["a", "b", "c"]);
+        assert_eq!(rows, vec![vec!["1", "2", "3"]]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(errors[0].byte, 12);
+        assert_eq!(errors[0].kind, "UnequalLengths");
+        assert!(fixes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_csv_lenient_pads_and_truncates() {
+        let csv = b"a,b,c\n4,5\n6,7,8,9\n";
+        let (_, rows, errors, fixes) = parse_csv(&csv[..], CsvMode::Lenient).unwrap();
+
+        assert_eq!(rows, vec![vec!["4", "5", ""], vec!["6", "7", "8"]]);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(fixes.get(&0), Some(&RowFix::Padded(1)));
+        assert_eq!(
+            fixes.get(&1),
+            Some(&RowFix::Truncated(vec!["9".to_string()]))
         );
+    }
 
-    frame.render_widget(tabs, chunks[0]);
+    #[test]
+    fn test_parse_csv_invalid_utf8() {
+        let csv = b"a,b\n1,\xff\n2,3\n";
 
-    // Render content based on active tab
-    let content_block = Block::default()
-        .borders(Borders::ALL)
-        .border_style(border_style);
+        let (_, rows, errors, _) = parse_csv(&csv[..], CsvMode::Strict).unwrap();
+        assert_eq!(rows, vec![vec!["2", "3"]]);
+        assert_eq!(errors[0].kind, "Utf8");
+
+        let (_, rows, _, _) = parse_csv(&csv[..], CsvMode::Lenient).unwrap();
+        assert_eq!(rows[0], vec!["1", "\u{fffd}"]);
+    }
+
+    #[test]
+    fn test_load_compressed_csv() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_gz_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write_gz = |name: &str, content: &[u8]| {
+            let path = dir.join(name);
+            let mut encoder =
+                flate2::write::GzEncoder::new(fs::File::create(&path).unwrap(), Default::default());
+            encoder.write_all(content).unwrap();
+            encoder.finish().unwrap();
+            path
+        };
+        let app = test_app();
+
+        // UTF-8 is parsed as it is decompressed, and paged from the file
+        let content = b"\xEF\xBB\xBFa,b\n1,2\n3,4\n";
+        let data = app.load_data(&write_gz("events.csv.gz", content)).unwrap();
+        assert_eq!(data.headers, ["a", "b"]);
+        assert_eq!(data.rows, [["1", "2"], ["3", "4"]]);
+        assert_eq!(data.decoded_size, content.len() as u64);
+        assert_eq!(data.compression, Compression::Gzip);
+        assert!(data.encoding.bom);
+        assert!(matches!(data.raw, RawText::File(_)));
+
+        // Other encodings are decoded whole first
+        let data = app
+            .load_data(&write_gz("latin.csv.gz", b"name\ncaf\xe9\n"))
+            .unwrap();
+        assert_eq!(data.rows, [["caf\u{e9}"]]);
+        assert_eq!(data.decoded_size, 10);
+        assert!(matches!(data.raw, RawText::Text(_)));
+
+        // Latin-1 that only shows up past the sniffed start is decoded whole too
+        let mut content = b"name\n".to_vec();
+        for _ in 0..20_000 {
+            content.extend_from_slice(b"cafe\n");
+        }
+        content.extend_from_slice(b"caf\xe9\n");
+        let data = app.load_data(&write_gz("late.csv.gz", &content)).unwrap();
+        assert_eq!(data.rows.len(), 20_001);
+        assert_eq!(data.rows[20_000], ["caf\u{e9}"]);
+        assert!(data.errors.is_empty());
+        assert_ne!(data.encoding.encoding, UTF_8);
+        assert!(!data.encoding.lossy);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pivot_drill_down_and_back() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["host", "status"],
+            &[&["a", "200"], &["b", "500"], &["a", "500"]],
+        ));
+        app.table_state.select(Some(2));
+
+        app.apply_pivot(PivotSpec {
+            group_by: vec![0],
+            aggregates: vec![],
+        });
+        assert_eq!(app.view_len(), 2);
+
+        app.drill_down();
+        assert_eq!(app.pivot.as_ref().unwrap().drilled, Some(0));
+        assert_eq!(app.view_len(), 2);
+
+        app.pivot_back();
+        assert!(app.pivot.is_some());
+        app.pivot_back();
+        assert!(app.pivot.is_none());
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.view_len(), 3);
+    }
+
+    #[test]
+    fn test_query_console_runs_and_records_history() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["host", "status"],
+            &[&["a", "200"], &["b", "500"], &["a", "503"]],
+        ));
+
+        app.handle_key(KeyCode::Char('Q'));
+        for c in "SELECT host, count(*) FROM current WHERE status >= 500 GROUP BY host".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Enter);
+        let query = app.query.as_ref().unwrap();
+        assert_eq!(query.data.headers, vec!["host", "count(*)"]);
+        assert_eq!(query.data.rows, vec![vec!["b", "1"], vec!["a", "1"]]);
+        assert_eq!(app.view_len(), 2);
+
+        app.query_console.as_mut().unwrap().input = "SELECT nope FROM current".to_string();
+        app.handle_key(KeyCode::Enter);
+        let console = app.query_console.as_ref().unwrap();
+        assert_eq!(console.error.as_ref().map(|e| e.pos), Some(7));
+        assert_eq!(app.query_history.len(), 2);
+
+        app.handle_key(KeyCode::Up);
+        app.handle_key(KeyCode::Up);
+        assert!(app
+            .query_console
+            .as_ref()
+            .unwrap()
+            .input
+            .starts_with("SELECT host"));
+
+        app.handle_key(KeyCode::Esc);
+        app.handle_key(KeyCode::Backspace);
+        assert!(app.query.is_none());
+        assert_eq!(app.view_len(), 3);
+    }
+
+    #[test]
+    fn test_json_path_prompt_applies_suggestion() {
+        let mut app = test_app();
+        let mut data = test_data(&["Key", "Value"], &[&["data", "{1 keys}"]]);
+        data.file_type = "JSON".to_string();
+        data.raw = RawText::Text(r#"{"data": {"items": [{"id": 1}, {"id": 2}]}}"#.to_string());
+        app.table_data = Some(data);
+        app.selected_file = Some(PathBuf::from("/tmp/api.json"));
+
+        app.handle_key(KeyCode::Char('.'));
+        app.handle_key(KeyCode::Char('x'));
+        app.handle_key(KeyCode::Enter);
+        let prompt = app.json_path_prompt.as_ref().unwrap();
+        assert_eq!(prompt.error.as_ref().map(|e| e.pos), Some(0));
+
+        app.handle_key(KeyCode::Tab);
+        app.handle_key(KeyCode::Enter);
+        assert!(app.json_path_prompt.is_none());
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, vec!["id"]);
+        assert_eq!(data.rows, vec![vec!["1"], vec!["2"]]);
+        assert_eq!(
+            app.json_paths
+                .get(Path::new("/tmp/api.json"))
+                .map(String::as_str),
+            Some(".data.items[]")
+        );
+    }
+
+    #[test]
+    fn test_marks_visual_range_and_jump() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["n"],
+            &[&["0"], &["1"], &["2"], &["3"], &["4"], &["5"]],
+        ));
+        app.table_state.select(Some(0));
+        app.active_panel = ActivePanel::DataView;
+
+        app.handle_key(KeyCode::Char(' '));
+        assert_eq!(app.table_state.selected(), Some(1));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('V'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('V'));
+        assert_eq!(app.marks.iter().copied().collect::<Vec<_>>(), vec![0, 3, 4]);
+
+        app.handle_key(KeyCode::Char('['));
+        assert_eq!(app.table_state.selected(), Some(3));
+        app.handle_key(KeyCode::Char('['));
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.handle_key(KeyCode::Char(']'));
+        assert_eq!(app.table_state.selected(), Some(3));
+
+        app.handle_key(KeyCode::Char('V'));
+        app.handle_key(KeyCode::Esc);
+        assert!(app.visual_anchor.is_none());
+        assert!(!app.should_quit);
+    }
+
+    #[test]
+    fn test_filter_and_delete_marked_rows() {
+        let mut app = test_app();
+        let mut data = test_data(&["n"], &[&["0"], &["1"], &["2"], &["3"]]);
+        data.row_fixes.insert(3, RowFix::Padded(1));
+        app.table_data = Some(data);
+        app.marks.extend([1, 2]);
+        app.active_panel = ActivePanel::DataView;
+
+        app.handle_key(KeyCode::Char('m'));
+        app.handle_key(KeyCode::Char('f'));
+        assert_eq!(app.view_len(), 2);
+        assert_eq!(app.displayed_rows(), vec![&vec!["1"], &vec!["2"]]);
+        assert_eq!(app.marked_rows_text(false), "n\n1\n2");
+
+        app.handle_key(KeyCode::Backspace);
+        assert_eq!(app.view_len(), 4);
+        assert_eq!(app.table_state.selected(), Some(1));
+
+        // Deleting needs editing mode
+        app.handle_key(KeyCode::Char('m'));
+        app.handle_key(KeyCode::Char('d'));
+        assert_eq!(app.table_data.as_ref().unwrap().rows.len(), 4);
+        assert_eq!(app.marks.len(), 2);
+        app.handle_key(KeyCode::Char('i'));
+        assert!(app.editing);
+        app.handle_key(KeyCode::Char('m'));
+        app.handle_key(KeyCode::Char('d'));
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.rows, vec![vec!["0"], vec!["3"]]);
+        assert_eq!(data.row_fixes.get(&1), Some(&RowFix::Padded(1)));
+        assert!(data.modified);
+        assert!(app.marks.is_empty());
+    }
+
+    #[test]
+    fn test_export_marked_rows() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_marks_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut app = test_app();
+        app.table_data = Some(test_data(&["a", "b"], &[&["1", "x,y"], &["2", "z"]]));
+        app.selected_file = Some(dir.join("test.csv"));
+        app.marks.insert(0);
+
+        let path = app.export_marked(false).unwrap();
+        assert_eq!(path, dir.join("test-marked.csv"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a,b\n1,\"x,y\"\n");
+        assert_eq!(
+            app.export_marked(false).unwrap(),
+            dir.join("test-marked-1.csv")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_status_bar() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_status_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut app = test_app();
+        let text = |app: &App| -> Vec<String> {
+            app.status_parts()
+                .iter()
+                .map(|part| part.content.to_string())
+                .collect()
+        };
+        assert_eq!(text(&app), ["No file loaded"]);
+
+        app.table_data = Some(test_data(
+            &["host", "bytes"],
+            &[&["web1", "10"], &["db1", ""], &["web2", "2.5"]],
+        ));
+        app.selected_file = Some(dir.join("hosts.csv"));
+        app.table_state.select(Some(1));
+        assert_eq!(text(&app), ["Row 2 of 3", "host: string", "CSV, UTF-8, 0B"]);
+
+        app.view.filter = Some("host != 'db1'".to_string());
+        app.refresh_view_rows();
+        app.scroll_columns(1);
+        app.table_data.as_mut().unwrap().modified = true;
+        assert_eq!(
+            text(&app),
+            [
+                "Row 1 of 2 (3 total)",
+                "bytes: number",
+                "CSV, UTF-8, 0B",
+                "where host != 'db1'",
+                "modified"
+            ]
+        );
+
+        // Confirmations go to the status bar and time out
+        app.marks.insert(0);
+        app.handle_key(KeyCode::Char('m'));
+        app.handle_key(KeyCode::Char('e'));
+        assert!(app.modal.is_none());
+        let (message, _) = app.status_message.clone().unwrap();
+        assert!(message.starts_with("Wrote 1 rows"));
+        app.tick();
+        assert!(app.status_message.is_some());
+        app.status_message = Some((message, Instant::now() - STATUS_TIMEOUT));
+        app.tick();
+        assert!(app.status_message.is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_help_and_which_key() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(&["a"], &[&["1"], &["2"]]));
+        app.table_state.select(Some(0));
+        app.active_panel = ActivePanel::DataView;
+
+        // The help takes the keys until it is closed
+        app.handle_key(KeyCode::Char('?'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('k'));
+        assert_eq!(app.help, Some(1));
+        assert_eq!(app.table_state.selected(), Some(0));
+        app.handle_key(KeyCode::Char('q'));
+        assert!(app.help.is_none());
+        assert!(!app.should_quit);
+
+        // m waits for the next key only while rows are marked
+        app.handle_key(KeyCode::Char('m'));
+        assert!(app.prefix.is_none());
+        app.handle_key(KeyCode::Char(' '));
+        app.handle_key(KeyCode::Char('m'));
+        assert_eq!(app.prefix, Some('m'));
+        app.handle_key(KeyCode::Char('x'));
+        assert!(app.prefix.is_none());
+        assert_eq!(app.marks.len(), 1);
+        app.handle_key(KeyCode::Char('m'));
+        app.handle_key(KeyCode::Char('u'));
+        assert!(app.marks.is_empty());
+    }
+
+    #[test]
+    fn test_paging_and_goto() {
+        let mut app = test_app();
+        let rows: Vec<Vec<String>> = (0..30)
+            .map(|i| vec![i.to_string(), "web".to_string(), "web.local".to_string()])
+            .collect();
+        let rows: Vec<Vec<&str>> = rows
+            .iter()
+            .map(|r| r.iter().map(String::as_str).collect())
+            .collect();
+        let rows: Vec<&[&str]> = rows.iter().map(Vec::as_slice).collect();
+        app.table_data = Some(test_data(&["id", "host", "hostname"], &rows));
+        app.table_state.select(Some(0));
+        app.active_panel = ActivePanel::DataView;
+        app.view_page = 12;
+        let typed = |app: &mut App, keys: &str| {
+            for c in keys.chars() {
+                app.handle_key(KeyCode::Char(c));
+            }
+        };
+
+        app.handle_key(KeyCode::PageDown);
+        assert_eq!(app.table_state.selected(), Some(10));
+        app.handle_ctrl_key(KeyCode::Char('d'));
+        assert_eq!(app.table_state.selected(), Some(15));
+        app.handle_ctrl_key(KeyCode::Char('u'));
+        app.handle_key(KeyCode::Char('G'));
+        assert_eq!(app.table_state.selected(), Some(29));
+        app.handle_key(KeyCode::PageUp);
+        assert_eq!(app.table_state.selected(), Some(19));
+        app.handle_key(KeyCode::Char('g'));
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        // :N selects row N, stopping at the last
+        typed(&mut app, ":25");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.table_state.selected(), Some(24));
+        typed(&mut app, ":x");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(
+            app.goto_prompt.as_ref().unwrap().error.as_deref(),
+            Some("Type a row number")
+        );
+        app.handle_key(KeyCode::Esc);
+        typed(&mut app, ":99");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.table_state.selected(), Some(29));
+
+        // Column names complete with Tab, prefix matches first
+        typed(&mut app, "cNAME");
+        assert_eq!(app.goto_prompt.as_ref().unwrap().matches, ["hostname"]);
+        app.handle_key(KeyCode::Esc);
+        typed(&mut app, "cho");
+        app.handle_key(KeyCode::Tab);
+        app.handle_key(KeyCode::Tab);
+        assert_eq!(app.goto_prompt.as_ref().unwrap().input, "hostname");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.column_offset, 2);
+        assert_eq!(app.current_column().unwrap().0, "hostname");
+
+        // In the record view the name picks a field
+        app.handle_key(KeyCode::Char('T'));
+        typed(&mut app, "chost");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.record_view.as_ref().unwrap().selected(), Some(1));
+        app.handle_key(KeyCode::Char('G'));
+        assert_eq!(app.record_view.as_ref().unwrap().selected(), Some(2));
+
+        // The file list jumps between files
+        let dir = std::env::temp_dir().join(format!("data_viewer_goto_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a.csv", "b.csv", "c.json"] {
+            fs::write(dir.join(name), "x\n1\n").unwrap();
+        }
+        app.current_dir = dir.clone();
+        app.refresh_file_list().unwrap();
+        app.file_list_state.select(Some(0));
+        app.active_panel = ActivePanel::FileList;
+        app.handle_key(KeyCode::End);
+        assert_eq!(app.selected_entry().unwrap().name, "c.json");
+        typed(&mut app, ":2");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.selected_entry().unwrap().name, "a.csv");
+        typed(&mut app, "cb.");
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.selected_entry().unwrap().name, "b.csv");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_column_manager() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_columns_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["a", "b", "c", "d"],
+            &[&["1", "2", "3", "4"], &["5", "6", "7", "8"]],
+        ));
+        app.selected_file = Some(dir.join("test.csv"));
+        app.active_panel = ActivePanel::DataView;
 
-    match app.active_tab {
-        ActiveTab::Table => {
-            render_table_view(frame, app, chunks[1], content_block);
+        // Pin d, move a after b and hide c; the selection follows each column
+        app.handle_key(KeyCode::Char('C'));
+        for key in "jjjpjJjjh".chars() {
+            app.handle_key(KeyCode::Char(key));
         }
-        ActiveTab::Raw => {
-            render_raw_view(frame, app, chunks[1], content_block);
+        assert_eq!(app.view.pinned_columns, ["d"]);
+        assert_eq!(app.view.column_order, ["d", "b", "a", "c"]);
+        assert_eq!(app.view.hidden_columns, ["c"]);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(
+            app.table_data.as_ref().unwrap().headers,
+            ["a", "b", "c", "d"]
+        );
+
+        // Scrolling sideways keeps the pinned column and stops at the last one
+        for _ in 0..3 {
+            app.handle_key(KeyCode::Char('l'));
         }
+        assert_eq!(app.column_offset, 1);
+        app.handle_key(KeyCode::Char('h'));
+        assert_eq!(app.column_offset, 0);
+
+        // Exports and copies use either the file columns or the shown ones
+        app.marks.insert(1);
+        assert_eq!(app.marked_rows_text(true), "d\tb\ta\n8\t6\t5");
+        let path = app.export_marked(true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "d,b,a\n8,6,5\n");
+        app.handle_key(KeyCode::Char('C'));
+        app.handle_key(KeyCode::Char('r'));
+        assert_eq!(app.view, ViewSettings::default());
+        fs::remove_dir_all(&dir).unwrap();
     }
-}
 
-/// Render the table view
-fn render_table_view(frame: &mut Frame, app: &mut App, area: Rect, block: Block) {
-    if let Some(data) = &app.table_data {
-        if data.headers.is_empty() {
-            let paragraph = Paragraph::new("No tabular data available")
-                .block(block)
-                .style(Style::default().fg(Color::DarkGray));
-            frame.render_widget(paragraph, area);
-            return;
+    #[test]
+    fn test_computed_columns() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["first", "last", "bytes"],
+            &[&["Ada", "Lovelace", "2048"], &["Alan", "Turing", "512"]],
+        ));
+        app.active_panel = ActivePanel::DataView;
+
+        app.handle_key(KeyCode::Char('C'));
+        app.handle_key(KeyCode::Char('a'));
+        for c in "bytes / 1024 as kb".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Enter);
+        assert!(app.computed_prompt.is_none());
+        app.handle_key(KeyCode::Char('a'));
+        for c in "concat(first, ' ', lst)".chars() {
+            app.handle_key(KeyCode::Char(c));
         }
+        app.handle_key(KeyCode::Enter);
+        let error = app
+            .computed_prompt
+            .as_ref()
+            .unwrap()
+            .error
+            .as_ref()
+            .unwrap();
+        assert_eq!(error.message, "Unknown column 'lst'");
+        app.handle_key(KeyCode::Esc);
 
-        // Calculate column widths
-        let col_count = data.headers.len();
-        let available_width = area.width.saturating_sub(2) as usize; // Account for borders
-        let col_width = (available_width / col_count).max(10);
+        // The new column is selected; sorting by it reorders the rows
+        app.handle_key(KeyCode::Char('s'));
+        assert_eq!(app.view.sort.as_ref().unwrap().column, "kb");
+        assert_eq!(app.view_rows, Some(vec![1, 0]));
+        app.handle_key(KeyCode::Enter);
 
-        let header_cells: Vec<Cell> = data
-            .headers
-            .iter()
-            .map(|h| {
-                Cell::from(h.clone()).style(
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                )
-            })
-            .collect();
-        let header = Row::new(header_cells).height(1).bottom_margin(1);
+        app.marks.insert(0);
+        assert_eq!(
+            app.marked_rows_text(true),
+            "first\tlast\tbytes\tkb\nAda\tLovelace\t2048\t2"
+        );
+        assert_eq!(app.table_data.as_ref().unwrap().headers.len(), 3);
 
-        let rows: Vec<Row> = data
-            .rows
-            .iter()
-            .enumerate()
-            .map(|(i, row)| {
-                let cells: Vec<Cell> = row.iter().map(|c| Cell::from(c.clone())).collect();
-                let style = if i % 2 == 0 {
-                    Style::default()
-                } else {
-                    Style::default().bg(Color::Rgb(30, 30, 30))
-                };
-                Row::new(cells).style(style)
-            })
-            .collect();
+        app.handle_key(KeyCode::Char('C'));
+        app.column_picker.as_mut().unwrap().select(Some(3));
+        app.handle_key(KeyCode::Char('x'));
+        assert!(app.view.computed_columns.is_empty());
+        assert_eq!(app.view.sort, None);
+    }
 
-        let widths: Vec<Constraint> = (0..col_count)
-            .map(|_| Constraint::Min(col_width as u16))
-            .collect();
+    #[test]
+    fn test_record_view() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["id", "tags", "seen"],
+            &[&["1", "[\"a\"]", "2024-05-01"], &["2", "", "never"]],
+        ));
+        app.view.toggle_hidden("seen");
+        app.active_panel = ActivePanel::DataView;
 
-        let table = Table::new(rows, widths)
-            .header(header)
-            .block(block.title(format!(" {} ({} rows) ", data.file_name, data.rows.len())))
-            .highlight_style(
-                Style::default()
-                    .bg(Color::DarkGray)
-                    .add_modifier(Modifier::BOLD),
-            )
-            .highlight_symbol("> ");
+        app.handle_key(KeyCode::Char('T'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('j'));
+        assert_eq!(app.record_view.as_ref().unwrap().selected(), Some(1));
+        let record = app.selected_record().unwrap();
+        assert_eq!(
+            record.fields[1],
+            ("tags".to_string(), "[\"a\"]".to_string())
+        );
+        assert_eq!(record.fields.len(), 2);
+        assert_eq!(value_type(&record.fields[1].1), "array");
+        assert_eq!(value_type("2024-05-01"), "timestamp");
 
-        frame.render_stateful_widget(table, area, &mut app.table_state);
-    } else {
-        let paragraph = Paragraph::new("Select a JSON or CSV file to view its contents\n\nUse j/k or arrow keys to navigate\nPress Enter to open a file\nPress Tab to switch panels\nPress 1/2 to switch tabs\nPress q to quit")
-            .block(block.title(" No Data "))
-            .style(Style::default().fg(Color::DarkGray))
-            .wrap(Wrap { trim: true });
-        frame.render_widget(paragraph, area);
+        // J/K step through records, keeping the field
+        app.handle_key(KeyCode::Char('J'));
+        app.handle_key(KeyCode::Char('J'));
+        assert_eq!(app.table_state.selected(), Some(1));
+        assert_eq!(app.selected_record().unwrap().fields[0].1, "2");
+        assert_eq!(app.record_view.as_ref().unwrap().selected(), Some(1));
+        assert!(app.join_wizard.is_none());
+
+        app.handle_key(KeyCode::Esc);
+        assert!(app.record_view.is_none());
+        assert!(!app.should_quit);
     }
-}
 
-/// Render the raw view
-fn render_raw_view(frame: &mut Frame, app: &App, area: Rect, block: Block) {
-    let content = if let Some(data) = &app.table_data {
-        data.raw_content.clone()
-    } else {
-        "No file loaded".to_string()
-    };
+    #[test]
+    fn test_file_panel_navigation_and_filter() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_browse_{}", std::process::id()));
+        fs::create_dir_all(dir.join("logs")).unwrap();
+        fs::write(dir.join("a.csv"), "x\n1\n").unwrap();
+        fs::write(dir.join("b.json"), "[]").unwrap();
+        let mut app = test_app();
+        app.current_dir = dir.join("logs");
+        app.refresh_file_list().unwrap();
+        app.file_list_state.select(Some(0));
 
-    let paragraph = Paragraph::new(content)
-        .block(block.title(" Raw Content "))
-        .style(Style::default())
-        .wrap(Wrap { trim: false });
+        // `..` leads back up with the directory we left selected
+        assert_eq!(app.file_list[0].name, "..");
+        app.select_current();
+        assert_eq!(app.current_dir, dir);
+        assert_eq!(app.selected_entry().unwrap().name, "logs");
 
-    frame.render_widget(paragraph, area);
-}
+        app.handle_key(KeyCode::Char('/'));
+        for c in "*.json".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Enter);
+        let names: Vec<_> = app.file_list.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["..", "logs", "b.json"]);
+        assert_eq!(app.selected_entry().unwrap().name, "logs");
 
-/// Render a modal dialog
-fn render_modal(frame: &mut Frame, modal: &ModalDialog) {
-    let area = centered_rect(50, 30, frame.area());
+        app.handle_key(KeyCode::Char('/'));
+        for _ in 0.."*.json".len() {
+            app.handle_key(KeyCode::Backspace);
+        }
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.list_options.glob, None);
+        assert_eq!(app.file_list.len(), 4);
 
-    // Clear the area behind the modal
-    frame.render_widget(Clear, area);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-    let block = Block::default()
-        .title(format!(" {} ", modal.title))
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Red))
-        .style(Style::default().bg(Color::Black));
+    #[test]
+    fn test_finder_opens_selected_file() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_find_{}", std::process::id()));
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::write(dir.join("a/b/sales.csv"), "x\n1\n").unwrap();
+        fs::write(dir.join("stats.json"), "[]").unwrap();
+        let mut app = test_app();
+        app.current_dir = dir.clone();
 
-    let text = format!("{}\n\nPress Enter or Esc to close", modal.message);
-    let paragraph = Paragraph::new(text)
-        .block(block)
-        .style(Style::default().fg(Color::White))
-        .wrap(Wrap { trim: true });
+        app.handle_ctrl_key(KeyCode::Char('p'));
+        for _ in 0..500 {
+            app.tick();
+            if app.finder.as_ref().unwrap().index.done {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(app.finder.as_ref().unwrap().matches.len(), 2);
 
-    frame.render_widget(paragraph, area);
-}
+        for c in "sls".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        let finder = app.finder.as_ref().unwrap();
+        assert_eq!(finder.matches.len(), 1);
+        assert_eq!(finder.state.selected(), Some(0));
 
-/// Setup the terminal
-fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
-    enable_raw_mode().context("Failed to enable raw mode")?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)
-        .context("Failed to enter alternate screen")?;
-    let backend = CrosstermBackend::new(stdout);
-    let terminal = Terminal::new(backend).context("Failed to create terminal")?;
-    Ok(terminal)
-}
+        app.handle_key(KeyCode::Enter);
+        assert!(app.finder.is_none());
+        assert_eq!(app.selected_file, Some(dir.join("a/b/sales.csv")));
+        assert_eq!(app.table_data.as_ref().unwrap().rows.len(), 1);
 
-/// Restore the terminal to its original state
-fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
-    disable_raw_mode().context("Failed to disable raw mode")?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )
-    .context("Failed to leave alternate screen")?;
-    terminal.show_cursor().context("Failed to show cursor")?;
-    Ok(())
-}
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-/// Run the application main loop
-fn run_app(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
-    loop {
-        terminal.draw(|f| ui(f, app))?;
+    #[test]
+    fn test_filter_sort_and_hide_columns() {
+        let mut app = test_app();
+        app.table_data = Some(test_data(
+            &["host", "bytes"],
+            &[&["a", "10"], &["b", "300"], &["c", "20"], &["d", "5"]],
+        ));
+        app.table_state.select(Some(2));
+        app.active_panel = ActivePanel::DataView;
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                app.handle_key(key.code);
-            }
+        app.handle_key(KeyCode::Char('f'));
+        for c in "byts > 8".chars() {
+            app.handle_key(KeyCode::Char(c));
         }
+        app.handle_key(KeyCode::Enter);
+        let error = app.filter_prompt.as_ref().unwrap().error.as_ref().unwrap();
+        assert_eq!(error.pos, 0);
 
-        if app.should_quit {
-            break;
-        }
+        app.filter_prompt = Some(FilterPrompt {
+            input: "bytes > 8".to_string(),
+            error: None,
+        });
+        app.handle_key(KeyCode::Enter);
+        assert!(app.filter_prompt.is_none());
+        assert_eq!(app.view_rows, Some(vec![0, 1, 2]));
+        // Row "c" stays selected
+        assert_eq!(app.base_row(app.table_state.selected().unwrap()), Some(2));
+
+        // Sort by bytes descending, hide the host column
+        app.handle_key(KeyCode::Char('C'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('s'));
+        app.handle_key(KeyCode::Char('s'));
+        app.handle_key(KeyCode::Char('k'));
+        app.handle_key(KeyCode::Char(' '));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.view_rows, Some(vec![1, 2, 0]));
+        assert_eq!(app.view.hidden_columns, ["host"]);
+        assert_eq!(app.displayed_rows()[0][0], "b");
+
+        // Backspace removes the filter, then the sort order
+        app.handle_key(KeyCode::Backspace);
+        assert_eq!(app.view_rows, Some(vec![1, 2, 0, 3]));
+        app.handle_key(KeyCode::Backspace);
+        assert_eq!(app.view_rows, None);
+        assert_eq!(app.view.hidden_columns, ["host"]);
     }
-    Ok(())
-}
 
-fn main() -> Result<()> {
-    // Setup terminal
-    let mut terminal = setup_terminal()?;
+    #[test]
+    fn test_file_view_state_is_restored() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_state_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.csv");
+        let second = dir.join("second.csv");
+        fs::write(&first, "n\n3\n1\n2\n").unwrap();
+        fs::write(&second, "x\n1\n").unwrap();
+        let mut app = test_app();
 
-    // Create app state
-    let mut app = App::new()?;
+        app.load_file(&first);
+        let mut view = app.view.clone();
+        view.cycle_sort("n");
+        app.set_view(view).unwrap();
+        app.table_state.select(Some(2));
+        app.select_tab(ActiveTab::Raw);
 
-    // Run the app
-    let result = run_app(&mut terminal, &mut app);
+        app.load_file(&second);
+        assert_eq!(app.view, ViewSettings::default());
+        assert_eq!(app.active_tab, ActiveTab::Table);
+        assert_eq!(app.session.recent_files, [second.clone(), first.clone()]);
 
-    // Restore terminal
-    restore_terminal(&mut terminal)?;
+        app.load_file(&first);
+        assert_eq!(app.view_rows, Some(vec![1, 2, 0]));
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.active_tab, ActiveTab::Raw);
 
-    // Handle any errors from run_app
-    result
-}
+        // A fresh app picks the recent files up from the saved session
+        let mut restored = test_app();
+        restored.restore_session(app.session.clone());
+        assert!(restored.recent_picker.is_some());
+        restored.handle_key(KeyCode::Enter);
+        assert_eq!(restored.selected_file, Some(first));
+        assert_eq!(restored.current_dir, dir);
+        assert_eq!(restored.view_rows, Some(vec![1, 2, 0]));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
     #[test]
-    fn test_active_tab_index() {
-        assert_eq!(ActiveTab::Table.index(), 0);
-        assert_eq!(ActiveTab::Raw.index(), 1);
+    fn test_buffers_keep_files_loaded() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_buffers_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.csv");
+        let second = dir.join("second.csv");
+        fs::write(&first, "n\n1\n2\n3\n").unwrap();
+        fs::write(&second, "x\na\n").unwrap();
+        let mut app = test_app();
+
+        app.load_file(&first);
+        app.table_state.select(Some(2));
+        app.marks.insert(1);
+        app.load_file(&second);
+        assert_eq!(app.buffers.len(), 2);
+        assert_eq!(app.table_data.as_ref().unwrap().file_name, "second.csv");
+        assert!(app.marks.is_empty());
+
+        // The first file comes back with its selection and marks
+        app.handle_key(KeyCode::Char('}'));
+        assert_eq!(app.selected_file, Some(first.clone()));
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.marks, BTreeSet::from([1]));
+        let names: Vec<_> = app
+            .buffer_list()
+            .iter()
+            .map(|(f, _)| f.to_path_buf())
+            .collect();
+        assert_eq!(names, [first.clone(), second.clone()]);
+
+        // Opening the second file again switches to it rather than adding a buffer
+        app.load_file(&second);
+        assert_eq!(app.buffers.len(), 2);
+        assert_eq!(app.selected_file, Some(second.clone()));
+
+        // Closing the active buffer shows its neighbour
+        app.handle_key(KeyCode::Char('b'));
+        assert_eq!(app.buffer_picker.as_ref().unwrap().selected(), Some(1));
+        app.handle_key(KeyCode::Char('d'));
+        assert_eq!(app.buffers.len(), 1);
+        assert_eq!(app.selected_file, Some(first));
+        app.handle_key(KeyCode::Char('d'));
+        assert!(app.buffer_picker.is_none());
+        assert!(app.table_data.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_active_tab_next() {
-        assert_eq!(ActiveTab::Table.next(), ActiveTab::Raw);
-        assert_eq!(ActiveTab::Raw.next(), ActiveTab::Table);
+    fn test_query_open_buffers_by_name() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_query_{}", std::process::id()));
+        let (left, right) = (dir.join("left"), dir.join("right"));
+        fs::create_dir_all(&left).unwrap();
+        fs::create_dir_all(&right).unwrap();
+        fs::write(left.join("hosts.csv"), "host\nweb1\nweb2\nweb3\n").unwrap();
+        fs::write(right.join("events.csv"), "n\n1\n").unwrap();
+        let mut app = test_app();
+
+        // A buffer from another directory, with a row deleted in memory
+        app.load_file(&left.join("hosts.csv"));
+        let data = app.table_data.as_mut().unwrap();
+        data.rows.pop();
+        data.modified = true;
+        app.current_dir = right.clone();
+        app.refresh_file_list().unwrap();
+        app.load_file(&right.join("events.csv"));
+
+        app.run_query("SELECT count(*) FROM hosts").unwrap();
+        assert_eq!(app.query.take().unwrap().data.rows, [["2"]]);
+        app.run_query("SELECT n FROM events.csv").unwrap();
+        assert_eq!(app.query.take().unwrap().data.rows, [["1"]]);
+        assert!(app.run_query("SELECT * FROM missing").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_json_to_table_array_of_objects() {
-        let app = App {
-            file_list: vec![],
-            file_list_state: ListState::default(),
-            selected_file: None,
-            table_data: None,
-            table_state: TableState::default(),
-            active_tab: ActiveTab::Table,
-            active_panel: ActivePanel::FileList,
-            current_dir: PathBuf::from("/tmp"),
-            modal: None,
-            should_quit: false,
-        };
+    fn test_join_wizard() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_join_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("hosts.csv"),
+            "host,ip\nweb1,10.0.0.1\nweb2,10.0.0.2\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("inventory.json"),
+            r#"[{"host": "web1", "rack": "A1"}, {"host": "cache1", "rack": "B2"}]"#,
+        )
+        .unwrap();
+        let mut app = test_app();
+        app.current_dir = dir.clone();
+        app.refresh_file_list().unwrap();
+        app.load_file(&dir.join("hosts.csv"));
 
-        let json = serde_json::json!([
-            {"name": "Alice", "age": 30},
-            {"name": "Bob", "age": 25}
-        ]);
+        app.handle_key(KeyCode::Char('J'));
+        assert_eq!(
+            app.join_wizard.as_ref().unwrap().files,
+            [dir.join("inventory.json")]
+        );
+        // The shared column is offered as the key on both sides
+        app.handle_key(KeyCode::Enter);
+        app.handle_key(KeyCode::Enter);
+        let wizard = app.join_wizard.as_ref().unwrap();
+        assert_eq!((wizard.step, wizard.left_key), (JoinStep::RightKey, 0));
+        assert_eq!(wizard.state.selected(), Some(0));
+        // Step back and forward again, then pick an outer join
+        app.handle_key(KeyCode::Esc);
+        app.handle_key(KeyCode::Enter);
+        app.handle_key(KeyCode::Enter);
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Enter);
 
-        let (headers, rows) = app.json_to_table(&json);
-        assert_eq!(headers.len(), 2);
-        assert_eq!(rows.len(), 2);
+        assert!(app.join_wizard.is_none());
+        let modal = app.modal.take().unwrap();
+        assert!(!modal.error);
+        assert!(modal.message.contains("Unmatched in hosts.csv: web2"));
+        assert!(modal
+            .message
+            .contains("Unmatched in inventory.json: cache1"));
+        let joined = &app.query.as_ref().unwrap().data;
+        assert_eq!(
+            joined.headers,
+            ["hosts.host", "ip", "inventory.host", "rack"]
+        );
+        assert_eq!(joined.rows.len(), 3);
+        assert_eq!(joined.rows[2], ["", "", "cache1", "B2"]);
+
+        // Backspace returns to the loaded file
+        app.handle_key(KeyCode::Backspace);
+        assert!(app.query.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_json_to_table_object() {
-        let app = App {
-            file_list: vec![],
-            file_list_state: ListState::default(),
-            selected_file: None,
-            table_data: None,
-            table_state: TableState::default(),
-            active_tab: ActiveTab::Table,
-            active_panel: ActivePanel::FileList,
-            current_dir: PathBuf::from("/tmp"),
-            modal: None,
-            should_quit: false,
-        };
+    fn test_schema_validation() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_schema_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("hosts.ndjson");
+        fs::write(
+            &file,
+            "{\"host\": \"web1\", \"port\": 80}\n{\"host\": \"db1\"}\nnot json\n\n{\"host\": \"db2\", \"port\": \"x\"}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("hosts.schema.json"),
+            r#"{"required": ["host", "port"], "properties": {"port": {"type": "integer"}}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("loose.json"), r#"{"required": ["host"]}"#).unwrap();
+        let mut app = test_app();
 
-        let json = serde_json::json!({"key1": "value1", "key2": "value2"});
+        // The sibling schema is picked up; the bad line is a parse error
+        app.load_file(&file);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.rows.len(), 3);
+        assert_eq!(data.errors[0].line, 3);
+        assert_eq!(data.schema, Some(dir.join("hosts.schema.json")));
+        let invalid: Vec<_> = data.schema_errors.keys().copied().collect();
+        assert_eq!(invalid, [1, 2]);
+        assert_eq!(data.schema_errors[&2][0].instance_path, "/port");
+        assert_eq!(data.schema_errors[&1][0].column.as_deref(), Some("port"));
 
-        let (headers, rows) = app.json_to_table(&json);
-        assert_eq!(headers, vec!["Key", "Value"]);
-        assert_eq!(rows.len(), 2);
+        app.handle_key(KeyCode::Char('I'));
+        assert_eq!(app.view_len(), 2);
+        assert_eq!(app.base_row(0), Some(1));
+        app.handle_key(KeyCode::Backspace);
+
+        // A chosen schema replaces the sibling until the input is cleared
+        app.handle_key(KeyCode::Char('v'));
+        assert_eq!(
+            app.schema_prompt.as_deref(),
+            Some(dir.join("hosts.schema.json").to_str().unwrap())
+        );
+        app.schema_prompt = Some("loose.json".to_string());
+        app.handle_key(KeyCode::Enter);
+        assert!(app.schema_prompt.is_none());
+        assert!(app.table_data.as_ref().unwrap().schema_errors.is_empty());
+        app.handle_key(KeyCode::Char('I'));
+        assert!(app.modal.is_none());
+        assert_eq!(app.status_message.as_ref().unwrap().0, "Every row is valid");
+
+        app.handle_key(KeyCode::Char('v'));
+        app.schema_prompt = Some("missing.json".to_string());
+        app.handle_key(KeyCode::Enter);
+        assert!(app.modal.take().is_some());
+        assert!(app.schema_prompt.is_some());
+        app.schema_prompt = Some(String::new());
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.table_data.as_ref().unwrap().schema_errors.len(), 2);
+        assert!(!app.schemas.contains_key(&file));
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
-    fn test_value_to_string() {
-        let app = App {
-            file_list: vec![],
-            file_list_state: ListState::default(),
-            selected_file: None,
-            table_data: None,
-            table_state: TableState::default(),
-            active_tab: ActiveTab::Table,
-            active_panel: ActivePanel::FileList,
-            current_dir: PathBuf::from("/tmp"),
-            modal: None,
-            should_quit: false,
-        };
+    fn test_streamed_json_array() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_stream_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("events.json");
+        let text = "\u{feff}[\n  {\"id\": 1, \"tags\": [\"a\"]},\n  \"skipped\",\n  {\"id\": \"two\"}\n]\n";
+        fs::write(&file, text).unwrap();
+        fs::write(
+            dir.join("events.schema.json"),
+            r#"{"properties": {"id": {"type": "integer"}}}"#,
+        )
+        .unwrap();
+        let mut app = test_app();
 
-        assert_eq!(app.value_to_string(None), "");
-        assert_eq!(app.value_to_string(Some(&Value::Null)), "null");
-        assert_eq!(app.value_to_string(Some(&Value::Bool(true))), "true");
-        assert_eq!(app.value_to_string(Some(&serde_json::json!(42))), "42");
-        assert_eq!(app.value_to_string(Some(&Value::String("hello".to_string()))), "hello");
+        // Rows come from the stream and the raw view reads the file
+        app.load_file(&file);
+        let data = app.table_data.as_mut().unwrap();
+        assert_eq!(data.headers, ["id", "tags"]);
+        assert_eq!(data.rows, [["1", "[1 items]"], ["two", ""]]);
+        assert_eq!(data.decoded_size, text.len() as u64);
+        assert!(data.encoding.bom);
+        assert!(matches!(data.raw, RawText::File(_)));
+        assert_eq!(
+            data.raw.lines(0, 2).unwrap(),
+            ["[", "  {\"id\": 1, \"tags\": [\"a\"]},"]
+        );
+        let invalid: Vec<_> = data.schema_errors.keys().copied().collect();
+        assert_eq!(invalid, [1]);
+
+        app.handle_key(KeyCode::Char('2'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Char('k'));
+        assert_eq!(app.raw_scroll, 1);
+        assert_eq!(app.table_state.selected(), Some(0));
+
+        // Other documents are still read whole
+        app.handle_key(KeyCode::Char('.'));
+        let prompt = app.json_path_prompt.take().unwrap();
+        assert_eq!(prompt.document[1], "skipped");
+        fs::write(&file, r#"{"items": [{"id": 1}]}"#).unwrap();
+        app.reload_current();
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, ["Key", "Value"]);
+        assert!(app.modal.take().unwrap().message.contains("JSON array"));
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
@@ -863,4 +7660,285 @@ mod tests {
         assert!(centered.width < area.width);
         assert!(centered.height < area.height);
     }
+
+    #[test]
+    fn test_print_output() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_print_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("hits.csv");
+        fs::write(
+            &file,
+            "host,bytes,status\nweb1,100,200\nweb2,2048,500\nweb3,4096,200\n",
+        )
+        .unwrap();
+        let print = |args: &str| -> Result<String> {
+            let args = format!("--print {} {}", file.display(), args);
+            let Command::Print(options) =
+                print::parse_args(args.split_whitespace().map(String::from))?
+            else {
+                panic!("expected --print");
+            };
+            Ok(String::from_utf8(print_output(&options)?).unwrap())
+        };
+
+        let table = print("--columns host,bytes --filter bytes>1000 --limit 1 --width 40").unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("┌ hits.csv (1 of 3 rows) ─"));
+        assert!(lines[1].contains("host") && lines[1].contains("bytes"));
+        assert!(lines[3].contains("web2") && lines[3].contains("2048"));
+        assert!(lines[4].starts_with('└') && lines[4].chars().count() == 40);
+
+        let plain = print("--format plain --width 40 --columns status").unwrap();
+        assert_eq!(
+            plain.lines().map(str::trim).collect::<Vec<_>>(),
+            ["status", "", "200", "500", "200"]
+        );
+
+        let csv = print("--format csv --filter status=200 --columns bytes,host").unwrap();
+        assert_eq!(csv, "bytes,host\n100,web1\n4096,web3\n");
+
+        let error = print("--columns size").unwrap_err().to_string();
+        assert!(error.contains("Unknown column 'size'") && error.contains("host, bytes, status"));
+        assert!(print("--filter bytes>").is_err());
+
+        // More rows than a u16 terminal height, drawn in several chunks
+        let mut big = String::from("n\n");
+        for i in 0..70_000 {
+            big.push_str(&format!("{}\n", i));
+        }
+        fs::write(&file, big).unwrap();
+        let table = print("--width 40").unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 70_004);
+        assert!(lines[0].starts_with("┌ hits.csv (70000 of 70000 rows)"));
+        assert_eq!(lines[3].trim_matches(['│', ' ']), "0");
+        assert_eq!(lines[1003].trim_matches(['│', ' ']), "1000");
+        assert_eq!(lines[70_002].trim_matches(['│', ' ']), "69999");
+        assert!(lines[70_003].starts_with('└'));
+        assert_eq!(lines.iter().filter(|l| l.starts_with('┌')).count(), 1);
+        let plain = print("--format plain --width 40").unwrap();
+        assert_eq!(plain.lines().count(), 70_002);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sqlite_tables() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_sqlite_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("shop.sqlite");
+        rusqlite::Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT);
+                 CREATE VIEW cheap AS SELECT id FROM items WHERE id < 3;
+                 WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 2500)
+                 INSERT INTO items SELECT i, 'item ' || i FROM n;",
+            )
+            .unwrap();
+        let mut app = test_app();
+        app.current_dir = dir.clone();
+        app.refresh_file_list().unwrap();
+        let names =
+            |app: &App| -> Vec<String> { app.file_list.iter().map(|e| e.name.clone()).collect() };
+
+        // The database expands into its tables and views
+        app.select_path(&db);
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(names(&app), ["..", "shop.sqlite", "cheap", "items"]);
+        app.handle_key(KeyCode::Char('G'));
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.selected_file, Some(db.join("items")));
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, ["id", "name"]);
+        assert_eq!(data.rows.len(), PAGE_ROWS);
+        assert_eq!(
+            table_title(data, app.csv_mode),
+            " items (rows 1-1000 of 2500, UTF-8) "
+        );
+        assert!(
+            matches!(&data.raw, RawText::Text(schema) if schema.contains("CREATE TABLE items"))
+        );
+
+        // Pages are read as they are needed
+        app.handle_key(KeyCode::Char('>'));
+        app.handle_key(KeyCode::Char('>'));
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.window.as_ref().unwrap().offset, 2000);
+        assert_eq!(data.rows.len(), 500);
+        app.handle_key(KeyCode::Char('>'));
+        assert_eq!(
+            app.status_message.as_ref().unwrap().0,
+            "Already on the last page"
+        );
+        app.goto_number(1234);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.window.as_ref().unwrap().offset, 1000);
+        let selected = app.table_state.selected().unwrap();
+        assert_eq!(app.displayed_rows()[selected][0], "1234");
+
+        // Queries read the whole table; marks and the selection keep their rows
+        app.toggle_mark();
+        app.table_state.select(Some(selected));
+        let export = app.export_marked(false).unwrap();
+        assert_eq!(export, dir.join("items-marked.csv"));
+        app.run_query("SELECT count(*), max(id) FROM current")
+            .unwrap();
+        assert_eq!(app.query.take().unwrap().data.rows, [["2500", "2500"]]);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.rows.len(), 2500);
+        assert!(!data.is_partial());
+        assert_eq!(app.marks.iter().copied().collect::<Vec<_>>(), [1233]);
+        let selected = app.table_state.selected().unwrap();
+        assert_eq!(app.displayed_rows()[selected][0], "1234");
+
+        // Rows would come back with the next page, so they can't be deleted
+        app.editing = true;
+        app.delete_marked();
+        assert_eq!(app.table_data.as_ref().unwrap().rows.len(), 2500);
+        assert_eq!(
+            app.status_message.as_ref().unwrap().0,
+            "Rows of a database table can't be deleted"
+        );
+        fs::remove_file(export).unwrap();
+
+        // Tables reopen from the recent files; h collapses the database
+        app.active_panel = ActivePanel::FileList;
+        app.handle_key(KeyCode::Char('h'));
+        assert_eq!(names(&app), ["..", "shop.sqlite"]);
+        assert_eq!(app.selected_entry().unwrap().path, db);
+        assert!(file_exists(&db.join("items")));
+        app.reveal(&db.join("cheap"));
+        assert_eq!(app.selected_entry().unwrap().name, "cheap");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spreadsheet_sheets() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_sheets_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("budget.xlsx");
+        spreadsheet::tests::write_xlsx(&path);
+        let mut app = test_app();
+        app.current_dir = dir.clone();
+        app.refresh_file_list().unwrap();
+
+        // A workbook with several sheets asks which one to show
+        app.select_path(&path);
+        app.handle_key(KeyCode::Enter);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, ["Item", "Cost / Net", "Cost / Gross", "Due"]);
+        assert_eq!(
+            table_title(data, app.csv_mode),
+            " budget.xlsx (2 rows, sheet Budget, UTF-8) "
+        );
+        let picker = app.sheet_picker.as_ref().unwrap();
+        assert_eq!(picker.names, ["Budget", "Notes"]);
+        assert_eq!(picker.state.selected(), Some(0));
+
+        app.handle_key(KeyCode::Char('j'));
+        app.handle_key(KeyCode::Enter);
+        assert!(app.sheet_picker.is_none());
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, ["note"]);
+        assert_eq!(data.rows, [["hello"]]);
+        assert_eq!(data.file_type, "Excel");
+
+        // The chosen sheet is kept when the file is opened again
+        app.load_file(&path);
+        assert!(app.sheet_picker.is_none());
+        assert_eq!(
+            app.table_data.as_ref().unwrap().sheet.as_deref(),
+            Some("Notes")
+        );
+        assert_eq!(app.session.files[&path].sheet.as_deref(), Some("Notes"));
+
+        app.handle_key(KeyCode::Char('.'));
+        assert_eq!(app.sheet_picker.as_ref().unwrap().state.selected(), Some(1));
+        app.handle_key(KeyCode::Esc);
+        assert!(app.sheet_picker.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parquet_row_groups() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_groups_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("orders.parquet");
+        columnar::tests::write_parquet(&path);
+        let mut app = test_app();
+        app.current_dir = dir.clone();
+        app.refresh_file_list().unwrap();
+
+        // Only the first row group is read on opening
+        app.select_path(&path);
+        app.handle_key(KeyCode::Enter);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, ["id", "customer", "tags"]);
+        assert_eq!(
+            data.rows,
+            [
+                ["1", "{2 keys}", "[2 items]"],
+                ["2", "{2 keys}", "[0 items]"]
+            ]
+        );
+        assert_eq!(
+            table_title(data, app.csv_mode),
+            " orders.parquet (2 of 5 rows, UTF-8) "
+        );
+        assert!(
+            matches!(&data.raw, RawText::Text(schema) if schema.contains("REQUIRED INT64 id;"))
+        );
+
+        // The next one follows as the selection nears the end, and going to
+        // a row reads up to it
+        app.tick();
+        assert_eq!(app.table_data.as_ref().unwrap().rows.len(), 4);
+        app.goto_number(5);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.rows.len(), 5);
+        assert_eq!(
+            table_title(data, app.csv_mode),
+            " orders.parquet (5 rows, UTF-8) "
+        );
+        assert_eq!(app.table_state.selected(), Some(4));
+
+        // Nested columns are tabulated with a JSON path, as in JSON files
+        app.handle_key(KeyCode::Char('.'));
+        for c in ".[].tags[]".chars() {
+            app.handle_key(KeyCode::Char(c));
+        }
+        app.handle_key(KeyCode::Enter);
+        let data = app.table_data.as_ref().unwrap();
+        assert_eq!(data.headers, ["Index", "Value"]);
+        assert_eq!(data.rows.len(), 6);
+        assert_eq!(data.rows[5], ["5", "f"]);
+
+        let options = PrintOptions {
+            file: path.clone(),
+            format: Format::Csv,
+            ..PrintOptions::default()
+        };
+        let csv = String::from_utf8(print_output(&options).unwrap()).unwrap();
+        assert_eq!(csv.lines().count(), 6);
+
+        // Pivots read the row groups that are left before grouping
+        app.json_paths.clear();
+        app.load_file(&path);
+        assert_eq!(app.table_data.as_ref().unwrap().rows.len(), 2);
+        app.apply_pivot(PivotSpec {
+            group_by: vec![0],
+            aggregates: vec![],
+        });
+        assert_eq!(app.view_len(), 5);
+        assert!(!app.table_data.as_ref().unwrap().is_partial());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
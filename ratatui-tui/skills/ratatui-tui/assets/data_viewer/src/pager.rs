@@ -0,0 +1,212 @@
+//! Paging through the raw text of a file without keeping it in memory
+//!
+//! The raw view reads the lines it shows straight from disk. Lines longer
+//! than [`SEGMENT`] bytes are split, so a minified document still pages a
+//! screen at a time. The start of every [`CHECKPOINT`]th line is remembered
+//! as the file is read, so moving back reads from the nearest checkpoint
+//! rather than from the start. Compressed files can't seek and are decoded
+//! from the start up to the checkpoint instead.
+
+use crate::compression::{self, Compression};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Longest line piece shown as one line, in bytes
+pub const SEGMENT: usize = 4096;
+/// Lines between remembered line starts
+const CHECKPOINT: usize = 1024;
+
+/// Text shown by the raw view
+#[derive(Debug, Clone)]
+pub enum RawText {
+    /// Decoded text held in memory, for files that had to be transcoded and
+    /// for derived tables (empty)
+    Text(String),
+    /// UTF-8 file read from disk a page at a time
+    File(FilePager),
+}
+
+impl RawText {
+    /// Lines `first..first + count`, fewer at the end of the text
+    pub fn lines(&mut self, first: usize, count: usize) -> io::Result<Vec<String>> {
+        match self {
+            RawText::Text(text) => Ok(text
+                .lines()
+                .skip(first)
+                .take(count)
+                .map(str::to_string)
+                .collect()),
+            RawText::File(pager) => pager.lines(first, count),
+        }
+    }
+
+    /// Number of lines, once known
+    pub fn line_count(&self) -> Option<usize> {
+        match self {
+            RawText::Text(text) => Some(text.lines().count()),
+            RawText::File(pager) => pager.line_count,
+        }
+    }
+
+    /// Heap memory held for the text, in bytes
+    pub fn memory_usage(&self) -> usize {
+        match self {
+            RawText::Text(text) => text.capacity(),
+            RawText::File(pager) => std::mem::size_of_val(&pager.checkpoints[..]),
+        }
+    }
+}
+
+/// Lines of a file, read on demand
+#[derive(Debug, Clone)]
+pub struct FilePager {
+    path: PathBuf,
+    compression: Compression,
+    /// Byte offset of line `i * CHECKPOINT`, for every checkpoint reached
+    checkpoints: Vec<u64>,
+    /// Set once a read reaches the end of the file
+    line_count: Option<usize>,
+}
+
+impl FilePager {
+    pub fn new(path: &Path) -> Self {
+        FilePager {
+            path: path.to_path_buf(),
+            compression: compression::split_extension(path).1,
+            checkpoints: vec![0],
+            line_count: None,
+        }
+    }
+
+    /// Lines `first..first + count`, fewer at the end of the file
+    pub fn lines(&mut self, first: usize, count: usize) -> io::Result<Vec<String>> {
+        let checkpoint = (first / CHECKPOINT).min(self.checkpoints.len() - 1);
+        let mut reader = self.open_at(self.checkpoints[checkpoint])?;
+        let mut line = checkpoint * CHECKPOINT;
+        let mut offset = self.checkpoints[checkpoint];
+        let mut lines = Vec::new();
+
+        while line < first.saturating_add(count) {
+            let Some(segment) = read_segment(&mut reader)? else {
+                self.line_count = Some(line);
+                break;
+            };
+            offset += segment.len() as u64;
+            line += 1;
+            if line.is_multiple_of(CHECKPOINT) && line / CHECKPOINT == self.checkpoints.len() {
+                self.checkpoints.push(offset);
+            }
+            if line > first {
+                let text = segment.strip_suffix(b"\n").unwrap_or(&segment);
+                let text = text.strip_suffix(b"\r").unwrap_or(text);
+                let text = match line {
+                    1 => text.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(text),
+                    _ => text,
+                };
+                lines.push(String::from_utf8_lossy(text).into_owned());
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Open the file positioned at a byte offset of its decompressed text
+    fn open_at(&self, offset: u64) -> io::Result<Box<dyn BufRead>> {
+        if self.compression == Compression::None {
+            let mut file = File::open(&self.path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            return Ok(Box::new(BufReader::new(file)));
+        }
+        let mut reader = compression::open(&self.path).map_err(io::Error::other)?;
+        io::copy(&mut reader.by_ref().take(offset), &mut io::sink())?;
+        Ok(Box::new(BufReader::new(reader)))
+    }
+}
+
+/// Read up to and including the next newline, or [`SEGMENT`] bytes cut at a
+/// character boundary when the line is longer; `None` at the end of input
+fn read_segment(reader: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut segment = Vec::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok((!segment.is_empty()).then_some(segment));
+        }
+        let room = SEGMENT - segment.len();
+        if let Some(newline) = buf[..buf.len().min(room)].iter().position(|&b| b == b'\n') {
+            segment.extend_from_slice(&buf[..=newline]);
+            reader.consume(newline + 1);
+            return Ok(Some(segment));
+        }
+        if buf.len() < room {
+            let len = buf.len();
+            segment.extend_from_slice(buf);
+            reader.consume(len);
+            continue;
+        }
+        // Cut before any UTF-8 continuation bytes so characters stay whole
+        let mut cut = room;
+        while cut > 0 && buf.get(cut).is_some_and(|&b| b & 0xC0 == 0x80) {
+            cut -= 1;
+        }
+        if cut == 0 && segment.is_empty() {
+            cut = room;
+        }
+        segment.extend_from_slice(&buf[..cut]);
+        reader.consume(cut);
+        return Ok(Some(segment));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_pages_lines_and_long_lines() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_pager_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lines.json");
+        let mut text: String = (0..3000).map(|i| format!("line {}\r\n", i)).collect();
+        text.push_str(&"é".repeat(SEGMENT));
+        fs::write(&path, &text).unwrap();
+
+        let mut pager = FilePager::new(&path);
+        assert_eq!(pager.lines(2998, 2).unwrap(), ["line 2998", "line 2999"]);
+        assert_eq!(pager.checkpoints.len(), 3);
+        assert_eq!(pager.lines(1500, 1).unwrap(), ["line 1500"]);
+
+        // The long line is split into whole characters
+        let tail = pager.lines(3000, 10).unwrap();
+        assert_eq!(tail.len(), 2);
+        assert_eq!(tail[0], "é".repeat(SEGMENT / 2));
+        assert_eq!(pager.line_count, Some(3002));
+        assert!(pager.lines(4000, 5).unwrap().is_empty());
+
+        let mut raw = RawText::Text("a\nb\nc".to_string());
+        assert_eq!(raw.lines(1, 5).unwrap(), ["b", "c"]);
+        assert_eq!(raw.line_count(), Some(3));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_pages_compressed_files() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_pager_gz_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lines.csv.gz");
+        let text: String = (0..2100).map(|i| format!("{}\n", i)).collect();
+        let mut encoder =
+            flate2::write::GzEncoder::new(fs::File::create(&path).unwrap(), Default::default());
+        io::Write::write_all(&mut encoder, text.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let mut pager = FilePager::new(&path);
+        assert_eq!(pager.lines(2050, 2).unwrap(), ["2050", "2051"]);
+        assert_eq!(pager.lines(2099, 1).unwrap(), ["2099"]);
+        assert_eq!(pager.lines(1025, 1).unwrap(), ["1025"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
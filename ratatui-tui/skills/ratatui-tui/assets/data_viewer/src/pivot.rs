@@ -0,0 +1,255 @@
+//! Group-by and aggregate (pivot) tables
+//!
+//! A pivot groups the rows of a table by one or more columns and computes
+//! aggregates over other columns. Every output row remembers which input rows
+//! it was built from so the view can drill back down into a group.
+
+use crate::expr::Scalar;
+use std::collections::{BTreeMap, HashSet};
+
+/// Aggregate function applied to a column within each group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    DistinctCount,
+}
+
+impl Aggregate {
+    /// All aggregates, in the order they are offered in the pivot editor
+    pub const ALL: [Aggregate; 6] = [
+        Aggregate::Count,
+        Aggregate::Sum,
+        Aggregate::Avg,
+        Aggregate::Min,
+        Aggregate::Max,
+        Aggregate::DistinctCount,
+    ];
+
+    /// Name used in output column headers, e.g. `sum(bytes)`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::DistinctCount => "distinct",
+        }
+    }
+
+    /// Key that toggles this aggregate in the pivot editor
+    pub fn key(&self) -> char {
+        match self {
+            Aggregate::Count => 'c',
+            Aggregate::Sum => 's',
+            Aggregate::Avg => 'a',
+            Aggregate::Min => 'n',
+            Aggregate::Max => 'x',
+            Aggregate::DistinctCount => 'd',
+        }
+    }
+
+    /// Compute the aggregate over the given cell values, as cell text
+    pub fn apply<'a>(&self, values: impl Iterator<Item = &'a str>) -> String {
+        self.value(values).to_cell()
+    }
+
+    /// Compute the aggregate over the given cell values: a number, or for
+    /// min and max over text the smallest or largest text; NULL when there
+    /// is nothing to aggregate
+    pub fn value<'a>(&self, values: impl Iterator<Item = &'a str>) -> Scalar {
+        let values: Vec<&str> = values.filter(|v| !v.is_empty()).collect();
+        let numbers = || values.iter().filter_map(|v| v.trim().parse::<f64>().ok());
+
+        match self {
+            Aggregate::Count => Scalar::Num(values.len() as f64),
+            Aggregate::DistinctCount => {
+                Scalar::Num(values.iter().collect::<HashSet<_>>().len() as f64)
+            }
+            Aggregate::Sum => Scalar::Num(numbers().sum()),
+            Aggregate::Avg => {
+                let (sum, n) = numbers().fold((0.0, 0usize), |(s, n), x| (s + x, n + 1));
+                if n == 0 {
+                    Scalar::Null
+                } else {
+                    Scalar::Num(sum / n as f64)
+                }
+            }
+            Aggregate::Min | Aggregate::Max => {
+                let all_numeric = values.iter().all(|v| v.trim().parse::<f64>().is_ok());
+                if all_numeric {
+                    let fold = if *self == Aggregate::Min {
+                        f64::min
+                    } else {
+                        f64::max
+                    };
+                    numbers().reduce(fold).map_or(Scalar::Null, Scalar::Num)
+                } else if *self == Aggregate::Min {
+                    values
+                        .iter()
+                        .min()
+                        .map_or(Scalar::Null, |v| Scalar::from_cell(v))
+                } else {
+                    values
+                        .iter()
+                        .max()
+                        .map_or(Scalar::Null, |v| Scalar::from_cell(v))
+                }
+            }
+        }
+    }
+}
+
+/// Which columns to group by and which aggregates to compute
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PivotSpec {
+    /// Indices of the group-by columns, in grouping order
+    pub group_by: Vec<usize>,
+    /// Aggregates to compute, as (function, column index)
+    pub aggregates: Vec<(Aggregate, usize)>,
+}
+
+/// Result of applying a [`PivotSpec`] to a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PivotTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Indices of the input rows that make up each output row
+    pub groups: Vec<Vec<usize>>,
+}
+
+/// Group `rows` according to `spec` and compute its aggregates
+///
+/// With no aggregates selected, a row count per group is produced.
+pub fn pivot(headers: &[String], rows: &[Vec<String>], spec: &PivotSpec) -> PivotTable {
+    fn cell(row: &[String], col: usize) -> &str {
+        row.get(col).map(String::as_str).unwrap_or("")
+    }
+
+    let mut groups: BTreeMap<Vec<String>, Vec<usize>> = BTreeMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        let key = spec
+            .group_by
+            .iter()
+            .map(|&col| cell(row, col).to_string())
+            .collect();
+        groups.entry(key).or_default().push(i);
+    }
+
+    let mut out_headers: Vec<String> = spec
+        .group_by
+        .iter()
+        .map(|&col| headers.get(col).cloned().unwrap_or_default())
+        .collect();
+    if spec.aggregates.is_empty() {
+        out_headers.push("count".to_string());
+    }
+    out_headers.extend(spec.aggregates.iter().map(|(agg, col)| {
+        format!(
+            "{}({})",
+            agg.name(),
+            headers.get(*col).map(String::as_str).unwrap_or("?")
+        )
+    }));
+
+    let mut out_rows = Vec::with_capacity(groups.len());
+    let mut out_groups = Vec::with_capacity(groups.len());
+    for (key, members) in groups {
+        let mut row = key;
+        if spec.aggregates.is_empty() {
+            row.push(members.len().to_string());
+        }
+        for (agg, col) in &spec.aggregates {
+            row.push(agg.apply(members.iter().map(|&i| cell(&rows[i], *col))));
+        }
+        out_rows.push(row);
+        out_groups.push(members);
+    }
+
+    PivotTable {
+        headers: out_headers,
+        rows: out_rows,
+        groups: out_groups,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = ["host", "status", "bytes"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let rows = [
+            ["a", "200", "100"],
+            ["b", "500", "50"],
+            ["a", "500", "300"],
+            ["a", "200", ""],
+        ]
+        .iter()
+        .map(|r| r.iter().map(|s| s.to_string()).collect())
+        .collect();
+        (headers, rows)
+    }
+
+    #[test]
+    fn test_pivot_count_by_group() {
+        let (headers, rows) = sample();
+        let spec = PivotSpec {
+            group_by: vec![0],
+            aggregates: vec![],
+        };
+
+        let table = pivot(&headers, &rows, &spec);
+        assert_eq!(table.headers, vec!["host", "count"]);
+        assert_eq!(table.rows, vec![vec!["a", "3"], vec!["b", "1"]]);
+        assert_eq!(table.groups, vec![vec![0, 2, 3], vec![1]]);
+    }
+
+    #[test]
+    fn test_pivot_aggregates() {
+        let (headers, rows) = sample();
+        let spec = PivotSpec {
+            group_by: vec![0],
+            aggregates: Aggregate::ALL.iter().map(|a| (*a, 2)).collect(),
+        };
+
+        let table = pivot(&headers, &rows, &spec);
+        assert_eq!(
+            table.headers,
+            vec![
+                "host",
+                "count(bytes)",
+                "sum(bytes)",
+                "avg(bytes)",
+                "min(bytes)",
+                "max(bytes)",
+                "distinct(bytes)"
+            ]
+        );
+        assert_eq!(
+            table.rows[0],
+            vec!["a", "2", "400", "200", "100", "300", "2"]
+        );
+    }
+
+    #[test]
+    fn test_pivot_multiple_group_columns() {
+        let (headers, rows) = sample();
+        let spec = PivotSpec {
+            group_by: vec![0, 1],
+            aggregates: vec![(Aggregate::Sum, 2)],
+        };
+
+        let table = pivot(&headers, &rows, &spec);
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.rows[1], vec!["a", "500", "300"]);
+        assert_eq!(table.groups[1], vec![2]);
+    }
+}
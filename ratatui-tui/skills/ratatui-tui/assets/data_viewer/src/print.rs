@@ -0,0 +1,276 @@
+//! Headless `--print` mode for scripts and CI
+//!
+//! `data_viewer --print FILE` loads the file as the viewer would and writes
+//! it to stdout instead of opening the terminal UI: as the same box-drawn
+//! table (drawn into an off-screen buffer at a fixed width), as a plain
+//! table, or converted to CSV, JSON or Markdown.
+
+use anyhow::{bail, Context, Result};
+use ratatui::buffer::Buffer;
+use serde_json::{Map, Value};
+use std::{io::Write, path::PathBuf};
+use unicode_width::UnicodeWidthStr;
+
+pub const USAGE: &str = "\
+Usage: data_viewer [--print FILE [OPTIONS]]
+
+Without --print, opens the viewer in the current directory.
+
+Options:
+  --print FILE        write FILE to stdout instead of opening the viewer
+  --columns A,B       only these columns, in this order
+  --filter EXPR       only rows matching a filter expression, e.g. 'bytes > 1024'
+  --limit N           at most N rows
+  --format FORMAT     table (default), plain, csv, json or markdown
+  --width N           table width in columns (default: the terminal's, or 100)
+  -h, --help          show this help
+";
+
+/// What the command line asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Interactive,
+    Help,
+    Print(PrintOptions),
+}
+
+/// How `--print` writes the rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Box-drawn table, as shown in the viewer
+    #[default]
+    Table,
+    /// Table without borders
+    Plain,
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "table" => Format::Table,
+            "plain" => Format::Plain,
+            "csv" => Format::Csv,
+            "json" => Format::Json,
+            "markdown" | "md" => Format::Markdown,
+            _ => bail!(
+                "Unknown format '{}' (expected table, plain, csv, json or markdown)",
+                name
+            ),
+        })
+    }
+}
+
+/// Options of a `--print` run
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PrintOptions {
+    pub file: PathBuf,
+    pub columns: Option<Vec<String>>,
+    pub filter: Option<String>,
+    pub limit: Option<usize>,
+    pub format: Format,
+    pub width: Option<u16>,
+}
+
+/// Parse the command line arguments, without the program name
+///
+/// Values follow their flag either as the next argument or after `=`.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command> {
+    let mut file = None;
+    let mut options = PrintOptions::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value)),
+            _ => (arg.clone(), None),
+        };
+        if flag == "-h" || flag == "--help" {
+            return Ok(Command::Help);
+        }
+        let mut value = || -> Result<String> {
+            match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args
+                    .next()
+                    .with_context(|| format!("{} needs a value", flag)),
+            }
+        };
+        match flag.as_str() {
+            "--print" => file = Some(PathBuf::from(value()?)),
+            "--columns" => {
+                let columns = value()?.split(',').map(|c| c.trim().to_string()).collect();
+                options.columns = Some(columns);
+            }
+            "--filter" => options.filter = Some(value()?),
+            "--limit" => {
+                let limit = value()?;
+                let limit = limit
+                    .parse()
+                    .with_context(|| format!("--limit expects a number, not '{}'", limit))?;
+                options.limit = Some(limit);
+            }
+            "--format" => options.format = Format::parse(&value()?)?,
+            "--width" => {
+                let width = value()?;
+                let width = width
+                    .parse()
+                    .ok()
+                    .filter(|&w| w >= 20)
+                    .with_context(|| format!("--width expects 20 or more, not '{}'", width))?;
+                options.width = Some(width);
+            }
+            _ => bail!("Unknown argument '{}'\n\n{}", arg, USAGE),
+        }
+    }
+
+    match file {
+        Some(file) => Ok(Command::Print(PrintOptions { file, ..options })),
+        None if options != PrintOptions::default() => {
+            bail!("--columns, --filter, --limit, --format and --width need --print FILE")
+        }
+        None => Ok(Command::Interactive),
+    }
+}
+
+/// Text of a rendered buffer, one string per line without trailing spaces
+pub fn buffer_lines(buffer: &Buffer) -> Vec<String> {
+    let area = buffer.area;
+    (area.top()..area.bottom())
+        .map(|y| {
+            let mut line = String::new();
+            let mut x = area.left();
+            while x < area.right() {
+                let symbol = buffer[(x, y)].symbol();
+                line.push_str(symbol);
+                // Cells covered by a wide character hold a space to skip
+                x += symbol.width().max(1) as u16;
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Write the rows as CSV with a header line
+pub fn write_csv(out: impl Write, headers: &[String], rows: &[Vec<String>]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record(headers)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write the rows as a JSON array of objects
+///
+/// Cells holding a JSON number or boolean are written as one; everything
+/// else stays a string.
+pub fn write_json(mut out: impl Write, headers: &[String], rows: &[Vec<String>]) -> Result<()> {
+    let items: Vec<Value> = rows
+        .iter()
+        .map(|row| {
+            let object: Map<String, Value> = headers
+                .iter()
+                .zip(row)
+                .map(|(header, cell)| {
+                    let value = match serde_json::from_str::<Value>(cell) {
+                        Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+                        _ => Value::String(cell.clone()),
+                    };
+                    (header.clone(), value)
+                })
+                .collect();
+            Value::Object(object)
+        })
+        .collect();
+    serde_json::to_writer_pretty(&mut out, &items)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Write the rows as a GitHub-flavored Markdown table
+pub fn write_markdown(mut out: impl Write, headers: &[String], rows: &[Vec<String>]) -> Result<()> {
+    let escape = |cell: &str| cell.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    writeln!(out, "{}", line(headers.iter().map(|h| escape(h)).collect()))?;
+    writeln!(
+        out,
+        "{}",
+        line(headers.iter().map(|_| "---".to_string()).collect())
+    )?;
+    for row in rows {
+        writeln!(out, "{}", line(row.iter().map(|c| escape(c)).collect()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{layout::Rect, style::Style};
+
+    fn args(line: &str) -> Result<Command> {
+        parse_args(line.split_whitespace().map(str::to_string))
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(args("").unwrap(), Command::Interactive);
+        assert_eq!(args("--limit 3 -h").unwrap(), Command::Help);
+        let Command::Print(options) =
+            args("--print a.json --columns host,bytes --limit=20 --format csv").unwrap()
+        else {
+            panic!("expected --print");
+        };
+        assert_eq!(options.file, PathBuf::from("a.json"));
+        assert_eq!(options.columns, Some(strings(&["host", "bytes"])));
+        assert_eq!(options.limit, Some(20));
+        assert_eq!(options.format, Format::Csv);
+        assert_eq!(options.width, None);
+
+        assert!(args("--limit 3").is_err());
+        assert!(args("--print a.json --format xml").is_err());
+        assert!(args("--print a.json --width 5").is_err());
+        assert!(args("--print").is_err());
+        assert!(args("a.json").is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        let headers = strings(&["name", "n"]);
+        let rows = vec![strings(&["a|b", "3"]), strings(&["007", "true"])];
+
+        let mut out = Vec::new();
+        write_markdown(&mut out, &headers, &rows).unwrap();
+        let markdown = String::from_utf8(out).unwrap();
+        assert_eq!(
+            markdown,
+            "| name | n |\n| --- | --- |\n| a\\|b | 3 |\n| 007 | true |\n"
+        );
+
+        let mut out = Vec::new();
+        write_json(&mut out, &headers, &rows).unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{"name": "a|b", "n": 3}, {"name": "007", "n": true}])
+        );
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &headers, &rows).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "name,n\na|b,3\n007,true\n");
+    }
+
+    #[test]
+    fn test_buffer_lines_skip_wide_characters() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 8, 2));
+        buffer.set_string(0, 0, "日本 x", Style::default());
+        assert_eq!(buffer_lines(&buffer), ["日本 x", ""]);
+    }
+}
@@ -0,0 +1,661 @@
+//! JSON Schema validation of table rows
+//!
+//! Covers the keywords data files are usually described with: `type`,
+//! `enum`, `const`, the number, string, array and object constraints,
+//! `allOf`/`anyOf`/`oneOf`/`not`, `if`/`then`/`else` and local `$ref`s such
+//! as `#/definitions/host` or `#/$defs/host`. `format` is treated as an
+//! annotation, which is the specification's default, and references to other
+//! documents are not followed.
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Nesting of `$ref`s and subschemas beyond which validation gives up, so
+/// recursive schemas cannot loop forever
+const MAX_DEPTH: usize = 64;
+
+/// A loaded schema with its regular expressions compiled
+#[derive(Debug)]
+pub struct Schema {
+    root: Value,
+    /// Compiled `pattern` and `patternProperties` expressions
+    patterns: HashMap<String, Regex>,
+}
+
+/// One failed keyword
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// JSON pointer to the offending value within the row, e.g. `/address/zip`
+    pub instance_path: String,
+    /// Keyword that failed, e.g. `required`
+    pub keyword: &'static str,
+    pub message: String,
+    /// Top-level property (table column) the error belongs to, if any
+    pub column: Option<String>,
+}
+
+/// The `<name>.schema.json` next to a data file, if there is one
+///
+/// The name is the file name up to its first dot, so `events.json.gz` and
+/// `events.ndjson` both use `events.schema.json`.
+pub fn sibling(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy();
+    if file_name.ends_with(".schema.json") {
+        return None;
+    }
+    let name = file_name.split('.').next()?;
+    let candidate = path.with_file_name(format!("{}.schema.json", name));
+    candidate.is_file().then_some(candidate)
+}
+
+impl Schema {
+    /// Read a schema file
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let root = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Schema::new(root)
+    }
+
+    pub fn new(root: Value) -> Result<Self> {
+        if !root.is_object() && !root.is_boolean() {
+            bail!("A schema must be an object or a boolean");
+        }
+        let mut patterns = HashMap::new();
+        collect_patterns(&root, &mut patterns)?;
+        Ok(Schema { root, patterns })
+    }
+
+    /// Every failed keyword for one row, empty if the row is valid
+    pub fn validate(&self, instance: &Value) -> Vec<SchemaError> {
+        let mut errors = Vec::new();
+        self.check(&self.root, instance, &mut Vec::new(), &mut errors, 0);
+        errors
+    }
+
+    fn is_valid(&self, schema: &Value, instance: &Value, depth: usize) -> bool {
+        let mut errors = Vec::new();
+        self.check(schema, instance, &mut Vec::new(), &mut errors, depth);
+        errors.is_empty()
+    }
+
+    /// Check `instance` (found at `path`) against `schema`
+    fn check(
+        &self,
+        schema: &Value,
+        instance: &Value,
+        path: &mut Vec<String>,
+        errors: &mut Vec<SchemaError>,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                errors.push(error(path, "false", "No value is allowed here".to_string()));
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        // Keywords next to a reference apply as well, as in newer drafts
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            match self.resolve(reference) {
+                Some(target) => self.check(target, instance, path, errors, depth + 1),
+                None => errors.push(error(
+                    path,
+                    "$ref",
+                    format!("Cannot resolve reference {}", reference),
+                )),
+            }
+        }
+
+        let mut fail = |keyword: &'static str, message: String| {
+            errors.push(error(path, keyword, message));
+        };
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|t| has_type(instance, t)) {
+                fail(
+                    "type",
+                    format!(
+                        "Expected {}, got {}",
+                        types.join(" or "),
+                        type_name(instance)
+                    ),
+                );
+            }
+        }
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.iter().any(|v| json_equal(v, instance)) {
+                fail(
+                    "enum",
+                    format!("{} is not one of the allowed values", short(instance)),
+                );
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if !json_equal(expected, instance) {
+                fail("const", format!("Expected {}", short(expected)));
+            }
+        }
+
+        match instance {
+            Value::Number(n) => check_number(schema, n.as_f64().unwrap_or(f64::NAN), &mut fail),
+            Value::String(s) => {
+                let length = s.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if length < min {
+                        fail("minLength", format!("Shorter than {} characters", min));
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if length > max {
+                        fail("maxLength", format!("Longer than {} characters", max));
+                    }
+                }
+                if let Some(Value::String(pattern)) = schema.get("pattern") {
+                    if self.patterns.get(pattern).is_some_and(|re| !re.is_match(s)) {
+                        fail(
+                            "pattern",
+                            format!("{} does not match {}", short(instance), pattern),
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        self.check_rest(schema, instance, path, errors, depth);
+    }
+
+    /// Array, object and combining keywords, which look into subschemas
+    fn check_rest(
+        &self,
+        schema: &Map<String, Value>,
+        instance: &Value,
+        path: &mut Vec<String>,
+        errors: &mut Vec<SchemaError>,
+        depth: usize,
+    ) {
+        match instance {
+            Value::Array(items) => self.check_array(schema, items, path, errors, depth),
+            Value::Object(object) => self.check_object(schema, object, path, errors, depth),
+            _ => {}
+        }
+
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for subschema in all {
+                self.check(subschema, instance, path, errors, depth + 1);
+            }
+        }
+        if let Some(Value::Array(any)) = schema.get("anyOf") {
+            if !any.iter().any(|s| self.is_valid(s, instance, depth + 1)) {
+                errors.push(error(
+                    path,
+                    "anyOf",
+                    "Matches none of the allowed schemas".to_string(),
+                ));
+            }
+        }
+        if let Some(Value::Array(one)) = schema.get("oneOf") {
+            let matching = one
+                .iter()
+                .filter(|s| self.is_valid(s, instance, depth + 1))
+                .count();
+            if matching != 1 {
+                errors.push(error(
+                    path,
+                    "oneOf",
+                    format!("Matches {} of the schemas instead of exactly one", matching),
+                ));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.is_valid(not, instance, depth + 1) {
+                errors.push(error(
+                    path,
+                    "not",
+                    "Matches a schema it must not match".to_string(),
+                ));
+            }
+        }
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.is_valid(condition, instance, depth + 1) {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+            if let Some(branch) = branch {
+                self.check(branch, instance, path, errors, depth + 1);
+            }
+        }
+    }
+
+    fn check_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        path: &mut Vec<String>,
+        errors: &mut Vec<SchemaError>,
+        depth: usize,
+    ) {
+        let count = items.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if count < min {
+                errors.push(error(path, "minItems", format!("Fewer than {} items", min)));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if count > max {
+                errors.push(error(path, "maxItems", format!("More than {} items", max)));
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicate = (1..items.len()).any(|i| {
+                items[..i]
+                    .iter()
+                    .any(|earlier| json_equal(earlier, &items[i]))
+            });
+            if duplicate {
+                errors.push(error(
+                    path,
+                    "uniqueItems",
+                    "Items are not unique".to_string(),
+                ));
+            }
+        }
+        if let Some(contains) = schema.get("contains") {
+            if !items
+                .iter()
+                .any(|item| self.is_valid(contains, item, depth + 1))
+            {
+                errors.push(error(
+                    path,
+                    "contains",
+                    "No item matches the contains schema".to_string(),
+                ));
+            }
+        }
+
+        // Positional schemas come from prefixItems (2020-12) or an items array
+        // (older drafts); the rest of the items use items or additionalItems
+        let (prefix, rest) = match (schema.get("prefixItems"), schema.get("items")) {
+            (Some(Value::Array(prefix)), rest) => (prefix.as_slice(), rest),
+            (_, Some(Value::Array(prefix))) => (prefix.as_slice(), schema.get("additionalItems")),
+            (_, rest) => (&[][..], rest),
+        };
+        for (i, item) in items.iter().enumerate() {
+            let Some(subschema) = prefix.get(i).or(rest) else {
+                break;
+            };
+            path.push(i.to_string());
+            self.check(subschema, item, path, errors, depth + 1);
+            path.pop();
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &mut Vec<String>,
+        errors: &mut Vec<SchemaError>,
+        depth: usize,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    let mut missing = error(
+                        path,
+                        "required",
+                        format!("Missing required property '{}'", name),
+                    );
+                    // A missing top-level property belongs to its column
+                    if path.is_empty() {
+                        missing.column = Some(name.to_string());
+                    }
+                    errors.push(missing);
+                }
+            }
+        }
+        let count = object.len() as u64;
+        if let Some(min) = schema.get("minProperties").and_then(Value::as_u64) {
+            if count < min {
+                errors.push(error(
+                    path,
+                    "minProperties",
+                    format!("Fewer than {} properties", min),
+                ));
+            }
+        }
+        if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64) {
+            if count > max {
+                errors.push(error(
+                    path,
+                    "maxProperties",
+                    format!("More than {} properties", max),
+                ));
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let pattern_properties = schema.get("patternProperties").and_then(Value::as_object);
+        for (name, value) in object {
+            path.push(name.clone());
+            let mut matched = false;
+            if let Some(subschema) = properties.and_then(|p| p.get(name)) {
+                matched = true;
+                self.check(subschema, value, path, errors, depth + 1);
+            }
+            for (pattern, subschema) in pattern_properties.into_iter().flatten() {
+                if self
+                    .patterns
+                    .get(pattern)
+                    .is_some_and(|re| re.is_match(name))
+                {
+                    matched = true;
+                    self.check(subschema, value, path, errors, depth + 1);
+                }
+            }
+            if !matched {
+                match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => errors.push(error(
+                        path,
+                        "additionalProperties",
+                        format!("Property '{}' is not allowed", name),
+                    )),
+                    Some(subschema) => self.check(subschema, value, path, errors, depth + 1),
+                    None => {}
+                }
+            }
+            if let Some(names) = schema.get("propertyNames") {
+                if !self.is_valid(names, &Value::String(name.clone()), depth + 1) {
+                    errors.push(error(
+                        path,
+                        "propertyNames",
+                        format!("Property name '{}' is not allowed", name),
+                    ));
+                }
+            }
+            path.pop();
+        }
+    }
+
+    /// Find the subschema a local reference such as `#/$defs/host` points to
+    fn resolve(&self, reference: &str) -> Option<&Value> {
+        let pointer = reference.strip_prefix('#')?;
+        if pointer.is_empty() {
+            return Some(&self.root);
+        }
+        self.root.pointer(pointer)
+    }
+}
+
+/// Number constraints, including the draft-04 boolean `exclusiveMinimum`
+fn check_number(schema: &Map<String, Value>, n: f64, fail: &mut impl FnMut(&'static str, String)) {
+    let exclusive = |key: &str| schema.get(key) == Some(&Value::Bool(true));
+    if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+        if exclusive("exclusiveMinimum") && n <= min {
+            fail("minimum", format!("{} is not greater than {}", n, min));
+        } else if n < min {
+            fail("minimum", format!("{} is less than the minimum {}", n, min));
+        }
+    }
+    if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+        if exclusive("exclusiveMaximum") && n >= max {
+            fail("maximum", format!("{} is not less than {}", n, max));
+        } else if n > max {
+            fail("maximum", format!("{} is more than the maximum {}", n, max));
+        }
+    }
+    if let Some(min) = schema.get("exclusiveMinimum").and_then(Value::as_f64) {
+        if n <= min {
+            fail(
+                "exclusiveMinimum",
+                format!("{} is not greater than {}", n, min),
+            );
+        }
+    }
+    if let Some(max) = schema.get("exclusiveMaximum").and_then(Value::as_f64) {
+        if n >= max {
+            fail(
+                "exclusiveMaximum",
+                format!("{} is not less than {}", n, max),
+            );
+        }
+    }
+    if let Some(divisor) = schema.get("multipleOf").and_then(Value::as_f64) {
+        let quotient = n / divisor;
+        if divisor > 0.0 && (quotient - quotient.round()).abs() > 1e-9 {
+            fail(
+                "multipleOf",
+                format!("{} is not a multiple of {}", n, divisor),
+            );
+        }
+    }
+}
+
+/// Build an error at `path`, attributed to the column its first segment names
+fn error(path: &[String], keyword: &'static str, message: String) -> SchemaError {
+    let instance_path = path
+        .iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect();
+    SchemaError {
+        instance_path,
+        keyword,
+        message,
+        column: path.first().cloned(),
+    }
+}
+
+/// Whether a value is of a JSON Schema type
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Equality as JSON Schema defines it, where `1` and `1.0` are the same number
+fn json_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        (Value::Array(x), Value::Array(y)) => {
+            x.len() == y.len() && x.iter().zip(y).all(|(x, y)| json_equal(x, y))
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            x.len() == y.len()
+                && x.iter()
+                    .all(|(k, v)| y.get(k).is_some_and(|w| json_equal(v, w)))
+        }
+        _ => a == b,
+    }
+}
+
+/// A value as shown in messages, cut short if long
+fn short(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() > 40 {
+        format!("{}…", text.chars().take(39).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Compile every regular expression in the schema up front, so a bad one is
+/// reported when the schema is loaded
+fn collect_patterns(value: &Value, patterns: &mut HashMap<String, Regex>) -> Result<()> {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object {
+                match (key.as_str(), child) {
+                    // Literal values, not schemas
+                    ("enum" | "const" | "default" | "examples", _) => continue,
+                    ("pattern", Value::String(pattern)) => compile(pattern, patterns)?,
+                    ("patternProperties", Value::Object(properties)) => {
+                        for pattern in properties.keys() {
+                            compile(pattern, patterns)?;
+                        }
+                    }
+                    _ => {}
+                }
+                collect_patterns(child, patterns)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_patterns(item, patterns)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn compile(pattern: &str, patterns: &mut HashMap<String, Regex>) -> Result<()> {
+    if !patterns.contains_key(pattern) {
+        let re = Regex::new(pattern).with_context(|| format!("Invalid pattern {}", pattern))?;
+        patterns.insert(pattern.to_string(), re);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn host_schema() -> Schema {
+        Schema::new(json!({
+            "$defs": {
+                "port": {"type": "integer", "minimum": 1, "maximum": 65535}
+            },
+            "type": "object",
+            "required": ["host", "port"],
+            "properties": {
+                "host": {"type": "string", "pattern": "^[a-z]+[0-9]*$"},
+                "port": {"$ref": "#/$defs/port"},
+                "role": {"enum": ["web", "db"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true}
+            },
+            "additionalProperties": false
+        }))
+        .unwrap()
+    }
+
+    fn keywords(errors: &[SchemaError]) -> Vec<(&str, &str)> {
+        errors
+            .iter()
+            .map(|e| (e.instance_path.as_str(), e.keyword))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_rows() {
+        let schema = host_schema();
+        assert!(schema
+            .validate(&json!({"host": "web1", "port": 80.0, "role": "web"}))
+            .is_empty());
+
+        let errors = schema.validate(&json!({
+            "host": "Web-1",
+            "port": 70000,
+            "role": "cache",
+            "tags": ["a", 1, "a"],
+            "extra": true
+        }));
+        assert_eq!(
+            keywords(&errors),
+            [
+                ("/extra", "additionalProperties"),
+                ("/host", "pattern"),
+                ("/port", "maximum"),
+                ("/role", "enum"),
+                ("/tags", "uniqueItems"),
+                ("/tags/1", "type"),
+            ]
+        );
+        assert_eq!(errors[5].column.as_deref(), Some("tags"));
+
+        let errors = schema.validate(&json!({"host": "db1"}));
+        assert_eq!(keywords(&errors), [("", "required")]);
+        assert_eq!(errors[0].column.as_deref(), Some("port"));
+        assert_eq!(keywords(&schema.validate(&json!([1]))), [("", "type")]);
+    }
+
+    #[test]
+    fn test_combinators_and_conditionals() {
+        let schema = Schema::new(json!({
+            "anyOf": [{"type": "string"}, {"type": "number", "multipleOf": 0.5}],
+            "not": {"const": "forbidden"},
+            "if": {"type": "string"},
+            "then": {"minLength": 3},
+            "else": {"exclusiveMinimum": 0}
+        }))
+        .unwrap();
+        assert!(schema.validate(&json!("abc")).is_empty());
+        assert!(schema.validate(&json!(2.5)).is_empty());
+        assert_eq!(
+            keywords(&schema.validate(&json!("ab"))),
+            [("", "minLength")]
+        );
+        assert_eq!(
+            keywords(&schema.validate(&json!("forbidden"))),
+            [("", "not")]
+        );
+        assert_eq!(
+            keywords(&schema.validate(&json!(-0.3))),
+            [("", "anyOf"), ("", "exclusiveMinimum")]
+        );
+
+        let one_of = Schema::new(json!({"oneOf": [{"type": "integer"}, {"minimum": 0}]})).unwrap();
+        assert!(one_of.validate(&json!(-1)).is_empty());
+        assert_eq!(keywords(&one_of.validate(&json!(1))), [("", "oneOf")]);
+
+        assert!(Schema::new(json!({"pattern": "("})).is_err());
+        assert!(Schema::new(json!({"enum": ["("]})).is_ok());
+        assert!(Schema::new(json!(3)).is_err());
+    }
+
+    #[test]
+    fn test_sibling_schema() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_schema_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let schema = dir.join("hosts.schema.json");
+        fs::write(&schema, "{}").unwrap();
+
+        assert_eq!(sibling(&dir.join("hosts.json")), Some(schema.clone()));
+        assert_eq!(sibling(&dir.join("hosts.ndjson.gz")), Some(schema.clone()));
+        assert_eq!(sibling(&dir.join("events.json")), None);
+        assert_eq!(sibling(&schema), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
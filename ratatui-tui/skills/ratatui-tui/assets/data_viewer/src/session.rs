@@ -0,0 +1,149 @@
+//! Session state kept between runs
+//!
+//! Saved as JSON in `$XDG_STATE_HOME/data_viewer/session.json` (falling back
+//! to `~/.local/state`): the last directory, recently opened files and the
+//! view state of each of those files.
+
+use crate::{view::ViewSettings, ActiveTab};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Recently opened files remembered, most recent first
+pub const RECENT_LIMIT: usize = 20;
+
+/// How a file was last shown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileState {
+    /// Selected display row in the table
+    pub selected: usize,
+    pub tab: ActiveTab,
+    pub view: ViewSettings,
+    /// JSON path used to pick the array to tabulate
+    pub json_path: Option<String>,
+    /// Sheet of a workbook to tabulate
+    pub sheet: Option<String>,
+    /// JSON Schema chosen to validate the rows with
+    pub schema: Option<PathBuf>,
+}
+
+impl Default for FileState {
+    fn default() -> Self {
+        FileState {
+            selected: 0,
+            tab: ActiveTab::Table,
+            view: ViewSettings::default(),
+            json_path: None,
+            sheet: None,
+            schema: None,
+        }
+    }
+}
+
+/// Everything remembered between runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub last_dir: Option<PathBuf>,
+    /// Most recent first
+    pub recent_files: Vec<PathBuf>,
+    /// View state of the recent files
+    pub files: BTreeMap<PathBuf, FileState>,
+}
+
+impl Session {
+    /// Location of the session file for the current user
+    pub fn default_path() -> Option<PathBuf> {
+        state_file(std::env::var_os("XDG_STATE_HOME"), std::env::var_os("HOME"))
+    }
+
+    /// Read a session file; a missing or unreadable file gives an empty session
+    pub fn load(path: &Path) -> Self {
+        fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the session file, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create state directory")?;
+        }
+        let json = serde_json::to_vec_pretty(self).context("Failed to encode session")?;
+        // Write then rename so a crash never leaves a half-written file
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json).context("Failed to write session")?;
+        fs::rename(&temp, path).context("Failed to write session")?;
+        Ok(())
+    }
+
+    /// Move a file to the front of the recent list, forgetting the view state
+    /// of files that fall off the end
+    pub fn record_open(&mut self, file: &Path) {
+        self.recent_files.retain(|f| f != file);
+        self.recent_files.insert(0, file.to_path_buf());
+        self.recent_files.truncate(RECENT_LIMIT);
+        let recent = &self.recent_files;
+        self.files.retain(|f, _| recent.contains(f));
+    }
+}
+
+/// Session file path from the XDG state directory or the home directory
+fn state_file(xdg_state_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    let base = match xdg_state_home.filter(|dir| Path::new(dir).is_absolute()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(home?).join(".local").join("state"),
+    };
+    Some(base.join("data_viewer").join("session.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_file_location() {
+        assert_eq!(
+            state_file(Some("/x/state".into()), Some("/home/u".into())),
+            Some(PathBuf::from("/x/state/data_viewer/session.json"))
+        );
+        // Relative XDG paths are invalid and ignored
+        assert_eq!(
+            state_file(Some("state".into()), Some("/home/u".into())),
+            Some(PathBuf::from(
+                "/home/u/.local/state/data_viewer/session.json"
+            ))
+        );
+        assert_eq!(state_file(None, None), None);
+    }
+
+    #[test]
+    fn test_record_open_and_round_trip() {
+        let mut session = Session::default();
+        for i in 0..RECENT_LIMIT + 2 {
+            let file = PathBuf::from(format!("/data/{}.csv", i));
+            session.files.insert(file.clone(), FileState::default());
+            session.record_open(&file);
+        }
+        session.record_open(Path::new("/data/5.csv"));
+        assert_eq!(session.recent_files.len(), RECENT_LIMIT);
+        assert_eq!(session.recent_files[0], PathBuf::from("/data/5.csv"));
+        assert_eq!(session.files.len(), RECENT_LIMIT);
+        assert!(!session.files.contains_key(Path::new("/data/0.csv")));
+
+        let dir = std::env::temp_dir().join(format!("data_viewer_session_{}", std::process::id()));
+        let path = dir.join("nested/session.json");
+        session.last_dir = Some(PathBuf::from("/data"));
+        session.save(&path).unwrap();
+        assert_eq!(Session::load(&path), session);
+        assert_eq!(Session::load(&dir.join("missing.json")), Session::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
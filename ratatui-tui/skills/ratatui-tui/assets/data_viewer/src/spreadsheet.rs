@@ -0,0 +1,332 @@
+//! Excel and OpenDocument spreadsheets
+//!
+//! A sheet is read with its cells converted from their native types: numbers
+//! without a spurious `.0`, dates in ISO form and formulas as their cached
+//! values. Merged cells are filled across their area, and header rows made
+//! of merged group titles over column titles become one header per column
+//! (`Q1 / Jan`).
+
+use anyhow::{Context, Result};
+use calamine::{open_workbook_auto, Data, Dimensions, Range, Reader, Sheets};
+use chrono::Timelike;
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Whether a file is a spreadsheet the viewer can read
+pub fn is_spreadsheet(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    matches!(ext.as_str(), "xlsx" | "xlsm" | "xlsb" | "xls" | "ods")
+}
+
+/// Name of the format shown in titles
+pub fn format_label(path: &Path) -> &'static str {
+    let is_ods = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("ods"));
+    if is_ods {
+        "OpenDocument"
+    } else {
+        "Excel"
+    }
+}
+
+/// One sheet as a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// The cells as read, tab-separated, for the raw view
+    pub raw: String,
+}
+
+fn open(path: &Path) -> Result<Sheets<BufReader<File>>> {
+    open_workbook_auto(path).with_context(|| format!("Failed to open {}", path.display()))
+}
+
+/// Names of the sheets of a workbook, in workbook order
+pub fn sheet_names(path: &Path) -> Result<Vec<String>> {
+    Ok(open(path)?.sheet_names())
+}
+
+/// Read a sheet, or the first one when no name is given
+pub fn read_sheet(path: &Path, name: Option<&str>) -> Result<Sheet> {
+    let mut workbook = open(path)?;
+    let names = workbook.sheet_names();
+    let name = match name {
+        Some(name) if names.iter().any(|n| n == name) => name.to_string(),
+        Some(name) => anyhow::bail!("The workbook has no sheet named {}", name),
+        None => names
+            .first()
+            .cloned()
+            .context("The workbook has no sheets")?,
+    };
+    let range = workbook
+        .worksheet_range(&name)
+        .with_context(|| format!("Failed to read sheet {}", name))?;
+    let merges = match &mut workbook {
+        Sheets::Xlsx(xlsx) => xlsx.worksheet_merge_cells(&name).and_then(Result::ok),
+        Sheets::Xls(xls) => xls.worksheet_merge_cells(&name),
+        _ => None,
+    };
+
+    let grid: Vec<Vec<String>> = range
+        .rows()
+        .map(|row| row.iter().map(cell_text).collect())
+        .collect();
+    let raw = grid
+        .iter()
+        .map(|row| row.join("\t").trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let merges = relative_merges(&range, merges.unwrap_or_default());
+    let (headers, rows) = table(grid, &merges);
+    Ok(Sheet {
+        name,
+        headers,
+        rows,
+        raw,
+    })
+}
+
+/// Text of a cell as shown in the table
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::Int(n) => n.to_string(),
+        // Spreadsheets store whole numbers as floats too
+        Data::Float(x) if x.fract() == 0.0 && x.abs() < 1e15 => (*x as i64).to_string(),
+        Data::Float(x) => x.to_string(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Bool(b) => b.to_string(),
+        Data::Error(e) => e.to_string(),
+        Data::DateTime(dt) if dt.is_duration() => match dt.as_duration() {
+            Some(d) => {
+                let seconds = d.num_seconds();
+                format!(
+                    "{}:{:02}:{:02}",
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )
+            }
+            None => dt.as_f64().to_string(),
+        },
+        Data::DateTime(dt) => match dt.as_datetime() {
+            // Times of day have no date part
+            Some(t) if dt.as_f64() < 1.0 => t.format("%H:%M:%S").to_string(),
+            Some(t) if t.num_seconds_from_midnight() == 0 => t.format("%Y-%m-%d").to_string(),
+            Some(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => dt.as_f64().to_string(),
+        },
+    }
+}
+
+/// Merged areas as (first row, first column, last row, last column) of
+/// the range's cells, dropping any outside it
+fn relative_merges(range: &Range<Data>, merges: Vec<Dimensions>) -> Vec<[usize; 4]> {
+    let Some((top, left)) = range.start() else {
+        return Vec::new();
+    };
+    merges
+        .into_iter()
+        .filter(|m| m.start.0 >= top && m.start.1 >= left)
+        .map(|m| {
+            [
+                (m.start.0 - top) as usize,
+                (m.start.1 - left) as usize,
+                (m.end.0 - top) as usize,
+                (m.end.1 - left) as usize,
+            ]
+        })
+        .collect()
+}
+
+/// Split a grid of cells into headers and rows
+///
+/// Merged areas take their top-left value throughout. Leading blank rows and
+/// title rows (a single cell, merged across columns or followed by a blank
+/// row) are dropped; a header row with cells merged across columns is a
+/// group row, and the row below it adds to the header. Columns without a
+/// title are named by their letter.
+fn table(mut grid: Vec<Vec<String>>, merges: &[[usize; 4]]) -> (Vec<String>, Vec<Vec<String>>) {
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 {
+        return (Vec::new(), Vec::new());
+    }
+    let spans_columns = |row: usize| {
+        merges
+            .iter()
+            .any(|&[top, left, bottom, right]| top <= row && row <= bottom && right > left)
+    };
+    let filled = |row: usize| grid[row].iter().filter(|cell| !cell.is_empty()).count();
+    let mut first = 0;
+    while first + 1 < grid.len() {
+        let title = filled(first) == 1 && (spans_columns(first) || filled(first + 1) == 0);
+        if filled(first) > 0 && !title {
+            break;
+        }
+        first += 1;
+    }
+
+    for &[top, left, bottom, right] in merges {
+        let Some(value) = grid.get(top).and_then(|row| row.get(left)).cloned() else {
+            continue;
+        };
+        for row in grid.iter_mut().take(bottom + 1).skip(top) {
+            for cell in row.iter_mut().take(right + 1).skip(left) {
+                cell.clone_from(&value);
+            }
+        }
+    }
+
+    let mut last = first;
+    while last + 1 < grid.len() && spans_columns(last) {
+        last += 1;
+    }
+
+    let headers = (0..width)
+        .map(|col| {
+            let mut parts: Vec<&str> = Vec::new();
+            for row in &grid[first..=last] {
+                let part = row.get(col).map_or("", |cell| cell.trim());
+                if !part.is_empty() && parts.last() != Some(&part) {
+                    parts.push(part);
+                }
+            }
+            if parts.is_empty() {
+                format!("column {}", column_letters(col))
+            } else {
+                parts.join(" / ")
+            }
+        })
+        .collect();
+    let rows = grid
+        .drain(last + 1..)
+        .map(|mut row| {
+            row.resize(width, String::new());
+            row
+        })
+        .collect();
+    (headers, rows)
+}
+
+/// Spreadsheet letters of a column numbered from 0: A, B, ... Z, AA, ...
+fn column_letters(col: usize) -> String {
+    let mut letters = Vec::new();
+    let mut n = col + 1;
+    while n > 0 {
+        letters.push(b'A' + ((n - 1) % 26) as u8);
+        n = (n - 1) / 26;
+    }
+    letters.reverse();
+    String::from_utf8(letters).unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::{fs, io::Write};
+
+    fn grid(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_merged_header_rows() {
+        // A title over a group row over column titles; Region spans both
+        // header rows and Q1/Q2 span two months each
+        let cells = grid(&[
+            &["Sales 2024", "", "", "", ""],
+            &["", "", "", "", ""],
+            &["Region", "Q1", "", "Q2", ""],
+            &["", "Jan", "Feb", "Apr", ""],
+            &["North", "1", "2", "3", "x"],
+        ]);
+        let merges = [[2, 0, 3, 0], [2, 1, 2, 2], [2, 3, 2, 4]];
+        let (headers, rows) = table(cells, &merges);
+        assert_eq!(
+            headers,
+            ["Region", "Q1 / Jan", "Q1 / Feb", "Q2 / Apr", "Q2"]
+        );
+        assert_eq!(rows, grid(&[&["North", "1", "2", "3", "x"]]));
+
+        let (headers, rows) = table(grid(&[&["id", ""], &["1", "a"]]), &[]);
+        assert_eq!(headers, ["id", "column B"]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(column_letters(27), "AB");
+    }
+
+    /// Write a minimal xlsx workbook with two sheets
+    pub(crate) fn write_xlsx(path: &Path) {
+        let files = [
+            (
+                "xl/workbook.xml",
+                r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Budget" sheetId="1" r:id="rId1"/><sheet name="Notes" sheetId="2" r:id="rId2"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet2.xml"/></Relationships>"#,
+            ),
+            (
+                "xl/styles.xml",
+                r#"<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><cellXfs count="2"><xf numFmtId="0"/><xf numFmtId="14" applyNumberFormat="1"/></cellXfs></styleSheet>"#,
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>
+<row r="1"><c r="A1" t="inlineStr"><is><t>Item</t></is></c><c r="B1" t="inlineStr"><is><t>Cost</t></is></c><c r="D1" t="inlineStr"><is><t>Due</t></is></c></row>
+<row r="2"><c r="B2" t="inlineStr"><is><t>Net</t></is></c><c r="C2" t="inlineStr"><is><t>Gross</t></is></c></row>
+<row r="3"><c r="A3" t="inlineStr"><is><t>Paper</t></is></c><c r="B3"><v>10</v></c><c r="C3"><f>B3*1.2</f><v>12.5</v></c><c r="D3" s="1"><v>45658</v></c></row>
+<row r="4"><c r="A4" t="inlineStr"><is><t>Ink</t></is></c><c r="B4" t="b"><v>1</v></c><c r="C4" t="e"><v>#DIV/0!</v></c></row>
+</sheetData><mergeCells count="3"><mergeCell ref="A1:A2"/><mergeCell ref="B1:C1"/><mergeCell ref="D1:D2"/></mergeCells></worksheet>"#,
+            ),
+            (
+                "xl/worksheets/sheet2.xml",
+                r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData><row r="1"><c r="A1" t="inlineStr"><is><t>note</t></is></c></row><row r="2"><c r="A2" t="inlineStr"><is><t>hello</t></is></c></row></sheetData></worksheet>"#,
+            ),
+        ];
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, content) in files {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_read_xlsx() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_xlsx_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("budget.xlsx");
+        write_xlsx(&path);
+
+        assert!(is_spreadsheet(&path));
+        assert_eq!(format_label(&path), "Excel");
+        assert_eq!(sheet_names(&path).unwrap(), ["Budget", "Notes"]);
+
+        let sheet = read_sheet(&path, None).unwrap();
+        assert_eq!(sheet.name, "Budget");
+        assert_eq!(sheet.headers, ["Item", "Cost / Net", "Cost / Gross", "Due"]);
+        assert_eq!(
+            sheet.rows,
+            grid(&[
+                &["Paper", "10", "12.5", "2025-01-01"],
+                &["Ink", "true", "#DIV/0!", ""]
+            ])
+        );
+        assert!(sheet.raw.starts_with("Item\tCost\t\tDue\n\tNet\tGross\n"));
+
+        let notes = read_sheet(&path, Some("Notes")).unwrap();
+        assert_eq!(notes.headers, ["note"]);
+        assert_eq!(notes.rows, grid(&[&["hello"]]));
+        assert!(read_sheet(&path, Some("Missing")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
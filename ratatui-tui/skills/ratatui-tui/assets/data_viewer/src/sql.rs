@@ -0,0 +1,575 @@
+//! SQL SELECT statements over loaded tables
+//!
+//! Supports `SELECT [DISTINCT] ... FROM source [WHERE ...] [GROUP BY ...]
+//! [HAVING ...] [ORDER BY ...] [LIMIT n [OFFSET m]]`, built on the
+//! expression language in [`crate::expr`]. The source is resolved by the
+//! caller, so `current` and other files can be looked up by name.
+
+use crate::expr::{Expr, ExprError, Parser, Scalar, TokenKind};
+use std::{cmp::Ordering, collections::HashMap};
+
+/// One entry of the select list
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    /// `*`: every column of the source
+    Wildcard { pos: usize },
+    /// An expression with its output column name
+    Expr { expr: Expr, name: String },
+}
+
+/// What an ORDER BY key refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderTarget {
+    /// 1-based position in the select list
+    Position(usize),
+    /// Expression evaluated per output row (or matched against an alias)
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderKey {
+    pub target: OrderTarget,
+    pub descending: bool,
+    pub pos: usize,
+}
+
+/// A parsed SELECT statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub distinct: bool,
+    pub items: Vec<SelectItem>,
+    /// Table name after FROM, e.g. `current` or `events.csv`
+    pub from: String,
+    /// Position of the table name, for errors while resolving it
+    pub from_pos: usize,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub order_by: Vec<OrderKey>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// Result of running a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Words that end a select item or table name rather than being part of it
+const CLAUSE_KEYWORDS: [&str; 7] = [
+    "from", "where", "group", "having", "order", "limit", "offset",
+];
+
+/// Parse a SELECT statement
+pub fn parse(input: &str) -> Result<Query, ExprError> {
+    let chars: Vec<char> = input.chars().collect();
+    let text = |start: usize, end: usize| -> String {
+        chars[start..end.min(chars.len())]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string()
+    };
+    let mut parser = Parser::new(input)?;
+
+    parser.expect_keyword("select")?;
+    let distinct = parser.eat_keyword("distinct");
+
+    let mut items = Vec::new();
+    loop {
+        let start = parser.peek().pos;
+        if parser.eat_symbol("*") {
+            items.push(SelectItem::Wildcard { pos: start });
+        } else if CLAUSE_KEYWORDS.iter().any(|k| parser.at_keyword(k)) {
+            return Err(parser.unexpected("Expected an expression"));
+        } else {
+            let expr = parser.parse_expr()?;
+            let name = if parser.eat_keyword("as") {
+                identifier(&mut parser, "Expected a column alias")?
+            } else {
+                match &parser.peek().kind {
+                    TokenKind::Ident(word)
+                        if !CLAUSE_KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)) =>
+                    {
+                        identifier(&mut parser, "Expected a column alias")?
+                    }
+                    TokenKind::QuotedIdent(_) => {
+                        identifier(&mut parser, "Expected a column alias")?
+                    }
+                    _ => text(start, parser.peek().pos),
+                }
+            };
+            items.push(SelectItem::Expr { expr, name });
+        }
+        if !parser.eat_symbol(",") {
+            break;
+        }
+    }
+
+    parser.expect_keyword("from")?;
+    let from_pos = parser.peek().pos;
+    let mut from = match &parser.peek().kind {
+        TokenKind::Str(name) => {
+            let name = name.clone();
+            parser.advance();
+            name
+        }
+        _ => identifier(&mut parser, "Expected a table name")?,
+    };
+    // Allow bare file names like `events.csv`
+    while parser.eat_symbol(".") {
+        from.push('.');
+        from.push_str(&identifier(&mut parser, "Expected a file extension")?);
+    }
+
+    let filter = if parser.eat_keyword("where") {
+        Some(parser.parse_expr()?)
+    } else {
+        None
+    };
+
+    let mut group_by = Vec::new();
+    if parser.eat_keyword("group") {
+        parser.expect_keyword("by")?;
+        group_by.push(parser.parse_expr()?);
+        while parser.eat_symbol(",") {
+            group_by.push(parser.parse_expr()?);
+        }
+    }
+
+    let having = if parser.eat_keyword("having") {
+        Some(parser.parse_expr()?)
+    } else {
+        None
+    };
+
+    let mut order_by = Vec::new();
+    if parser.eat_keyword("order") {
+        parser.expect_keyword("by")?;
+        loop {
+            let pos = parser.peek().pos;
+            let target = match parser.parse_expr()? {
+                Expr::Literal(Scalar::Num(n)) if n >= 1.0 && n.fract() == 0.0 => {
+                    OrderTarget::Position(n as usize)
+                }
+                Expr::Literal(Scalar::Num(_)) => {
+                    return Err(ExprError::new(pos, "ORDER BY position must be 1 or more"));
+                }
+                expr => OrderTarget::Expr(expr),
+            };
+            let descending = if parser.eat_keyword("desc") {
+                true
+            } else {
+                parser.eat_keyword("asc");
+                false
+            };
+            order_by.push(OrderKey {
+                target,
+                descending,
+                pos,
+            });
+            if !parser.eat_symbol(",") {
+                break;
+            }
+        }
+    }
+
+    let limit = if parser.eat_keyword("limit") {
+        Some(count(&mut parser)?)
+    } else {
+        None
+    };
+    let offset = if parser.eat_keyword("offset") {
+        count(&mut parser)?
+    } else {
+        0
+    };
+
+    parser.expect_end()?;
+    Ok(Query {
+        distinct,
+        items,
+        from,
+        from_pos,
+        filter,
+        group_by,
+        having,
+        order_by,
+        limit,
+        offset,
+    })
+}
+
+/// Consume a plain or backtick-quoted identifier
+fn identifier(parser: &mut Parser, message: &str) -> Result<String, ExprError> {
+    match &parser.peek().kind {
+        TokenKind::Ident(word) | TokenKind::QuotedIdent(word) => {
+            let word = word.clone();
+            parser.advance();
+            Ok(word)
+        }
+        _ => Err(parser.unexpected(message)),
+    }
+}
+
+/// Consume a non-negative whole number, as used by LIMIT and OFFSET
+fn count(parser: &mut Parser) -> Result<usize, ExprError> {
+    match parser.peek().kind {
+        TokenKind::Number(n) if n >= 0.0 && n.fract() == 0.0 => {
+            parser.advance();
+            Ok(n as usize)
+        }
+        _ => Err(parser.unexpected("Expected a whole number")),
+    }
+}
+
+/// Bound ORDER BY key
+enum SortKey {
+    /// Index of an output column
+    Output(usize),
+    /// Expression over the source rows
+    Expr(Expr),
+}
+
+/// An output row together with the source rows it was computed from
+struct OutputRow<'a> {
+    cells: Vec<String>,
+    sources: Vec<&'a Vec<String>>,
+}
+
+impl Query {
+    /// Whether the query computes aggregates, so rows are grouped
+    fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty()
+            || self.having.is_some()
+            || self.items.iter().any(|item| match item {
+                SelectItem::Expr { expr, .. } => expr.find_aggregate().is_some(),
+                SelectItem::Wildcard { .. } => false,
+            })
+            || self.order_by.iter().any(|key| match &key.target {
+                OrderTarget::Expr(expr) => expr.find_aggregate().is_some(),
+                OrderTarget::Position(_) => false,
+            })
+    }
+
+    /// Run the query against a table
+    pub fn execute(
+        &self,
+        headers: &[String],
+        rows: &[Vec<String>],
+    ) -> Result<ResultTable, ExprError> {
+        let grouped = self.is_grouped();
+        let bind = |expr: &Expr| -> Result<Expr, ExprError> {
+            let mut expr = expr.clone();
+            expr.bind(headers)?;
+            Ok(expr)
+        };
+
+        let filter = self.filter.as_ref().map(bind).transpose()?;
+        if let Some(pos) = filter.as_ref().and_then(Expr::find_aggregate) {
+            return Err(ExprError::new(pos, "Aggregates are not allowed in WHERE"));
+        }
+        let group_by = self
+            .group_by
+            .iter()
+            .map(bind)
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(pos) = group_by.iter().find_map(Expr::find_aggregate) {
+            return Err(ExprError::new(
+                pos,
+                "Aggregates are not allowed in GROUP BY",
+            ));
+        }
+        let having = self.having.as_ref().map(bind).transpose()?;
+
+        let mut out_headers = Vec::new();
+        let mut columns: Vec<Option<Expr>> = Vec::new();
+        for item in &self.items {
+            match item {
+                SelectItem::Wildcard { pos } if grouped => {
+                    return Err(ExprError::new(
+                        *pos,
+                        "* cannot be used with GROUP BY or aggregates",
+                    ));
+                }
+                SelectItem::Wildcard { .. } => {
+                    out_headers.extend(headers.iter().cloned());
+                    columns.push(None);
+                }
+                SelectItem::Expr { expr, name } => {
+                    out_headers.push(name.clone());
+                    columns.push(Some(bind(expr)?));
+                }
+            }
+        }
+
+        let order_keys = self
+            .order_by
+            .iter()
+            .map(|key| match &key.target {
+                OrderTarget::Position(n) if *n > out_headers.len() => Err(ExprError::new(
+                    key.pos,
+                    format!("ORDER BY position {} is out of range", n),
+                )),
+                OrderTarget::Position(n) => Ok(SortKey::Output(n - 1)),
+                OrderTarget::Expr(expr @ Expr::Column { name, .. }) if !headers.contains(name) => {
+                    match out_headers.iter().position(|h| h == name) {
+                        Some(col) => Ok(SortKey::Output(col)),
+                        None => bind(expr).map(SortKey::Expr),
+                    }
+                }
+                OrderTarget::Expr(expr) => bind(expr).map(SortKey::Expr),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let matching: Vec<&Vec<String>> = rows
+            .iter()
+            .filter(|row| filter.as_ref().is_none_or(|f| f.eval(row).is_truthy()))
+            .collect();
+
+        let mut output: Vec<OutputRow> = if grouped {
+            let mut order: Vec<Vec<String>> = Vec::new();
+            let mut groups: HashMap<Vec<String>, Vec<&Vec<String>>> = HashMap::new();
+            for row in matching {
+                let key: Vec<String> = group_by.iter().map(|e| e.eval(row).to_cell()).collect();
+                groups
+                    .entry(key.clone())
+                    .or_insert_with(|| {
+                        order.push(key);
+                        Vec::new()
+                    })
+                    .push(row);
+            }
+            if group_by.is_empty() && order.is_empty() {
+                order.push(Vec::new());
+                groups.insert(Vec::new(), Vec::new());
+            }
+
+            order
+                .into_iter()
+                .filter_map(|key| groups.remove(&key))
+                .filter(|group| {
+                    having
+                        .as_ref()
+                        .is_none_or(|h| h.eval_group(group).is_truthy())
+                })
+                .map(|group| OutputRow {
+                    cells: columns
+                        .iter()
+                        .flatten()
+                        .map(|e| e.eval_group(&group).to_cell())
+                        .collect(),
+                    sources: group,
+                })
+                .collect()
+        } else {
+            matching
+                .into_iter()
+                .map(|row| OutputRow {
+                    cells: columns
+                        .iter()
+                        .flat_map(|column| match column {
+                            Some(expr) => vec![expr.eval(row).to_cell()],
+                            None => row.clone(),
+                        })
+                        .collect(),
+                    sources: vec![row],
+                })
+                .collect()
+        };
+
+        if !order_keys.is_empty() {
+            let sort_value = |row: &OutputRow, key: &SortKey| match key {
+                SortKey::Output(col) => Scalar::from_cell(&row.cells[*col]),
+                SortKey::Expr(expr) if grouped => expr.eval_group(&row.sources),
+                SortKey::Expr(expr) => expr.eval(row.sources[0]),
+            };
+            let mut keyed: Vec<(Vec<Scalar>, OutputRow)> = output
+                .into_iter()
+                .map(|row| {
+                    let values = order_keys.iter().map(|k| sort_value(&row, k)).collect();
+                    (values, row)
+                })
+                .collect();
+            keyed.sort_by(|(a, _), (b, _)| {
+                a.iter()
+                    .zip(b)
+                    .zip(&self.order_by)
+                    .map(|((a, b), key)| {
+                        let ordering = a.compare(b);
+                        if key.descending {
+                            ordering.reverse()
+                        } else {
+                            ordering
+                        }
+                    })
+                    .find(|o| *o != Ordering::Equal)
+                    .unwrap_or(Ordering::Equal)
+            });
+            output = keyed.into_iter().map(|(_, row)| row).collect();
+        }
+
+        let mut out_rows: Vec<Vec<String>> = output.into_iter().map(|row| row.cells).collect();
+        if self.distinct {
+            let mut seen = std::collections::HashSet::new();
+            out_rows.retain(|row| seen.insert(row.clone()));
+        }
+        let out_rows = out_rows
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(ResultTable {
+            headers: out_headers,
+            rows: out_rows,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pivot::Aggregate;
+
+    fn table() -> (Vec<String>, Vec<Vec<String>>) {
+        let headers = ["host", "status", "bytes"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let rows = [
+            ["web1", "200", "100"],
+            ["web2", "503", "50"],
+            ["web1", "500", "300"],
+            ["web3", "502", "10"],
+            ["web2", "500", "70"],
+        ]
+        .iter()
+        .map(|r| r.iter().map(|s| s.to_string()).collect())
+        .collect();
+        (headers, rows)
+    }
+
+    fn run(sql: &str) -> ResultTable {
+        let (headers, rows) = table();
+        parse(sql).unwrap().execute(&headers, &rows).unwrap()
+    }
+
+    #[test]
+    fn test_group_by_with_order_by_position() {
+        let result = run(
+            "SELECT host, count(*) FROM current WHERE status >= 500 GROUP BY host ORDER BY 2 DESC",
+        );
+        assert_eq!(result.headers, vec!["host", "count(*)"]);
+        assert_eq!(
+            result.rows,
+            vec![vec!["web2", "2"], vec!["web1", "1"], vec!["web3", "1"]]
+        );
+    }
+
+    #[test]
+    fn test_projection_alias_and_limit() {
+        let result =
+            run("select *, bytes / 10 as kb from current order by kb desc limit 2 offset 1");
+        assert_eq!(result.headers, vec!["host", "status", "bytes", "kb"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                vec!["web1", "200", "100", "10"],
+                vec!["web2", "500", "70", "7"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aggregates_without_group_by_and_having() {
+        let result = run("SELECT count(*) n, sum(bytes) FROM current WHERE host = 'none'");
+        assert_eq!(result.rows, vec![vec!["0", "0"]]);
+
+        // Aggregates stay numbers and print the way the pivot view does
+        let result = run("SELECT avg(bytes) / 7, max(host), avg(host) FROM current");
+        assert_eq!(result.rows, vec![vec!["15.1429", "web3", ""]]);
+        let rows: Vec<Vec<String>> = ["1", "2", "2"]
+            .iter()
+            .map(|v| vec![v.to_string()])
+            .collect();
+        let result = parse("SELECT avg(v) FROM current")
+            .unwrap()
+            .execute(&["v".to_string()], &rows)
+            .unwrap();
+        let pivot = Aggregate::Avg.apply(rows.iter().map(|r| r[0].as_str()));
+        assert_eq!(result.rows, vec![vec![pivot.as_str()]]);
+        assert_eq!(pivot, "1.6667");
+
+        let result =
+            run("SELECT host FROM current GROUP BY host HAVING sum(bytes) > 100 ORDER BY host");
+        assert_eq!(result.rows, vec![vec!["web1"], vec!["web2"]]);
+    }
+
+    #[test]
+    fn test_distinct_and_file_name_source() {
+        let query = parse("SELECT DISTINCT host FROM events.csv ORDER BY host DESC").unwrap();
+        assert_eq!(query.from, "events.csv");
+        let (headers, rows) = table();
+        let result = query.execute(&headers, &rows).unwrap();
+        assert_eq!(result.rows, vec![vec!["web3"], vec!["web2"], vec!["web1"]]);
+    }
+
+    #[test]
+    fn test_order_by_mixed_numbers_and_text() {
+        // Enough rows for the sort to notice an inconsistent comparison
+        let headers = vec!["v".to_string()];
+        let rows: Vec<Vec<String>> = ["10", "9", "1a", "", "x", "2.5"]
+            .iter()
+            .cycle()
+            .take(60)
+            .map(|v| vec![v.to_string()])
+            .collect();
+        let result = parse("SELECT DISTINCT v FROM current ORDER BY v")
+            .unwrap()
+            .execute(&headers, &rows)
+            .unwrap();
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![""],
+                vec!["2.5"],
+                vec!["9"],
+                vec!["10"],
+                vec!["1a"],
+                vec!["x"]
+            ]
+        );
+
+        let result = parse("SELECT v FROM current ORDER BY v DESC LIMIT 11")
+            .unwrap()
+            .execute(&headers, &rows)
+            .unwrap();
+        assert_eq!(result.rows[0], ["x"]);
+        assert_eq!(result.rows[10], ["1a"]);
+    }
+
+    #[test]
+    fn test_errors_have_positions() {
+        let err = parse("SELECT host FROM").unwrap_err();
+        assert_eq!(err.pos, 16);
+
+        let err = parse("SELECT host, FROM current").unwrap_err();
+        assert_eq!(err.pos, 13);
+
+        let (headers, rows) = table();
+        let err = parse("SELECT hots FROM current")
+            .unwrap()
+            .execute(&headers, &rows)
+            .unwrap_err();
+        assert_eq!(err, ExprError::new(7, "Unknown column 'hots'"));
+
+        let err = parse("SELECT host FROM current ORDER BY 3")
+            .unwrap()
+            .execute(&headers, &rows)
+            .unwrap_err();
+        assert_eq!(err.pos, 34);
+    }
+}
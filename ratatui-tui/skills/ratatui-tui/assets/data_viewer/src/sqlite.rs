@@ -0,0 +1,287 @@
+//! Read-only browsing of SQLite databases
+//!
+//! A database file is listed with its tables and views as children, each
+//! addressed by a path under the file (`app.db/users`) so the rest of the
+//! viewer can treat it like any other file. Tables are read a page at a time
+//! with `LIMIT`/`OFFSET`, so big tables never load fully.
+
+use anyhow::{Context, Result};
+use rusqlite::{types::ValueRef, Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+
+/// Rows read from a table at a time
+pub const PAGE_ROWS: usize = 1000;
+
+/// Kind of a database object listed under its file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    View,
+}
+
+impl ObjectKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ObjectKind::Table => "SQLite table",
+            ObjectKind::View => "SQLite view",
+        }
+    }
+}
+
+/// A table or view of a database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbObject {
+    pub name: String,
+    pub kind: ObjectKind,
+}
+
+/// The rows of a table currently loaded, out of all of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableWindow {
+    pub db: PathBuf,
+    pub name: String,
+    pub kind: ObjectKind,
+    /// Index of the first loaded row in the table
+    pub offset: usize,
+    /// Number of rows in the table
+    pub total: usize,
+}
+
+/// One page of a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Whether a file is named like a SQLite database
+pub fn is_database(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    matches!(ext.as_str(), "sqlite" | "sqlite3" | "db" | "db3")
+}
+
+/// Path addressing a table or view of a database
+pub fn object_path(db: &Path, name: &str) -> PathBuf {
+    db.join(name)
+}
+
+/// Database and object name of a path made by [`object_path`]
+pub fn split_object_path(path: &Path) -> Option<(&Path, String)> {
+    let db = path.parent()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    (is_database(db) && db.is_file()).then_some((db, name))
+}
+
+fn open(db: &Path) -> Result<Connection> {
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    Connection::open_with_flags(db, flags)
+        .with_context(|| format!("Failed to open {} as a SQLite database", db.display()))
+}
+
+/// Quote an identifier for use in SQL
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Tables and views of a database, by name, without SQLite's own tables
+pub fn objects(db: &Path) -> Result<Vec<DbObject>> {
+    let conn = open(db)?;
+    let mut statement = conn.prepare(
+        "SELECT name, type FROM sqlite_master \
+         WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let objects = statement
+        .query_map([], |row| {
+            let kind = match row.get::<_, String>(1)?.as_str() {
+                "view" => ObjectKind::View,
+                _ => ObjectKind::Table,
+            };
+            Ok(DbObject {
+                name: row.get(0)?,
+                kind,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()
+        .with_context(|| format!("Failed to list the tables of {}", db.display()))?;
+    Ok(objects)
+}
+
+/// Kind of the named object, if the database has it
+pub fn object_kind(db: &Path, name: &str) -> Result<Option<ObjectKind>> {
+    Ok(objects(db)?
+        .into_iter()
+        .find(|object| object.name == name)
+        .map(|object| object.kind))
+}
+
+/// Number of rows of a table or view
+pub fn count(db: &Path, name: &str) -> Result<usize> {
+    let conn = open(db)?;
+    let sql = format!("SELECT count(*) FROM {}", quote(name));
+    let count: i64 = conn
+        .query_row(&sql, [], |row| row.get(0))
+        .with_context(|| format!("Failed to count the rows of {}", name))?;
+    Ok(count as usize)
+}
+
+/// Up to `limit` rows of a table or view from row `offset` on, as text
+///
+/// NULL becomes an empty cell and blobs show their size.
+pub fn read_page(db: &Path, name: &str, offset: usize, limit: usize) -> Result<Page> {
+    let conn = open(db)?;
+    let sql = format!("SELECT * FROM {} LIMIT ?1 OFFSET ?2", quote(name));
+    let mut statement = conn
+        .prepare(&sql)
+        .with_context(|| format!("Failed to read {}", name))?;
+    let headers: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let mut query = statement.query([limit as i64, offset as i64])?;
+    let mut rows = Vec::new();
+    while let Some(row) = query.next()? {
+        let cells = (0..headers.len())
+            .map(|i| row.get_ref(i).map(cell_text))
+            .collect::<rusqlite::Result<_>>()?;
+        rows.push(cells);
+    }
+    Ok(Page { headers, rows })
+}
+
+fn cell_text(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(x) => x.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).to_string(),
+        ValueRef::Blob(bytes) => format!("<blob, {} bytes>", bytes.len()),
+    }
+}
+
+/// Schema of a table or view as text: its columns, indexes and the
+/// statement that created it
+pub fn describe(db: &Path, name: &str) -> Result<String> {
+    let conn = open(db)?;
+    let mut lines = vec!["Columns".to_string()];
+    let mut statement = conn.prepare(&format!("PRAGMA table_info({})", quote(name)))?;
+    let columns = statement
+        .query_map([], |row| {
+            let name: String = row.get(1)?;
+            let kind: String = row.get(2)?;
+            let not_null: bool = row.get(3)?;
+            let default: Option<String> = row.get(4)?;
+            let key: i64 = row.get(5)?;
+            let mut notes = Vec::new();
+            if key > 0 {
+                notes.push("primary key".to_string());
+            }
+            if not_null {
+                notes.push("not null".to_string());
+            }
+            if let Some(default) = default {
+                notes.push(format!("default {}", default));
+            }
+            Ok((name, kind, notes.join(", ")))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let name_width = columns.iter().map(|(n, _, _)| n.len()).max().unwrap_or(0);
+    let type_width = columns.iter().map(|(_, t, _)| t.len()).max().unwrap_or(0);
+    for (column, kind, notes) in &columns {
+        let line = format!("  {:name_width$}  {:type_width$}  {}", column, kind, notes);
+        lines.push(line.trim_end().to_string());
+    }
+
+    let mut statement = conn.prepare(&format!("PRAGMA index_list({})", quote(name)))?;
+    let indexes = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(1)?, row.get::<_, bool>(2)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !indexes.is_empty() {
+        lines.push(String::new());
+        lines.push("Indexes".to_string());
+    }
+    for (index, unique) in indexes {
+        let mut statement = conn.prepare(&format!("PRAGMA index_info({})", quote(&index)))?;
+        let columns = statement
+            .query_map([], |row| row.get::<_, Option<String>>(2))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let columns: Vec<String> = columns
+            .into_iter()
+            .map(|c| c.unwrap_or_else(|| "<expression>".to_string()))
+            .collect();
+        let unique = if unique { " unique" } else { "" };
+        lines.push(format!("  {} ({}){}", index, columns.join(", "), unique));
+    }
+
+    let sql: Option<String> = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE name = ?1",
+            [name],
+            |row| row.get(0),
+        )
+        .with_context(|| format!("No table or view named {}", name))?;
+    if let Some(sql) = sql {
+        lines.push(String::new());
+        lines.push(sql);
+    }
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_browse_database() {
+        let dir = std::env::temp_dir().join(format!("data_viewer_sqlite_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("shop.db");
+        let _ = fs::remove_file(&db);
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE items (id INTEGER PRIMARY KEY, \"the name\" TEXT NOT NULL, price REAL, data BLOB);
+             CREATE UNIQUE INDEX items_name ON items (\"the name\");
+             CREATE VIEW cheap AS SELECT id FROM items WHERE price < 2;
+             INSERT INTO items VALUES (1, 'pen', 1.5, x'0102'), (2, 'ink', NULL, NULL), (3, 'pad', 3.25, NULL);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let objects = objects(&db).unwrap();
+        let names: Vec<_> = objects.iter().map(|o| (o.name.as_str(), o.kind)).collect();
+        assert_eq!(
+            names,
+            [("cheap", ObjectKind::View), ("items", ObjectKind::Table)]
+        );
+
+        let path = object_path(&db, "items");
+        assert_eq!(
+            split_object_path(&path),
+            Some((db.as_path(), "items".to_string()))
+        );
+        assert_eq!(split_object_path(&dir.join("items")), None);
+
+        assert_eq!(count(&db, "items").unwrap(), 3);
+        let page = read_page(&db, "items", 1, 5).unwrap();
+        assert_eq!(page.headers, ["id", "the name", "price", "data"]);
+        assert_eq!(page.rows, [["2", "ink", "", ""], ["3", "pad", "3.25", ""]]);
+        let page = read_page(&db, "items", 0, 1).unwrap();
+        assert_eq!(page.rows, [["1", "pen", "1.5", "<blob, 2 bytes>"]]);
+        assert_eq!(read_page(&db, "cheap", 0, 10).unwrap().rows, [["1"]]);
+
+        let schema = describe(&db, "items").unwrap();
+        assert!(schema.contains("  id        INTEGER  primary key\n"));
+        assert!(schema.contains("  the name  TEXT     not null\n"));
+        assert!(schema.contains("items_name (the name) unique"));
+        assert!(schema.ends_with("data BLOB)"));
+        assert!(read_page(&db, "missing", 0, 10).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
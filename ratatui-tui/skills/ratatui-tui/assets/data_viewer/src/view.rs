@@ -0,0 +1,482 @@
+//! View settings of the table: row filter, sort order, column layout and
+//! computed columns
+//!
+//! Settings refer to columns by name, so they survive reloading a file and
+//! can be saved with the session. They never change the loaded data; they
+//! only decide which rows and columns are shown, and in what order.
+//!
+//! Computed columns follow the loaded ones in column numbering. Their cells
+//! are evaluated when shown, and filters and sorts that use them read the
+//! inlined expression instead of a stored value.
+
+use crate::expr::{self, Expr, ExprError, Parser, Scalar, TokenKind};
+use serde::{Deserialize, Serialize};
+
+/// Column the table is sorted by
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortOrder {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// Column whose cells are computed from other cells of the same row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComputedColumn {
+    pub name: String,
+    /// Expression over the loaded columns and earlier computed ones, e.g.
+    /// `bytes / 1024`
+    pub expr: String,
+}
+
+/// Computed columns bound to the loaded columns of a file
+#[derive(Debug, Clone)]
+pub struct Computed {
+    /// Number of loaded columns; computed column `i` is column `base + i`
+    pub base: usize,
+    pub names: Vec<String>,
+    /// Expressions over loaded columns only, `None` for a column that no
+    /// longer binds (e.g. after the file lost a column it uses)
+    exprs: Vec<Option<Expr>>,
+}
+
+impl Computed {
+    /// Name of a loaded or computed column
+    pub fn header<'a>(&'a self, headers: &'a [String], column: usize) -> &'a str {
+        match column.checked_sub(self.base) {
+            Some(i) => &self.names[i],
+            None => &headers[column],
+        }
+    }
+
+    /// Value of a loaded or computed cell of a row
+    pub fn value(&self, row: &[String], column: usize) -> Scalar {
+        match column.checked_sub(self.base) {
+            Some(i) => self.exprs[i].as_ref().map_or(Scalar::Null, |e| e.eval(row)),
+            None => Scalar::from_cell(row.get(column).map_or("", |c| c.as_str())),
+        }
+    }
+
+    /// Text of a loaded or computed cell of a row
+    pub fn cell(&self, row: &[String], column: usize) -> String {
+        match column.checked_sub(self.base) {
+            Some(_) => self.value(row, column).to_cell(),
+            None => row.get(column).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Parse an expression that may use computed columns and rewrite it to
+    /// read loaded columns only
+    fn parse(&self, input: &str, headers: &[String]) -> Result<Expr, ExprError> {
+        let mut expr = expr::parse(input, &self.all_headers(headers))?;
+        expr.inline(self.base, &self.exprs);
+        Ok(expr)
+    }
+
+    fn all_headers(&self, headers: &[String]) -> Vec<String> {
+        headers.iter().chain(&self.names).cloned().collect()
+    }
+}
+
+/// How the loaded data is shown in the table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewSettings {
+    /// Expression rows must satisfy, e.g. `status >= 500 and host like 'web%'`
+    pub filter: Option<String>,
+    pub sort: Option<SortOrder>,
+    /// Names of columns left out of the table
+    pub hidden_columns: Vec<String>,
+    /// Column names in display order; columns not listed follow in file order
+    pub column_order: Vec<String>,
+    /// Columns kept at the left edge while the table scrolls sideways
+    pub pinned_columns: Vec<String>,
+    pub computed_columns: Vec<ComputedColumn>,
+}
+
+impl ViewSettings {
+    /// Whether the filter or sort order changes which rows are shown
+    pub fn changes_rows(&self) -> bool {
+        self.filter.is_some() || self.sort.is_some()
+    }
+
+    /// Loaded data rows to show, in display order
+    ///
+    /// Starts from `subset` when given (e.g. the marked rows) and from every
+    /// row otherwise. Sorting is stable, so equal values keep their order.
+    pub fn rows(
+        &self,
+        headers: &[String],
+        rows: &[Vec<String>],
+        subset: Option<&[usize]>,
+    ) -> Result<Vec<usize>, ExprError> {
+        let mut indices: Vec<usize> = match subset {
+            Some(subset) => subset.to_vec(),
+            None => (0..rows.len()).collect(),
+        };
+        let computed = self.computed(headers);
+
+        if let Some(filter) = &self.filter {
+            let expr = computed.parse(filter, headers)?;
+            indices.retain(|&i| expr.eval(&rows[i]).is_truthy());
+        }
+
+        if let Some(sort) = &self.sort {
+            let column = self
+                .all_headers(headers)
+                .iter()
+                .position(|h| *h == sort.column)
+                .ok_or_else(|| {
+                    ExprError::new(0, format!("Unknown sort column '{}'", sort.column))
+                })?;
+            // Computed keys are evaluated once per row rather than per comparison
+            let mut keyed: Vec<(Scalar, usize)> = indices
+                .into_iter()
+                .map(|i| (computed.value(&rows[i], column), i))
+                .collect();
+            keyed.sort_by(|(a, _), (b, _)| {
+                let order = a.compare(b);
+                if sort.descending {
+                    order.reverse()
+                } else {
+                    order
+                }
+            });
+            indices = keyed.into_iter().map(|(_, i)| i).collect();
+        }
+
+        Ok(indices)
+    }
+
+    /// Names of the loaded columns followed by the computed ones
+    pub fn all_headers(&self, headers: &[String]) -> Vec<String> {
+        let names = self.computed_columns.iter().map(|c| c.name.clone());
+        headers.iter().cloned().chain(names).collect()
+    }
+
+    /// Bind the computed columns to the loaded columns `headers`
+    ///
+    /// Each computed column may use the loaded columns and the computed
+    /// columns before it.
+    pub fn computed(&self, headers: &[String]) -> Computed {
+        let mut computed = Computed {
+            base: headers.len(),
+            names: Vec::new(),
+            exprs: Vec::new(),
+        };
+        for column in &self.computed_columns {
+            let expr = computed.parse(&column.expr, headers).ok();
+            computed.names.push(column.name.clone());
+            computed.exprs.push(expr);
+        }
+        computed
+    }
+
+    /// Add a computed column from input like `bytes / 1024 as kb`; without
+    /// `as`, the expression itself names the column
+    pub fn add_computed(&mut self, headers: &[String], input: &str) -> Result<(), ExprError> {
+        let mut parser = Parser::new(input)?;
+        parser.parse_expr()?;
+        let (expr, name, name_pos) = if parser.at_keyword("as") {
+            let as_pos = parser.advance().pos;
+            let token = parser.peek().clone();
+            let name = match token.kind {
+                TokenKind::Ident(name) | TokenKind::QuotedIdent(name) => name,
+                _ => return Err(parser.unexpected("Expected a column name")),
+            };
+            parser.advance();
+            parser.expect_end()?;
+            let expr: String = input.chars().take(as_pos).collect();
+            (expr.trim().to_string(), name, token.pos)
+        } else {
+            parser.expect_end()?;
+            (input.trim().to_string(), input.trim().to_string(), 0)
+        };
+
+        if self.all_headers(headers).contains(&name) {
+            return Err(ExprError::new(
+                name_pos,
+                format!("Column '{}' already exists", name),
+            ));
+        }
+        self.computed(headers).parse(&expr, headers)?;
+        self.computed_columns.push(ComputedColumn { name, expr });
+        Ok(())
+    }
+
+    /// Remove a computed column unless a later computed column uses it
+    pub fn remove_computed(&mut self, headers: &[String], name: &str) -> Result<(), String> {
+        let Some(index) = self.computed_columns.iter().position(|c| c.name == name) else {
+            return Ok(());
+        };
+        let mut view = self.clone();
+        view.computed_columns.remove(index);
+        let computed = view.computed(headers);
+        if let Some(user) = view.computed_columns[index..]
+            .iter()
+            .zip(&computed.exprs[index..])
+            .find(|(_, expr)| expr.is_none())
+        {
+            return Err(format!("Column '{}' uses '{}'", user.0.name, name));
+        }
+        let name = name.to_string();
+        view.hidden_columns.retain(|c| *c != name);
+        view.column_order.retain(|c| *c != name);
+        view.pinned_columns.retain(|c| *c != name);
+        if view.sort.as_ref().is_some_and(|s| s.column == name) {
+            view.sort = None;
+        }
+        *self = view;
+        Ok(())
+    }
+
+    /// Indices of every loaded and computed column in display order, pinned
+    /// columns first
+    pub fn ordered_columns(&self, headers: &[String]) -> Vec<usize> {
+        let headers = &self.all_headers(headers);
+        let mut order: Vec<usize> = self
+            .column_order
+            .iter()
+            .filter_map(|name| headers.iter().position(|h| h == name))
+            .collect();
+        let rest: Vec<usize> = (0..headers.len()).filter(|i| !order.contains(i)).collect();
+        order.extend(rest);
+        // Stable, so pinned columns keep their relative order
+        order.sort_by_key(|&i| !self.pinned_columns.contains(&headers[i]));
+        order
+    }
+
+    /// Indices of the columns to show, in display order
+    pub fn visible_columns(&self, headers: &[String]) -> Vec<usize> {
+        let all_headers = self.all_headers(headers);
+        self.ordered_columns(headers)
+            .into_iter()
+            .filter(|&i| !self.hidden_columns.contains(&all_headers[i]))
+            .collect()
+    }
+
+    /// Number of leading visible columns that are pinned
+    pub fn pinned_count(&self, headers: &[String]) -> usize {
+        let all_headers = self.all_headers(headers);
+        self.visible_columns(headers)
+            .iter()
+            .take_while(|&&i| self.pinned_columns.contains(&all_headers[i]))
+            .count()
+    }
+
+    /// Move a column one place towards the end (`delta` 1) or the start
+    /// (`delta` -1) of the display order, staying within the pinned or
+    /// unpinned group
+    pub fn move_column(&mut self, headers: &[String], column: &str, delta: isize) {
+        let all_headers = self.all_headers(headers);
+        let mut order: Vec<String> = self
+            .ordered_columns(headers)
+            .into_iter()
+            .map(|i| all_headers[i].clone())
+            .collect();
+        let Some(from) = order.iter().position(|c| c == column) else {
+            return;
+        };
+        let Some(to) = from
+            .checked_add_signed(delta)
+            .filter(|&to| to < order.len())
+        else {
+            return;
+        };
+        if self.pinned_columns.contains(&order[from]) != self.pinned_columns.contains(&order[to]) {
+            return;
+        }
+        order.swap(from, to);
+        self.column_order = order;
+    }
+
+    /// Pin a column to the left edge, or unpin it
+    pub fn toggle_pinned(&mut self, column: &str) {
+        match self.pinned_columns.iter().position(|c| c == column) {
+            Some(index) => {
+                self.pinned_columns.remove(index);
+            }
+            None => self.pinned_columns.push(column.to_string()),
+        }
+    }
+
+    /// Show or hide a column
+    pub fn toggle_hidden(&mut self, column: &str) {
+        match self.hidden_columns.iter().position(|c| c == column) {
+            Some(index) => {
+                self.hidden_columns.remove(index);
+            }
+            None => self.hidden_columns.push(column.to_string()),
+        }
+    }
+
+    /// Step a column through ascending, descending and unsorted
+    pub fn cycle_sort(&mut self, column: &str) {
+        self.sort = match &self.sort {
+            Some(sort) if sort.column == column && !sort.descending => Some(SortOrder {
+                column: column.to_string(),
+                descending: true,
+            }),
+            Some(sort) if sort.column == column => None,
+            _ => Some(SortOrder {
+                column: column.to_string(),
+                descending: false,
+            }),
+        };
+    }
+
+    /// Parts of the table title describing the filter and sort order
+    pub fn describe(&self) -> Vec<String> {
+        let mut parts = Vec::new();
+        if let Some(filter) = &self.filter {
+            parts.push(format!("where {}", filter));
+        }
+        if let Some(sort) = &self.sort {
+            let arrow = if sort.descending { "↓" } else { "↑" };
+            parts.push(format!("by {} {}", sort.column, arrow));
+        }
+        parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> Vec<String> {
+        ["host", "status", "bytes"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn rows() -> Vec<Vec<String>> {
+        [
+            ["web1", "200", "900"],
+            ["web2", "500", "80"],
+            ["db1", "200", "1000"],
+            ["web1", "503", ""],
+        ]
+        .iter()
+        .map(|r| r.iter().map(|c| c.to_string()).collect())
+        .collect()
+    }
+
+    #[test]
+    fn test_filter_and_sort_rows() {
+        let mut view = ViewSettings {
+            filter: Some("host like 'web%'".to_string()),
+            ..ViewSettings::default()
+        };
+        assert_eq!(view.rows(&headers(), &rows(), None).unwrap(), [0, 1, 3]);
+
+        // Numeric sort, empty cells first, stable for ties
+        view.filter = None;
+        view.cycle_sort("bytes");
+        assert_eq!(view.rows(&headers(), &rows(), None).unwrap(), [3, 1, 0, 2]);
+        view.cycle_sort("bytes");
+        assert_eq!(view.rows(&headers(), &rows(), None).unwrap(), [2, 0, 1, 3]);
+        view.cycle_sort("status");
+        assert_eq!(
+            view.rows(&headers(), &rows(), Some(&[2, 1, 0])).unwrap(),
+            [2, 0, 1]
+        );
+        view.cycle_sort("status");
+        view.cycle_sort("status");
+        assert_eq!(view.sort, None);
+    }
+
+    #[test]
+    fn test_sort_mixed_numbers_and_text() {
+        // Numbers sort before text, so the order is consistent however the
+        // cells are mixed
+        let headers = vec!["v".to_string()];
+        let rows: Vec<Vec<String>> = ["10", "9", "1a"]
+            .iter()
+            .cycle()
+            .take(30)
+            .map(|v| vec![v.to_string()])
+            .collect();
+        let mut view = ViewSettings::default();
+        view.cycle_sort("v");
+        let sorted = view.rows(&headers, &rows, None).unwrap();
+        let cells: Vec<&str> = sorted.iter().map(|&i| rows[i][0].as_str()).collect();
+        assert_eq!(cells[..10], ["9"; 10]);
+        assert_eq!(cells[10..20], ["10"; 10]);
+        assert_eq!(cells[20..], ["1a"; 10]);
+        assert_eq!(sorted[..2], [1, 4]);
+    }
+
+    #[test]
+    fn test_errors_and_hidden_columns() {
+        let view = ViewSettings {
+            filter: Some("stat = 1".to_string()),
+            ..ViewSettings::default()
+        };
+        assert_eq!(view.rows(&headers(), &rows(), None).unwrap_err().pos, 0);
+
+        let mut view = ViewSettings::default();
+        view.toggle_hidden("status");
+        assert_eq!(view.visible_columns(&headers()), [0, 2]);
+        view.toggle_hidden("status");
+        assert_eq!(view.visible_columns(&headers()), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_column_order_and_pins() {
+        let mut view = ViewSettings::default();
+        view.move_column(&headers(), "host", 1);
+        assert_eq!(view.ordered_columns(&headers()), [1, 0, 2]);
+        view.move_column(&headers(), "status", -1);
+        assert_eq!(view.column_order, ["status", "host", "bytes"]);
+
+        // Pinned columns lead, and columns don't move across the pin boundary
+        view.toggle_pinned("bytes");
+        assert_eq!(view.ordered_columns(&headers()), [2, 1, 0]);
+        view.move_column(&headers(), "status", -1);
+        assert_eq!(view.ordered_columns(&headers()), [2, 1, 0]);
+        view.toggle_hidden("status");
+        assert_eq!(view.visible_columns(&headers()), [2, 0]);
+        assert_eq!(view.pinned_count(&headers()), 1);
+
+        // Unknown names are ignored, e.g. after the file gained other columns
+        view.column_order.insert(0, "gone".to_string());
+        assert_eq!(view.ordered_columns(&headers()), [2, 1, 0]);
+        view.move_column(&headers(), "bytes", -1);
+        assert_eq!(view.ordered_columns(&headers()), [2, 1, 0]);
+    }
+
+    #[test]
+    fn test_computed_columns() {
+        let mut view = ViewSettings::default();
+        view.add_computed(&headers(), "bytes / 100 as hundreds")
+            .unwrap();
+        view.add_computed(&headers(), "hundreds * 2").unwrap();
+        assert_eq!(
+            view.all_headers(&headers())[3..],
+            ["hundreds", "hundreds * 2"]
+        );
+        let err = view.add_computed(&headers(), "1 as host").unwrap_err();
+        assert_eq!(err.pos, 5);
+        assert!(view.add_computed(&headers(), "size / 2").is_err());
+
+        let computed = view.computed(&headers());
+        assert_eq!(computed.cell(&rows()[0], 4), "18");
+        assert_eq!(computed.cell(&rows()[0], 2), "900");
+        assert_eq!(computed.header(&headers(), 3), "hundreds");
+
+        // Filters and sorts see computed columns like loaded ones
+        view.filter = Some("`hundreds * 2` > 10".to_string());
+        view.cycle_sort("hundreds");
+        view.cycle_sort("hundreds");
+        assert_eq!(view.rows(&headers(), &rows(), None).unwrap(), [2, 0]);
+
+        assert_eq!(
+            view.remove_computed(&headers(), "hundreds").unwrap_err(),
+            "Column 'hundreds * 2' uses 'hundreds'"
+        );
+        view.remove_computed(&headers(), "hundreds * 2").unwrap();
+        view.remove_computed(&headers(), "hundreds").unwrap();
+        assert_eq!(view.sort, None);
+        assert_eq!(view.visible_columns(&headers()), [0, 1, 2]);
+    }
+}